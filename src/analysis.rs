@@ -0,0 +1,5 @@
+pub mod freq;
+pub mod ident;
+pub mod locus;
+pub mod spectral;
+pub mod tf;
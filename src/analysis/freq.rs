@@ -0,0 +1,355 @@
+use std::f64::consts::PI;
+
+use nalgebra::Complex;
+
+use crate::control::{Config, ConfigPid, ControlType, TypePid};
+use super::tf::{Poly, Tf};
+
+// Open and closed loop of one level of the cascade, inner loops closed.
+#[derive(Clone)]
+pub struct LoopTf{
+    open: Tf,
+    closed: Tf,
+}
+
+pub struct FreqResponse{
+    freq: Vec<f64>,
+    response: Vec<Complex<f64>>,
+    mag: Vec<f64>,
+    phase: Vec<f64>,
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct Margins{
+    gain_margin: Option<f64>,
+    phase_crossover: Option<f64>,
+    phase_margin: Option<f64>,
+    gain_crossover: Option<f64>,
+    bandwidth: Option<f64>,
+    sensitivity_peak: Option<f64>,
+    sensitivity_freq: Option<f64>,
+}
+
+impl LoopTf{
+    fn new(open: Tf) -> Self{
+        Self{closed: open.feedback(), open}
+    }
+
+    pub fn get_open(&self) -> &Tf{
+        &self.open
+    }
+
+    pub fn get_closed(&self) -> &Tf{
+        &self.closed
+    }
+}
+
+impl FreqResponse{
+    // Log spaced from f_min up to the Nyquist frequency, in hz.
+    pub fn new(tf: &Tf, frequency: f64, f_min: f64, points: usize) -> Self{
+        let f_max = frequency/2.0;
+        let ratio = (f_max/f_min).ln();
+        let freq: Vec<f64> = (0..points).map(|i| f_min*(ratio*i as f64/(points - 1) as f64).exp()).collect();
+        let response: Vec<Complex<f64>> = freq.iter().map(|f| tf.eval(unit_circle(2.0*PI*f/frequency))).collect();
+        FreqResponse::from_response(freq, response)
+    }
+
+    // freq in hz, ascending
+    pub fn from_response(freq: Vec<f64>, response: Vec<Complex<f64>>) -> Self{
+        let mag = response.iter().map(|h| 20.0*h.re.hypot(h.im).log10()).collect();
+        let phase = unwrap_phase(&response);
+        Self{freq, response, mag, phase}
+    }
+
+    pub fn get_freq(&self) -> &Vec<f64>{
+        &self.freq
+    }
+
+    pub fn get_response(&self) -> &Vec<Complex<f64>>{
+        &self.response
+    }
+
+    pub fn get_mag(&self) -> &Vec<f64>{
+        &self.mag
+    }
+
+    pub fn get_phase(&self) -> &Vec<f64>{
+        &self.phase
+    }
+
+    pub fn mag_as_vec(&self) -> Vec<[f64; 2]>{
+        self.freq.iter().zip(self.mag.iter()).map(|(f, m)| [f.log10(), *m]).collect()
+    }
+
+    pub fn phase_as_vec(&self) -> Vec<[f64; 2]>{
+        self.freq.iter().zip(self.phase.iter()).map(|(f, p)| [f.log10(), *p]).collect()
+    }
+
+    // Points with |response| above max_magnitude are dropped so the plot stays around -1.
+    pub fn nyquist_as_vec(&self, max_magnitude: f64) -> Vec<[f64; 2]>{
+        self.response.iter().filter(|h| h.re.hypot(h.im) <= max_magnitude).map(|h| [h.re, h.im]).collect()
+    }
+
+    // phase in deg against magnitude in dB
+    pub fn nichols_as_vec(&self) -> Vec<[f64; 2]>{
+        let shift = nichols_shift(&self.phase);
+        self.phase.iter().zip(self.mag.iter()).map(|(p, m)| [p + shift, *m]).collect()
+    }
+}
+
+impl Margins{
+    pub fn new(open: &FreqResponse, closed: &FreqResponse) -> Self{
+        let mut margins = Margins::default();
+        let interpolate = |values: &Vec<f64>, i: usize, ratio: f64| values[i] + ratio*(values[i + 1] - values[i]);
+
+        for i in 0..open.freq.len() - 1{
+            let (m0, m1) = (open.mag[i], open.mag[i + 1]);
+            if margins.gain_crossover.is_none() && m0 >= 0.0 && m1 < 0.0{
+                let ratio = m0/(m0 - m1);
+                margins.gain_crossover = Some(interpolate(&open.freq, i, ratio));
+                margins.phase_margin = Some(wrap_phase(interpolate(&open.phase, i, ratio)) + 180.0);
+            }
+            // crossing of -180 deg modulo 360
+            let (p0, p1) = ((open.phase[i] + 180.0)/360.0, (open.phase[i + 1] + 180.0)/360.0);
+            if margins.phase_crossover.is_none() && p0.floor() != p1.floor(){
+                let edge = p0.max(p1).floor();
+                let ratio = (edge - p0)/(p1 - p0);
+                margins.phase_crossover = Some(interpolate(&open.freq, i, ratio));
+                margins.gain_margin = Some(-interpolate(&open.mag, i, ratio));
+            }
+        }
+
+        let sensitivity = open.response.iter().map(|h|{
+            let return_difference = Complex::new(1.0, 0.0) + h;
+            1.0/return_difference.re.hypot(return_difference.im)
+        });
+        if let Some((i, peak)) = sensitivity.enumerate().max_by(|(_, a), (_, b)| a.total_cmp(b)){
+            margins.sensitivity_peak = Some(peak);
+            margins.sensitivity_freq = Some(open.freq[i]);
+        }
+
+        let reference = closed.mag[0];
+        margins.bandwidth = closed.mag.iter().position(|m| *m < reference - 3.0).map(|i| closed.freq[i]);
+        margins
+    }
+
+    // dB
+    pub fn get_gain_margin(&self) -> Option<f64>{
+        self.gain_margin
+    }
+
+    // hz
+    pub fn get_phase_crossover(&self) -> Option<f64>{
+        self.phase_crossover
+    }
+
+    // deg
+    pub fn get_phase_margin(&self) -> Option<f64>{
+        self.phase_margin
+    }
+
+    // hz
+    pub fn get_gain_crossover(&self) -> Option<f64>{
+        self.gain_crossover
+    }
+
+    // closed loop -3 dB, hz
+    pub fn get_bandwidth(&self) -> Option<f64>{
+        self.bandwidth
+    }
+
+    // Ms = max |1/(1+L)|
+    pub fn get_sensitivity_peak(&self) -> Option<f64>{
+        self.sensitivity_peak
+    }
+
+    // hz
+    pub fn get_sensitivity_freq(&self) -> Option<f64>{
+        self.sensitivity_freq
+    }
+}
+
+pub fn unit_circle(angle: f64) -> Complex<f64>{
+    Complex::new(angle.cos(), angle.sin())
+}
+
+// Locus of open loop values with closed loop magnitude |L/(1+L)| = m, m != 1.
+pub fn m_circle(m: f64, points: usize) -> Vec<Complex<f64>>{
+    let center = -m*m/(m*m - 1.0);
+    let radius = (m/(m*m - 1.0)).abs();
+    (0..=points).map(|i| Complex::new(center, 0.0) + unit_circle(2.0*PI*i as f64/points as f64)*radius).collect()
+}
+
+// Points at the distance 1/ms from -1, touched by the Nyquist curve when the sensitivity peak equals ms.
+pub fn ms_circle(ms: f64, points: usize) -> Vec<Complex<f64>>{
+    (0..=points).map(|i| Complex::new(-1.0, 0.0) + unit_circle(2.0*PI*i as f64/points as f64)/ms).collect()
+}
+
+// (phase deg, magnitude dB) of a curve of open loop values, for Nichols charts.
+pub fn to_nichols(values: &[Complex<f64>]) -> Vec<[f64; 2]>{
+    let phase = unwrap_phase(values);
+    let shift = nichols_shift(&phase);
+    phase.iter().zip(values.iter()).map(|(p, h)| [p + shift, 20.0*h.re.hypot(h.im).log10()]).collect()
+}
+
+// deg, continuous along the curve
+fn unwrap_phase(values: &[Complex<f64>]) -> Vec<f64>{
+    let mut phase: Vec<f64> = Vec::with_capacity(values.len());
+    for h in values.iter(){
+        let mut value = h.im.atan2(h.re).to_degrees();
+        if let Some(prev) = phase.last(){
+            while value - prev > 180.0{
+                value -= 360.0;
+            }
+            while value - prev < -180.0{
+                value += 360.0;
+            }
+        }
+        phase.push(value);
+    }
+    phase
+}
+
+// Multiple of 360 deg moving the largest phase of an unwrapped curve to (-360, 0].
+fn nichols_shift(phase: &[f64]) -> f64{
+    let max = phase.iter().copied().fold(f64::MIN, f64::max);
+    if max == f64::MIN {0.0} else {wrap_phase(max) - max}
+}
+
+// to (-360, 0]
+fn wrap_phase(phase: f64) -> f64{
+    let wrapped = phase.rem_euclid(360.0);
+    if wrapped > 0.0 {wrapped - 360.0} else {wrapped}
+}
+
+// Pid with trapezoidal integral and backward difference derivative, as in control::Pid.
+pub fn pid_tf(pid: &ConfigPid, delta: f64) -> Tf{
+    let (kp, ki, kd) = (pid.get_kp(), pid.get_ki(), pid.get_kd());
+    let integral = Poly::new(vec![ki*delta/2.0, ki*delta/2.0]);
+    match (ki != 0.0, kd != 0.0){
+        (false, false) => Tf::gain(kp),
+        (true, false) => Tf::new(&Poly::new(vec![-kp, kp]) + &integral, Poly::new(vec![-1.0, 1.0])),
+        (false, true) => Tf::new(Poly::new(vec![-kd/delta, kp + kd/delta]), Poly::new(vec![0.0, 1.0])),
+        (true, true) => Tf::new(&(&Poly::new(vec![0.0, -kp, kp]) + &(&integral*&Poly::new(vec![0.0, 1.0])))
+            + &Poly::new(vec![kd/delta, -2.0*kd/delta, kd/delta]), Poly::new(vec![0.0, -1.0, 1.0])),
+    }
+}
+
+// Trapezoidal integration of the velocity into the angle, as in Motor.
+fn integrator_tf(delta: f64) -> Tf{
+    Tf::new(Poly::new(vec![delta/2.0, delta/2.0]), Poly::new(vec![-1.0, 1.0]))
+}
+
+// Voltage to velocity (rad/s) and voltage to torque of the zero-order hold motor model.
+pub fn plant_tfs(config: &Config, delta: f64) -> (Tf, Tf){
+    let motor = config.get_motor_conf();
+    let (a, b, _) = motor.discretize(delta);
+    let den = Poly::new(vec![a[(0, 0)]*a[(1, 1)] - a[(0, 1)]*a[(1, 0)], -(a[(0, 0)] + a[(1, 1)]), 1.0]);
+    let vel = Poly::new(vec![-a[(1, 1)]*b[0] + a[(0, 1)]*b[1], b[0]]);
+    let current = Poly::new(vec![a[(1, 0)]*b[0] - a[(0, 0)]*b[1], b[1]]);
+    (Tf::new(vel, den.clone()), Tf::new(current.scale(motor.get_k()), den))
+}
+
+// Loops indexed as TypePid: angle, speed, torque. Units follow the controller: deg, rpm, N*m.
+pub fn loop_tfs(config: &Config) -> [Option<LoopTf>; 3]{
+    let delta = 1.0/config.get_controller_conf().get_frequency();
+    let pid_conf = config.get_pid_conf();
+    let (vel_plant, trq_plant) = plant_tfs(config, delta);
+    let rad_to_deg = 180.0/PI;
+
+    match config.get_controller_conf().get_control_option(){
+        ControlType::Pos => {
+            let pos_plant = (&vel_plant*&integrator_tf(delta)).scale(rad_to_deg);
+            [Some(LoopTf::new(&pid_tf(&pid_conf[TypePid::Pos as usize], delta)*&pos_plant)), None, None]
+        }
+        _ => {
+            let trq_pid = pid_tf(&pid_conf[TypePid::Trq as usize], delta);
+            let trq_open = &trq_pid*&trq_plant;
+            // torque reference to velocity, with the same denominator as the closed torque loop
+            let trq_to_vel = Tf::new(trq_pid.get_num()*vel_plant.get_num(), trq_open.feedback().get_den().clone());
+
+            let vel_open = (&pid_tf(&pid_conf[TypePid::Vel as usize], delta)*&trq_to_vel).scale(30.0/PI);
+            let vel_closed = vel_open.feedback();
+
+            // angle in deg integrates velocity in rpm with gain 6
+            let pos_plant = (&vel_closed*&integrator_tf(delta)).scale(6.0);
+            let pos_open = &pid_tf(&pid_conf[TypePid::Pos as usize], delta)*&pos_plant;
+
+            [Some(LoopTf::new(pos_open)), Some(LoopTf{open: vel_open, closed: vel_closed}), Some(LoopTf::new(trq_open))]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::control::Pid;
+
+    // Output of tf for input, zero initial conditions.
+    fn filter(tf: &Tf, input: &[f64]) -> Vec<f64>{
+        let (num, den) = (tf.get_num().get_coefs(), tf.get_den().get_coefs());
+        let n = den.len() - 1;
+        let mut output: Vec<f64> = vec![];
+        for k in 0..input.len(){
+            let past = |values: &[f64], i: usize| if k + i >= n {values.get(k + i - n).copied().unwrap_or(0.0)} else {0.0};
+            let forced: f64 = num.iter().enumerate().map(|(i, coef)| coef*past(input, i)).sum();
+            let free: f64 = den[..n].iter().enumerate().map(|(i, coef)| coef*past(&output, i)).sum();
+            output.push((forced - free)/den[n]);
+        }
+        output
+    }
+
+    #[test]
+    fn pid_matches_the_controller(){
+        let delta = 0.001;
+        let errors: Vec<f64> = (0..200).map(|k| (0.05*k as f64).sin() + if k > 50 {1.0} else {0.0}).collect();
+        for (kp, ki, kd) in [(2.0, 0.0, 0.0), (2.0, 30.0, 0.0), (2.0, 0.0, 0.01), (2.0, 30.0, 0.01)]{
+            let config = ConfigPid::new(kp, ki, kd, TypePid::Pos);
+            let mut pid = Pid::new(config);
+            let expected: Vec<f64> = errors.iter().map(|error| pid.generate_control(0.0, *error, delta, f64::MAX)).collect();
+            let output = filter(&pid_tf(&config, delta), &errors);
+            for (value, expected) in output.iter().zip(expected.iter()){
+                assert!((value - expected).abs() < 1e-9, "{} against {} for {:?}", value, expected, (kp, ki, kd));
+            }
+        }
+    }
+
+    #[test]
+    fn first_order_margins(){
+        // k*(1 - a)/(z*(z - a)), a first order lag with a period of delay
+        let (k, a, frequency) = (3.0, 0.9, 1000.0);
+        let open = Tf::new(Poly::constant(k*(1.0 - a)), Poly::new(vec![0.0, -a, 1.0]));
+        let margins = Margins::new(&FreqResponse::new(&open, frequency, 0.01, 20000), &FreqResponse::new(&open.feedback(), frequency, 0.01, 20000));
+
+        // |e^jw - a| = k*(1 - a)
+        let gain_w = ((1.0 + a*a - (k*(1.0 - a)).powi(2))/(2.0*a)).acos();
+        let lag = |w: f64| w + w.sin().atan2(w.cos() - a);
+        let phase_margin = 180.0 - lag(gain_w).to_degrees();
+        // w + arg(e^jw - a) = pi
+        let (mut low, mut high) = (gain_w, PI);
+        for _ in 0..60{
+            let mid = (low + high)/2.0;
+            if lag(mid) < PI {low = mid} else {high = mid}
+        }
+        let phase_w = low;
+        let lag_at_phase_w = unit_circle(phase_w) - Complex::new(a, 0.0);
+        let gain_margin = -20.0*(k*(1.0 - a)/lag_at_phase_w.re.hypot(lag_at_phase_w.im)).log10();
+
+        let hz = |w: f64| w*frequency/(2.0*PI);
+        assert!((margins.get_gain_crossover().unwrap() - hz(gain_w)).abs() < 1e-3*hz(gain_w));
+        assert!((margins.get_phase_margin().unwrap() - phase_margin).abs() < 0.05, "{:?} against {}", margins.get_phase_margin(), phase_margin);
+        assert!((margins.get_phase_crossover().unwrap() - hz(phase_w)).abs() < 1e-3*hz(phase_w));
+        assert!((margins.get_gain_margin().unwrap() - gain_margin).abs() < 0.01, "{:?} against {}", margins.get_gain_margin(), gain_margin);
+    }
+
+    #[test]
+    fn phase_wrapping(){
+        assert_eq!(wrap_phase(0.0), 0.0);
+        assert_eq!(wrap_phase(360.0), 0.0);
+        assert_eq!(wrap_phase(-90.0), -90.0);
+        assert_eq!(wrap_phase(270.0), -90.0);
+        let values: Vec<Complex<f64>> = (0..8).map(|i| unit_circle(-PI/3.0*i as f64)).collect();
+        let phase = unwrap_phase(&values);
+        assert!((phase[7] + 420.0).abs() < 1e-9);
+    }
+}
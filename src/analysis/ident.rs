@@ -0,0 +1,300 @@
+use std::f64::consts::PI;
+
+use nalgebra::{vector, DMatrix, DVector};
+
+use crate::control::motor::ConfigMotor;
+
+pub const PARAMETERS: [&str; 6] = ["j", "b", "k", "r", "l", "tl"];
+
+// Time series of a drive, velocity in rad/s. The voltage of a row is held until the next row.
+#[derive(Clone, Default)]
+pub struct Recording{
+    time: Vec<f64>,
+    voltage: Vec<f64>,
+    position: Vec<f64>,
+    velocity: Vec<f64>,
+    current: Vec<f64>,
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct Estimate{
+    value: f64,
+    std_err: f64,
+}
+
+pub struct Identification{
+    motor: ConfigMotor,
+    // ordered as PARAMETERS
+    estimates: [Estimate; 6],
+    vel_nrmse: f64,
+    current_nrmse: f64,
+    recording: Recording,
+    replay_velocity: Vec<f64>,
+    replay_current: Vec<f64>,
+}
+
+impl Recording{
+    // Columns: time s, voltage V, position deg, velocity rpm, current A.
+    // Comma or semicolon separated, header lines before the first numbers and blank lines are skipped.
+    pub fn from_csv(text: &str) -> Result<Self, String>{
+        let mut recording = Recording::default();
+        for (number, line) in text.lines().enumerate(){
+            if line.trim().is_empty(){
+                continue;
+            }
+            let values: Result<Vec<f64>, _> = line.split([',', ';']).map(|value| value.trim().parse::<f64>()).collect();
+            let values = match values{
+                Ok(values) => values,
+                Err(_) if recording.is_empty() => continue,
+                Err(error) => return Err(format!("line {}: {}", number + 1, error)),
+            };
+            if values.len() < 5{
+                return Err(format!("line {}: expected 5 columns, found {}", number + 1, values.len()));
+            }
+            if recording.time.last().is_some_and(|time| values[0] <= *time){
+                return Err(format!("line {}: time is not increasing", number + 1));
+            }
+            recording.time.push(values[0]);
+            recording.voltage.push(values[1]);
+            recording.position.push(values[2]);
+            recording.velocity.push(values[3]*PI/30.0);
+            recording.current.push(values[4]);
+        }
+        if recording.time.len() < 10{
+            return Err("at least 10 samples are needed".to_string());
+        }
+        Ok(recording)
+    }
+
+    pub fn len(&self) -> usize{
+        self.time.len()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.time.is_empty()
+    }
+
+    pub fn get_time(&self) -> &Vec<f64>{
+        &self.time
+    }
+
+    pub fn get_position(&self) -> &Vec<f64>{
+        &self.position
+    }
+}
+
+impl Estimate{
+    pub fn get_value(&self) -> f64{
+        self.value
+    }
+
+    pub fn get_std_err(&self) -> f64{
+        self.std_err
+    }
+
+    // half width of the 95% confidence interval
+    pub fn get_ci95(&self) -> f64{
+        1.96*self.std_err
+    }
+}
+
+impl Identification{
+    pub fn get_motor(&self) -> ConfigMotor{
+        self.motor
+    }
+
+    pub fn get_estimates(&self) -> &[Estimate; 6]{
+        &self.estimates
+    }
+
+    // rms replay error over the recorded range
+    pub fn get_vel_nrmse(&self) -> f64{
+        self.vel_nrmse
+    }
+
+    pub fn get_current_nrmse(&self) -> f64{
+        self.current_nrmse
+    }
+
+    // Parameters the simulator can run with.
+    pub fn is_physical(&self) -> bool{
+        let motor = &self.motor;
+        motor.get_j() > 0.0 && motor.get_b() >= 0.0 && motor.get_k() > 0.0 && motor.get_r() > 0.0 && motor.get_l() > 0.0
+    }
+
+    // (recorded, replayed) velocity in rpm
+    pub fn velocity_as_vec(&self) -> (Vec<[f64; 2]>, Vec<[f64; 2]>){
+        let to_rpm = |values: &Vec<f64>| self.recording.time.iter().zip(values.iter()).map(|(t, v)| [*t, v*30.0/PI]).collect();
+        (to_rpm(&self.recording.velocity), to_rpm(&self.replay_velocity))
+    }
+
+    // (recorded, replayed) current in A
+    pub fn current_as_vec(&self) -> (Vec<[f64; 2]>, Vec<[f64; 2]>){
+        let to_points = |values: &Vec<f64>| self.recording.time.iter().zip(values.iter()).map(|(t, i)| [*t, *i]).collect();
+        (to_points(&self.recording.current), to_points(&self.replay_current))
+    }
+}
+
+// Least squares fit of the motor equations over each sample period, with the voltage held
+// and the states averaged over the period as in the trapezoidal discretization:
+//   v = l*di/dt + r*i + k*w
+//   i = (j/k)*dw/dt + (b/k)*w + tl/k
+// Fit quality is the replay of the recorded voltage through the zero-order hold model.
+pub fn identify(recording: &Recording) -> Result<Identification, String>{
+    let n = recording.len() - 1;
+    let mut electrical = DMatrix::zeros(n, 3);
+    let mut mechanical = DMatrix::zeros(n, 3);
+    let mut voltage = DVector::zeros(n);
+    let mut current = DVector::zeros(n);
+    for row in 0..n{
+        let delta = recording.time[row + 1] - recording.time[row];
+        let (i0, i1) = (recording.current[row], recording.current[row + 1]);
+        let (w0, w1) = (recording.velocity[row], recording.velocity[row + 1]);
+        electrical[(row, 0)] = (i1 - i0)/delta;
+        electrical[(row, 1)] = (i0 + i1)/2.0;
+        electrical[(row, 2)] = (w0 + w1)/2.0;
+        voltage[row] = recording.voltage[row];
+        mechanical[(row, 0)] = (w1 - w0)/delta;
+        mechanical[(row, 1)] = (w0 + w1)/2.0;
+        mechanical[(row, 2)] = 1.0;
+        current[row] = (i0 + i1)/2.0;
+    }
+
+    let [l, r, k] = least_squares(&electrical, &voltage).ok_or("voltage equation is singular, the current and velocity need excitation")?;
+    let [j, b, tl] = least_squares(&mechanical, &current).ok_or("torque equation is singular, the velocity needs excitation")?;
+    // the torque equation is fitted divided by k, first order propagation of both errors
+    let scale = |estimate: Estimate| Estimate{value: estimate.value*k.value,
+        std_err: (k.value*estimate.std_err).hypot(estimate.value*k.std_err)};
+    let estimates = [scale(j), scale(b), k, r, l, scale(tl)];
+
+    let mut motor = ConfigMotor::default();
+    *motor.set_j() = estimates[0].value;
+    *motor.set_b() = estimates[1].value;
+    *motor.set_k() = estimates[2].value;
+    *motor.set_r() = estimates[3].value;
+    *motor.set_l() = estimates[4].value;
+    *motor.set_tl() = estimates[5].value;
+
+    let mut identification = Identification{motor, estimates, vel_nrmse: f64::NAN, current_nrmse: f64::NAN,
+        recording: recording.clone(), replay_velocity: vec![], replay_current: vec![]};
+    if identification.is_physical(){
+        replay(&mut identification);
+    }
+    Ok(identification)
+}
+
+fn replay(identification: &mut Identification){
+    let recording = &identification.recording;
+    let motor = identification.motor;
+    let mut state = vector![recording.velocity[0], recording.current[0]];
+    let (mut velocity, mut current) = (vec![state[0]], vec![state[1]]);
+    for row in 0..recording.len() - 1{
+        let (a, b, e) = motor.discretize(recording.time[row + 1] - recording.time[row]);
+        state = a*state + b*recording.voltage[row] + e*motor.get_tl();
+        velocity.push(state[0]);
+        current.push(state[1]);
+    }
+    identification.vel_nrmse = nrmse(&recording.velocity, &velocity);
+    identification.current_nrmse = nrmse(&recording.current, &current);
+    identification.replay_velocity = velocity;
+    identification.replay_current = current;
+}
+
+// rms error normalized by the recorded range
+fn nrmse(recorded: &[f64], replayed: &[f64]) -> f64{
+    let rms = (recorded.iter().zip(replayed.iter()).map(|(a, b)| (a - b).powi(2)).sum::<f64>()/recorded.len() as f64).sqrt();
+    let range = recorded.iter().copied().fold(f64::MIN, f64::max) - recorded.iter().copied().fold(f64::MAX, f64::min);
+    if range > 0.0 {rms/range} else {f64::NAN}
+}
+
+// Ordinary least squares on columns scaled to unit rms, standard errors from the residual variance.
+fn least_squares(x: &DMatrix<f64>, y: &DVector<f64>) -> Option<[Estimate; 3]>{
+    let (rows, cols) = x.shape();
+    if rows <= cols{
+        return None;
+    }
+    let scales: Vec<f64> = x.column_iter().map(|column| (column.norm_squared()/rows as f64).sqrt()).collect();
+    if scales.contains(&0.0){
+        return None;
+    }
+    let mut scaled = x.clone();
+    for (mut column, scale) in scaled.column_iter_mut().zip(scales.iter()){
+        column /= *scale;
+    }
+
+    let inverse = (scaled.transpose()*&scaled).try_inverse()?;
+    let coefs = &inverse*scaled.transpose()*y;
+    let residual = y - &scaled*&coefs;
+    let variance = residual.norm_squared()/(rows - cols) as f64;
+
+    let mut estimates = [Estimate::default(); 3];
+    for (i, estimate) in estimates.iter_mut().enumerate(){
+        estimate.value = coefs[i]/scales[i];
+        estimate.std_err = (variance*inverse[(i, i)]).sqrt()/scales[i];
+    }
+    Some(estimates)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // CSV of the zero-order hold model driven by a square wave with pseudo random steps on top.
+    fn simulate(motor: &ConfigMotor, delta: f64, len: usize) -> String{
+        let (a, b, e) = motor.discretize(delta);
+        let mut state = vector![0.0, 0.0];
+        let mut position = 0.0;
+        let mut seed: u64 = 7;
+        let mut step = 0.0;
+        let mut text = String::from("time;voltage;position;velocity;current\n");
+        for row in 0..len{
+            let time = row as f64*delta;
+            if row % 100 == 0{
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                step = (seed >> 11) as f64/(1u64 << 52) as f64*2.0 - 2.0;
+            }
+            let voltage = if (2.0*PI*4.0*time).sin() >= 0.0 {12.0} else {-6.0} + step;
+            text.push_str(&format!("{};{};{};{};{}\n", time, voltage, position, state[0]*30.0/PI, state[1]));
+            let velocity = state[0];
+            state = a*state + b*voltage + e*motor.get_tl();
+            position += (velocity + state[0])/2.0*delta*180.0/PI;
+        }
+        text
+    }
+
+    #[test]
+    fn recovers_the_motor(){
+        let mut motor = ConfigMotor::default();
+        *motor.set_tl() = 0.01;
+        let recording = Recording::from_csv(&simulate(&motor, 2e-5, 25000)).unwrap();
+        assert_eq!(recording.len(), 25000);
+        let identification = identify(&recording).unwrap();
+        assert!(identification.is_physical());
+        let estimates = identification.get_estimates();
+        let expected = [motor.get_j(), motor.get_b(), motor.get_k(), motor.get_r(), motor.get_l(), motor.get_tl()];
+        for ((name, estimate), expected) in PARAMETERS.iter().zip(estimates.iter()).zip(expected){
+            assert!((estimate.get_value()/expected - 1.0).abs() < 0.01, "{} is {} against {}", name, estimate.get_value(), expected);
+        }
+        assert!(identification.get_vel_nrmse() < 0.01 && identification.get_current_nrmse() < 0.01);
+    }
+
+    #[test]
+    fn csv_errors(){
+        let row = |time: usize| format!("{}, 1, 0, 0, 0\n", time);
+        let rows: String = (0..10).map(row).collect();
+        let recording = Recording::from_csv(&format!("# drive log\ntime,voltage\n\n{}", rows)).unwrap();
+        assert_eq!(recording.len(), 10);
+        assert_eq!(recording.get_time()[9], 9.0);
+
+        assert_eq!(Recording::from_csv(&rows[..rows.len() - row(9).len()]).err().unwrap(), "at least 10 samples are needed");
+        assert_eq!(Recording::from_csv(&format!("{}1, 2, 3\n", rows)).err().unwrap(), "line 11: expected 5 columns, found 3");
+        assert_eq!(Recording::from_csv(&format!("{}{}", rows, row(9))).err().unwrap(), "line 11: time is not increasing");
+        assert!(Recording::from_csv(&format!("{}10, x, 0, 0, 0\n", rows)).err().unwrap().starts_with("line 11: "));
+    }
+
+    #[test]
+    fn needs_excitation(){
+        let rows: String = (0..20).map(|time| format!("{}, 1, 0, 0, 0\n", time)).collect();
+        assert!(identify(&Recording::from_csv(&rows).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use nalgebra::Complex;
+
+use crate::control::{Config, TypePid};
+use super::freq::loop_tfs;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Gain{
+    Kp,
+    Ki,
+    Kd,
+}
+
+impl Gain{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            Gain::Kp => "Kp",
+            Gain::Ki => "Ki",
+            Gain::Kd => "Kd",
+        }
+    }
+
+    pub fn get_value(&self, config: &Config, pid: TypePid) -> f64{
+        let pid_conf = config.get_pid_conf()[pid as usize];
+        match self{
+            Gain::Kp => pid_conf.get_kp(),
+            Gain::Ki => pid_conf.get_ki(),
+            Gain::Kd => pid_conf.get_kd(),
+        }
+    }
+
+    pub fn set_value(&self, config: &mut Config, pid: TypePid, value: f64){
+        let pid_conf = &mut config.set_pid_conf()[pid as usize];
+        match self{
+            Gain::Kp => *pid_conf.set_kp() = value,
+            Gain::Ki => *pid_conf.set_ki() = value,
+            Gain::Kd => *pid_conf.set_kd() = value,
+        }
+    }
+}
+
+// (poles, zeros)
+pub type PoleZero = (Vec<Complex<f64>>, Vec<Complex<f64>>);
+
+pub fn magnitude(z: &Complex<f64>) -> f64{
+    z.re.hypot(z.im)
+}
+
+// Closed loop poles and zeros of one level of the cascade, inner loops closed.
+pub fn pole_zero(config: &Config, analysis_loop: TypePid) -> Option<PoleZero>{
+    let loops = loop_tfs(config);
+    let closed = loops[analysis_loop as usize].as_ref()?.get_closed();
+    Some((closed.poles(), closed.zeros()))
+}
+
+// Closed loop poles of analysis_loop while one gain of pid goes linearly from 0 to max.
+pub fn root_locus(config: &Config, analysis_loop: TypePid, pid: TypePid, gain: Gain, max: f64, points: usize) -> Vec<(f64, Vec<Complex<f64>>)>{
+    let mut config = *config;
+    (0..points).filter_map(|i|{
+        let value = max*i as f64/(points - 1) as f64;
+        gain.set_value(&mut config, pid, value);
+        pole_zero(&config, analysis_loop).map(|(poles, _)| (value, poles))
+    }).collect()
+}
+
+// First nonzero gain of the locus with a pole outside the unit circle,
+// poles at z = 1 are computed with ~1e-6 error.
+pub fn critical_gain(locus: &[(f64, Vec<Complex<f64>>)]) -> Option<f64>{
+    locus.iter().filter(|(value, _)| *value > 0.0).find(|(_, poles)| poles.iter().any(|pole| magnitude(pole) > 1.0 + 1e-5)).map(|(value, _)| *value)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::analysis::freq::{FreqResponse, Margins};
+    use crate::control::{ConfigPid, ControlType};
+
+    fn config() -> Config{
+        let mut config = Config::default();
+        *config.set_controller_conf().set_control_option() = ControlType::Pos;
+        *config.set_motor_conf().set_l() = 0.1;
+        config
+    }
+
+    #[test]
+    fn gains(){
+        let mut config = config();
+        for (i, gain) in [Gain::Kp, Gain::Ki, Gain::Kd].iter().enumerate(){
+            gain.set_value(&mut config, TypePid::Vel, i as f64 + 1.5);
+            assert_eq!(gain.get_value(&config, TypePid::Vel), i as f64 + 1.5);
+        }
+        assert_eq!(Gain::Kp.get_value(&config, TypePid::Pos), Config::default().get_pid_conf()[TypePid::Pos as usize].get_kp());
+    }
+
+    #[test]
+    fn closed_loop_poles(){
+        let mut config = config();
+        // below the critical gain of about 0.007
+        config.set_pid_conf()[TypePid::Pos as usize] = ConfigPid::new(0.003, 0.0, 0.0, TypePid::Pos);
+        let (poles, _) = pole_zero(&config, TypePid::Pos).unwrap();
+        let den = loop_tfs(&config)[TypePid::Pos as usize].clone().unwrap().get_closed().get_den().clone();
+        assert_eq!(poles.len(), den.degree());
+        // residual relative to the size of the terms, the poles near z = 1 are ill conditioned
+        for pole in poles.iter(){
+            let scale: f64 = den.get_coefs().iter().enumerate().map(|(i, coef)| coef.abs()*magnitude(pole).powi(i as i32)).sum();
+            assert!(magnitude(&den.eval(*pole)) < 1e-9*scale, "residual at {}", pole);
+            assert!(magnitude(pole) < 1.0);
+        }
+        assert!(pole_zero(&config, TypePid::Vel).is_none());
+    }
+
+    #[test]
+    fn critical_gain_at_the_gain_margin(){
+        let mut config = config();
+        config.set_pid_conf()[TypePid::Pos as usize] = ConfigPid::new(1.0, 0.0, 0.0, TypePid::Pos);
+        let frequency = config.get_controller_conf().get_frequency();
+        let tf = loop_tfs(&config)[TypePid::Pos as usize].clone().unwrap();
+        let margins = Margins::new(&FreqResponse::new(tf.get_open(), frequency, 0.1, 20000),
+            &FreqResponse::new(tf.get_closed(), frequency, 0.1, 20000));
+        let ku = 10f64.powf(margins.get_gain_margin().unwrap()/20.0);
+
+        let locus = root_locus(&config, TypePid::Pos, TypePid::Pos, Gain::Kp, 2.0*ku, 401);
+        assert_eq!(locus.len(), 401);
+        let critical = critical_gain(&locus).unwrap();
+        assert!((critical/ku - 1.0).abs() < 0.02, "critical gain {} against {}", critical, ku);
+        assert!(critical_gain(&locus[..150]).is_none());
+    }
+}
@@ -0,0 +1,223 @@
+use std::f64::consts::PI;
+
+use nalgebra::Complex;
+
+use super::freq::{unit_circle, FreqResponse};
+
+pub struct MeasuredResponse{
+    closed: FreqResponse,
+    open: FreqResponse,
+    coherence: Vec<f64>,
+}
+
+impl MeasuredResponse{
+    pub fn get_closed(&self) -> &FreqResponse{
+        &self.closed
+    }
+
+    // derived from the closed loop as t/(1-t)
+    pub fn get_open(&self) -> &FreqResponse{
+        &self.open
+    }
+
+    pub fn get_coherence(&self) -> &Vec<f64>{
+        &self.coherence
+    }
+
+    pub fn coherence_as_vec(&self) -> Vec<[f64; 2]>{
+        self.closed.get_freq().iter().zip(self.coherence.iter()).map(|(f, c)| [f.log10(), *c]).collect()
+    }
+}
+
+// In-place radix-2 FFT, the length has to be a power of two.
+pub fn fft(data: &mut [Complex<f64>]){
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n{
+        let mut bit = n >> 1;
+        while j & bit != 0{
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j{
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n{
+        let step = unit_circle(-2.0*PI/len as f64);
+        for start in (0..n).step_by(len){
+            let mut w = Complex::new(1.0, 0.0);
+            for k in 0..len/2{
+                let even = data[start + k];
+                let odd = data[start + k + len/2]*w;
+                data[start + k] = even + odd;
+                data[start + k + len/2] = even - odd;
+                w *= step;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// Linear interpolation of [time, value] samples onto a uniform grid.
+fn resample(points: &[[f64; 2]], start: f64, delta: f64, len: usize) -> Vec<f64>{
+    let mut result = Vec::with_capacity(len);
+    let mut index = 0;
+    for k in 0..len{
+        let time = start + k as f64*delta;
+        while index + 2 < points.len() && points[index + 1][0] < time{
+            index += 1;
+        }
+        let ([t0, v0], [t1, v1]) = (points[index], points[(index + 1).min(points.len() - 1)]);
+        let value = if t1 > t0 {v0 + (v1 - v0)*(time - t0)/(t1 - t0)} else {v0};
+        result.push(value);
+    }
+    result
+}
+
+// Welch estimate of the reference to output response with Hann windows and half overlap.
+pub fn estimate_response(reference: &[[f64; 2]], output: &[[f64; 2]], frequency: f64, f_start: f64, f_end: f64) -> Option<MeasuredResponse>{
+    if reference.len() < 2 || output.len() < 2{
+        return None;
+    }
+    let delta = 1.0/frequency;
+    let start = reference[0][0].max(output[0][0]);
+    let end = reference[reference.len() - 1][0].min(output[output.len() - 1][0]);
+    let len = ((end - start)/delta) as usize;
+
+    let mut segment = 256;
+    while segment < len/2 && frequency/(segment as f64) > f_start/2.0{
+        segment *= 2;
+    }
+    if segment > len{
+        return None;
+    }
+
+    let detrend = |values: Vec<f64>| -> Vec<f64>{
+        let mean = values.iter().sum::<f64>()/values.len() as f64;
+        values.iter().map(|value| value - mean).collect()
+    };
+    let x = detrend(resample(reference, start, delta, len));
+    let y = detrend(resample(output, start, delta, len));
+    let window: Vec<f64> = (0..segment).map(|i| 0.5 - 0.5*(2.0*PI*i as f64/segment as f64).cos()).collect();
+
+    let bins = segment/2;
+    let mut pxx = vec![0.0; bins];
+    let mut pyy = vec![0.0; bins];
+    let mut pyx = vec![Complex::new(0.0, 0.0); bins];
+    let mut offset = 0;
+    while offset + segment <= len{
+        let mut fx: Vec<Complex<f64>> = (0..segment).map(|i| Complex::new(x[offset + i]*window[i], 0.0)).collect();
+        let mut fy: Vec<Complex<f64>> = (0..segment).map(|i| Complex::new(y[offset + i]*window[i], 0.0)).collect();
+        fft(&mut fx);
+        fft(&mut fy);
+        for k in 0..bins{
+            pxx[k] += fx[k].re*fx[k].re + fx[k].im*fx[k].im;
+            pyy[k] += fy[k].re*fy[k].re + fy[k].im*fy[k].im;
+            pyx[k] += fy[k]*fx[k].conj();
+        }
+        offset += segment/2;
+    }
+
+    let mut freq = vec![];
+    let mut closed = vec![];
+    let mut open = vec![];
+    let mut coherence = vec![];
+    for k in 1..bins{
+        let f = k as f64*frequency/segment as f64;
+        if f < f_start || f > f_end || pxx[k] <= 0.0{
+            continue;
+        }
+        let h = pyx[k]/pxx[k];
+        freq.push(f);
+        closed.push(h);
+        open.push(h/(Complex::new(1.0, 0.0) - h));
+        coherence.push((pyx[k].re*pyx[k].re + pyx[k].im*pyx[k].im)/(pxx[k]*pyy[k]));
+    }
+    if freq.is_empty(){
+        return None;
+    }
+    Some(MeasuredResponse{closed: FreqResponse::from_response(freq.clone(), closed), open: FreqResponse::from_response(freq, open), coherence})
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn magnitude(value: Complex<f64>) -> f64{
+        value.re.hypot(value.im)
+    }
+
+    // Uniform in [-1, 1), deterministic.
+    fn noise(len: usize) -> Vec<f64>{
+        let mut state: u64 = 12345;
+        (0..len).map(|_|{
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 11) as f64/(1u64 << 52) as f64 - 1.0
+        }).collect()
+    }
+
+    #[test]
+    fn pure_sine(){
+        let n = 64;
+        let mut data: Vec<Complex<f64>> = (0..n).map(|i| Complex::new((2.0*PI*5.0*i as f64/n as f64).sin() + 0.5, 0.0)).collect();
+        fft(&mut data);
+        for (k, value) in data.iter().enumerate(){
+            let expected = match k{
+                0 => Complex::new(32.0, 0.0),
+                5 => Complex::new(0.0, -32.0),
+                59 => Complex::new(0.0, 32.0),
+                _ => Complex::new(0.0, 0.0),
+            };
+            assert!(magnitude(value - expected) < 1e-9, "bin {} is {}", k, value);
+        }
+    }
+
+    #[test]
+    fn matches_the_dft(){
+        let input = noise(32);
+        let mut data: Vec<Complex<f64>> = input.iter().map(|x| Complex::new(*x, 0.0)).collect();
+        fft(&mut data);
+        for (k, value) in data.iter().enumerate(){
+            let dft: Complex<f64> = input.iter().enumerate().map(|(i, x)| unit_circle(-2.0*PI*(i*k) as f64/32.0)*(*x)).sum();
+            assert!(magnitude(value - dft) < 1e-9);
+        }
+    }
+
+    #[test]
+    fn first_order_filter(){
+        // y[k] = a*y[k-1] + (1 - a)*x[k], h = (1 - a)/(1 - a/z)
+        let (a, frequency) = (0.9, 1000.0);
+        let input = noise(20000);
+        let mut state = 0.0;
+        let mut reference = vec![];
+        let mut output = vec![];
+        for (k, x) in input.iter().enumerate(){
+            state = a*state + (1.0 - a)*x;
+            reference.push([k as f64/frequency, *x]);
+            output.push([k as f64/frequency, state]);
+        }
+        let measured = estimate_response(&reference, &output, frequency, 5.0, 200.0).unwrap();
+        let closed = measured.get_closed();
+        assert!(closed.get_freq().first().unwrap() >= &5.0 && closed.get_freq().last().unwrap() <= &200.0);
+        for (i, f) in closed.get_freq().iter().enumerate(){
+            let z = unit_circle(2.0*PI*f/frequency);
+            let expected = Complex::new(1.0 - a, 0.0)/(Complex::new(1.0, 0.0) - Complex::new(a, 0.0)/z);
+            let h = closed.get_response()[i];
+            assert!(magnitude(h - expected) < 0.02*magnitude(expected), "{} against {} at {} hz", h, expected, f);
+            let open = measured.get_open().get_response()[i];
+            assert!(magnitude(open - h/(Complex::new(1.0, 0.0) - h)) < 1e-9);
+            assert!(measured.get_coherence()[i] > 0.99);
+        }
+    }
+
+    #[test]
+    fn too_short(){
+        let points: Vec<[f64; 2]> = (0..100).map(|k| [k as f64/1000.0, 0.0]).collect();
+        assert!(estimate_response(&points, &points, 1000.0, 1.0, 100.0).is_none());
+        assert!(estimate_response(&points[..1], &points, 1000.0, 1.0, 100.0).is_none());
+    }
+}
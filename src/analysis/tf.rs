@@ -0,0 +1,196 @@
+use std::ops::{Add, Mul};
+
+use nalgebra::{Complex, DMatrix};
+
+// Polynomial in z, coefficients in ascending powers.
+#[derive(Clone, Debug)]
+pub struct Poly{
+    coefs: Vec<f64>,
+}
+
+// Discrete transfer function num(z)/den(z).
+#[derive(Clone, Debug)]
+pub struct Tf{
+    num: Poly,
+    den: Poly,
+}
+
+impl Poly{
+    pub fn new(coefs: Vec<f64>) -> Self{
+        let mut poly = Self{coefs};
+        poly.trim();
+        poly
+    }
+
+    pub fn constant(value: f64) -> Self{
+        Poly::new(vec![value])
+    }
+
+    fn trim(&mut self){
+        while self.coefs.len() > 1 && *self.coefs.last().unwrap() == 0.0{
+            self.coefs.pop();
+        }
+        if self.coefs.is_empty(){
+            self.coefs.push(0.0);
+        }
+    }
+
+    pub fn get_coefs(&self) -> &Vec<f64>{
+        &self.coefs
+    }
+
+    pub fn degree(&self) -> usize{
+        self.coefs.len() - 1
+    }
+
+    pub fn scale(&self, value: f64) -> Poly{
+        Poly::new(self.coefs.iter().map(|coef| coef*value).collect())
+    }
+
+    pub fn eval(&self, z: Complex<f64>) -> Complex<f64>{
+        self.coefs.iter().rev().fold(Complex::new(0.0, 0.0), |acc, coef| acc*z + coef)
+    }
+
+    // Eigenvalues of the companion matrix.
+    pub fn roots(&self) -> Vec<Complex<f64>>{
+        let n = self.degree();
+        if n == 0{
+            return vec![];
+        }
+        let lead = self.coefs[n];
+        let mut companion = DMatrix::<f64>::zeros(n, n);
+        for i in 0..n{
+            companion[(0, i)] = -self.coefs[n - 1 - i]/lead;
+            if i + 1 < n{
+                companion[(i + 1, i)] = 1.0;
+            }
+        }
+        companion.complex_eigenvalues().iter().copied().collect()
+    }
+}
+
+impl Add for &Poly{
+    type Output = Poly;
+
+    fn add(self, other: &Poly) -> Poly{
+        let len = self.coefs.len().max(other.coefs.len());
+        Poly::new((0..len).map(|i| self.coefs.get(i).unwrap_or(&0.0) + other.coefs.get(i).unwrap_or(&0.0)).collect())
+    }
+}
+
+impl Mul for &Poly{
+    type Output = Poly;
+
+    fn mul(self, other: &Poly) -> Poly{
+        let mut coefs = vec![0.0; self.coefs.len() + other.coefs.len() - 1];
+        for (i, a) in self.coefs.iter().enumerate(){
+            for (j, b) in other.coefs.iter().enumerate(){
+                coefs[i + j] += a*b;
+            }
+        }
+        Poly::new(coefs)
+    }
+}
+
+impl Tf{
+    pub fn new(num: Poly, den: Poly) -> Self{
+        Self{num, den}
+    }
+
+    pub fn gain(value: f64) -> Self{
+        Tf::new(Poly::constant(value), Poly::constant(1.0))
+    }
+
+    pub fn get_num(&self) -> &Poly{
+        &self.num
+    }
+
+    pub fn get_den(&self) -> &Poly{
+        &self.den
+    }
+
+    pub fn scale(&self, value: f64) -> Tf{
+        Tf::new(self.num.scale(value), self.den.clone())
+    }
+
+    // Unity negative feedback around the open loop, self/(1+self).
+    pub fn feedback(&self) -> Tf{
+        Tf::new(self.num.clone(), &self.den + &self.num)
+    }
+
+    pub fn eval(&self, z: Complex<f64>) -> Complex<f64>{
+        self.num.eval(z)/self.den.eval(z)
+    }
+
+    pub fn poles(&self) -> Vec<Complex<f64>>{
+        self.den.roots()
+    }
+
+    pub fn zeros(&self) -> Vec<Complex<f64>>{
+        self.num.roots()
+    }
+}
+
+impl Mul for &Tf{
+    type Output = Tf;
+
+    fn mul(self, other: &Tf) -> Tf{
+        Tf::new(&self.num*&other.num, &self.den*&other.den)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn magnitude(value: Complex<f64>) -> f64{
+        value.re.hypot(value.im)
+    }
+
+    fn sorted_real(roots: Vec<Complex<f64>>) -> Vec<f64>{
+        let mut real: Vec<f64> = roots.iter().map(|root|{
+            assert!(root.im.abs() < 1e-9);
+            root.re
+        }).collect();
+        real.sort_by(f64::total_cmp);
+        real
+    }
+
+    #[test]
+    fn arithmetic(){
+        // (z - 1)(z + 2) = z^2 + z - 2
+        let product = &Poly::new(vec![-1.0, 1.0])*&Poly::new(vec![2.0, 1.0]);
+        assert_eq!(product.get_coefs(), &vec![-2.0, 1.0, 1.0]);
+        let sum = &product + &Poly::new(vec![2.0, 0.0, -1.0]);
+        assert_eq!(sum.get_coefs(), &vec![0.0, 1.0]);
+        assert_eq!(sum.degree(), 1);
+        assert_eq!(Poly::new(vec![]).get_coefs(), &vec![0.0]);
+        assert_eq!(product.eval(Complex::new(0.0, 1.0)), Complex::new(-3.0, 1.0));
+    }
+
+    #[test]
+    fn roots(){
+        let roots = sorted_real(Poly::new(vec![-6.0, 11.0, -6.0, 1.0]).roots());
+        for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0]){
+            assert!((root - expected).abs() < 1e-9);
+        }
+        // z^2 + 1
+        let mut roots = Poly::new(vec![1.0, 0.0, 1.0]).roots();
+        roots.sort_by(|a, b| a.im.total_cmp(&b.im));
+        assert!(magnitude(roots[0] - Complex::new(0.0, -1.0)) < 1e-9 && magnitude(roots[1] - Complex::new(0.0, 1.0)) < 1e-9);
+        assert!(Poly::constant(2.0).roots().is_empty());
+    }
+
+    #[test]
+    fn feedback(){
+        // 0.5/(z - 0.9) closed by unity feedback has its pole at 0.4
+        let open = Tf::new(Poly::constant(0.5), Poly::new(vec![-0.9, 1.0]));
+        let closed = open.feedback();
+        assert!((sorted_real(closed.poles())[0] - 0.4).abs() < 1e-12);
+        let z = Complex::new(0.3, 0.7);
+        let expected = open.eval(z)/(Complex::new(1.0, 0.0) + open.eval(z));
+        assert!(magnitude(closed.eval(z) - expected) < 1e-12);
+        assert!(magnitude(open.scale(2.0).eval(z) - open.eval(z)*2.0) < 1e-12);
+        assert!(magnitude((&open*&open).eval(z) - open.eval(z)*open.eval(z)) < 1e-12);
+    }
+}
@@ -1,16 +1,39 @@
+pub mod analytic;
+pub mod autotune;
+pub mod cia402;
+pub mod codegen;
+pub mod excitation;
+pub mod external;
+pub mod fault;
+pub mod fixed;
+pub mod law;
+pub mod limits;
+pub mod metrics;
+pub mod modbus;
+pub mod montecarlo;
 pub mod motor;
+pub mod observer;
+pub mod optimizer;
+pub mod protection;
+pub mod server;
+pub mod sweep;
+pub mod switches;
+pub mod timing;
 mod math;
 mod time_mod;
 
-use std::{sync::{Mutex, Arc}, collections::VecDeque};
+use std::{sync::{Mutex, Arc}, collections::VecDeque, time::Instant};
 
 pub use crate::control::motor::Motor;
-use self::{time_mod::Time, math::{Integrator, Derivative}, motor::ConfigMotor};
+use self::{time_mod::Time, math::{Integrator, Derivative}, motor::ConfigMotor, observer::ConfigDob, excitation::ConfigExcitation, fault::{ConfigFault, FaultInjector, FAULT_SLOTS}, law::{ControlLaw, LawRegistry, Measurements, References}, metrics::StepMetrics,
+    protection::{ConfigProtection, ProtectionStatus, Supervisor}, cia402::{Cia402, DriveObjects, MODE_HOMING}, switches::{ConfigSwitches, Switches}, limits::ConfigLimits, timing::{ConfigTiming, TimingEmulator, TimingStats},
+    fixed::{ConfigFixed, FixedCascade}, external::ConfigExternal};
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum ControlType {
     Pos,
     PosVelTrq,
+    Custom(usize),
 }
 
 
@@ -28,7 +51,7 @@ pub struct Pid{
     derivative: Derivative,
 }
 
-#[derive(Clone,Copy)]
+#[derive(Clone,Copy, PartialEq)]
 pub struct ConfigPid{
     kp: f64,
     ki: f64,
@@ -43,8 +66,11 @@ pub struct ConfigController{
     trq_bound: f64,
     duration: f64,
     frequency: f64,
+    settling_band: f64,
     calib_option: Option<TypePid>,
     control_option: ControlType,
+    // references and power stage from the CiA 402 drive outside calibrations
+    cia402: bool,
     start_flag: bool,
     end_flag: bool
 }
@@ -53,7 +79,18 @@ pub struct ConfigController{
 pub struct Config{
     motor: ConfigMotor,
     pid_conf: [ConfigPid; 3],
-    controller: ConfigController
+    controller: ConfigController,
+    dob: ConfigDob,
+    excitation: ConfigExcitation,
+    faults: [ConfigFault; FAULT_SLOTS],
+    protection: ConfigProtection,
+    switches: ConfigSwitches,
+    limits: ConfigLimits,
+    timing: ConfigTiming,
+    fixed: ConfigFixed,
+    external: ConfigExternal,
+    // faults also in the optimizer, sweep, Monte Carlo and fixed point comparison runs
+    headless_faults: bool
 }
 
 pub struct PlotPnts{
@@ -61,23 +98,44 @@ pub struct PlotPnts{
     vel: VecDeque<[f64; 2]>,
     voltage: VecDeque<[f64; 2]>,
     trq: VecDeque<[f64; 2]>,
+    dist: VecDeque<[f64; 2]>,
+    reference: VecDeque<[f64; 2]>,
+    // what the control law sees, recorded while sensor faults are configured
+    sensed_pos: VecDeque<[f64; 2]>,
+    sensed_vel: VecDeque<[f64; 2]>,
+    sensed_trq: VecDeque<[f64; 2]>,
+    // [time, position] of the homing events
+    homes: Vec<[f64; 2]>,
+    // word overflows of the fixed point controller
+    overflows: usize,
+    metrics: Option<StepMetrics>,
 }
 
 
 pub struct Controller{
     motor: Motor,
-    pos_pid: Pid,
-    vel_pid: Pid,
-    trq_pid: Pid,
+    law: Box<dyn ControlLaw>,
+    registry: Arc<LawRegistry>,
     time: Time,
     config: ConfigController,
+    excitation: ConfigExcitation,
+    faults: FaultInjector,
+    supervisor: Supervisor,
+    protection: Arc<Mutex<ProtectionStatus>>,
+    drive: Cia402,
+    drive_objects: Arc<Mutex<DriveObjects>>,
+    switches: Switches,
+    limits: ConfigLimits,
+    timing: TimingEmulator,
+    timing_stats: Arc<Mutex<TimingStats>>,
+    fixed: Option<FixedCascade>,
     plotpoints: Arc<Mutex<PlotPnts>>,
     target: Arc<Mutex<f64>>
 }
 
 impl Default for ConfigController{
     fn default() -> Self {
-        Self{vltg_bound: 24., vel_bound: 4000.,trq_bound: 1., duration: 3.0, frequency: 1000., calib_option: None, control_option: ControlType::PosVelTrq, start_flag: false, end_flag: false }
+        Self{vltg_bound: 24., vel_bound: 4000.,trq_bound: 1., duration: 3.0, frequency: 1000., settling_band: 2.0, calib_option: None, control_option: ControlType::PosVelTrq, cia402: false, start_flag: false, end_flag: false }
     }
 }
 
@@ -87,13 +145,17 @@ impl Default for Config{
         Self{motor: ConfigMotor::default(), pid_conf: [ConfigPid::new(40.0, 1.0,1.5, TypePid::Pos),
             ConfigPid::new(0.001, 0.0,0.0, TypePid::Vel),
             ConfigPid::new(8.0, 5000.0,0.0, TypePid::Trq)],
-            controller: ConfigController::default()}
+            controller: ConfigController::default(), dob: ConfigDob::default(),
+            excitation: ConfigExcitation::default(), faults: [ConfigFault::default(); FAULT_SLOTS],
+            protection: ConfigProtection::default(), switches: ConfigSwitches::default(), limits: ConfigLimits::default(), timing: ConfigTiming::default(), fixed: ConfigFixed::default(), external: ConfigExternal::default(),
+            headless_faults: false}
     }
 }
 
 impl Default for PlotPnts{
     fn default() -> Self {
-        Self{pos: vec![].into(), vel: vec![].into(), trq: vec![].into(), voltage: vec![].into() }
+        Self{pos: vec![].into(), vel: vec![].into(), trq: vec![].into(), voltage: vec![].into(), dist: vec![].into(), reference: vec![].into(),
+            sensed_pos: vec![].into(), sensed_vel: vec![].into(), sensed_trq: vec![].into(), homes: vec![], overflows: 0, metrics: None }
     }
 }
 
@@ -119,11 +181,78 @@ impl PlotPnts{
     pub fn clone_voltage_as_vec(&self) -> Vec<[f64; 2]>{
         self.voltage.clone().into()
     }
+
+    // latest points, [0.0, 0.0] before the first one
+    pub fn last_pos(&self) -> [f64; 2]{
+        self.pos.back().copied().unwrap_or_default()
+    }
+
+    pub fn last_vel(&self) -> [f64; 2]{
+        self.vel.back().copied().unwrap_or_default()
+    }
+
+    pub fn last_trq(&self) -> [f64; 2]{
+        self.trq.back().copied().unwrap_or_default()
+    }
+
+    pub fn last_voltage(&self) -> [f64; 2]{
+        self.voltage.back().copied().unwrap_or_default()
+    }
+
+    pub fn clone_dist_as_vec(&self) -> Vec<[f64; 2]>{
+        self.dist.clone().into()
+    }
+
+    pub fn clone_reference_as_vec(&self) -> Vec<[f64; 2]>{
+        self.reference.clone().into()
+    }
+
+    pub fn clone_sensed_pos_as_vec(&self) -> Vec<[f64; 2]>{
+        self.sensed_pos.clone().into()
+    }
+
+    pub fn clone_sensed_vel_as_vec(&self) -> Vec<[f64; 2]>{
+        self.sensed_vel.clone().into()
+    }
+
+    pub fn clone_sensed_trq_as_vec(&self) -> Vec<[f64; 2]>{
+        self.sensed_trq.clone().into()
+    }
+
+    pub fn get_homes(&self) -> &Vec<[f64; 2]>{
+        &self.homes
+    }
+
+    pub fn get_overflows(&self) -> usize{
+        self.overflows
+    }
+
+    // controlled value of a calibrated loop
+    pub fn clone_loop_as_vec(&self, option: TypePid) -> Vec<[f64; 2]>{
+        match option{
+            TypePid::Pos => self.clone_pos_as_vec(),
+            TypePid::Vel => self.clone_vel_as_vec(),
+            TypePid::Trq => self.clone_trq_as_vec(),
+        }
+    }
+
     pub fn reset(&mut self){
         self.pos = vec![].into();
         self.vel = vec![].into();
         self.trq = vec![].into();
         self.voltage = vec![].into();
+        self.dist = vec![].into();
+        self.reference = vec![].into();
+        self.sensed_pos = vec![].into();
+        self.sensed_vel = vec![].into();
+        self.sensed_trq = vec![].into();
+        self.homes = vec![];
+        self.overflows = 0;
+        self.metrics = None;
+    }
+
+    pub fn get_metrics(&self) -> Option<StepMetrics>{
+        self.metrics
     }
 }
 
@@ -146,6 +275,18 @@ impl ConfigPid{
     pub fn get_option(&self) ->TypePid{
         self.option
     }
+
+    pub fn get_kp(&self) -> f64{
+        self.kp
+    }
+
+    pub fn get_ki(&self) -> f64{
+        self.ki
+    }
+
+    pub fn get_kd(&self) -> f64{
+        self.kd
+    }
 }
 
 impl ConfigController {
@@ -201,6 +342,14 @@ impl ConfigController {
         self.frequency
     }
 
+    pub fn set_settling_band(&mut self) -> &mut f64{
+        &mut self.settling_band
+    }
+
+    pub fn get_settling_band(&self) -> f64{
+        self.settling_band
+    }
+
     pub fn set_calib_option(&mut self) -> &mut Option<TypePid>{
         &mut self.calib_option
     }
@@ -216,6 +365,23 @@ impl ConfigController {
    pub fn get_control_option(&self) -> &ControlType{
     &self.control_option
    }
+
+    pub fn set_cia402(&mut self) -> &mut bool{
+        &mut self.cia402
+    }
+
+    pub fn get_cia402(&self) -> bool{
+        self.cia402
+    }
+
+    pub fn get_calib_target(&self) -> Option<f64>{
+        match self.calib_option{
+            Some(TypePid::Pos) => Some(180.0),
+            Some(TypePid::Vel) => Some(self.vel_bound/2.),
+            Some(TypePid::Trq) => Some(self.trq_bound/2.),
+            None => None
+        }
+    }
 }
     
 
@@ -268,86 +434,222 @@ impl Config{
     pub fn set_motor_conf(&mut self) -> &mut ConfigMotor{
         &mut self.motor
     }
+
+    pub fn get_motor_conf(&self) -> &ConfigMotor{
+        &self.motor
+    }
+
+    pub fn set_dob_conf(&mut self) -> &mut ConfigDob{
+        &mut self.dob
+    }
+
+    pub fn get_dob_conf(&self) -> &ConfigDob{
+        &self.dob
+    }
+
+    pub fn set_excitation_conf(&mut self) -> &mut ConfigExcitation{
+        &mut self.excitation
+    }
+
+    pub fn get_excitation_conf(&self) -> &ConfigExcitation{
+        &self.excitation
+    }
+
+    pub fn set_faults(&mut self) -> &mut [ConfigFault; FAULT_SLOTS]{
+        &mut self.faults
+    }
+
+    pub fn get_faults(&self) -> &[ConfigFault; FAULT_SLOTS]{
+        &self.faults
+    }
+
+    pub fn set_headless_faults(&mut self) -> &mut bool{
+        &mut self.headless_faults
+    }
+
+    pub fn set_protection_conf(&mut self) -> &mut ConfigProtection{
+        &mut self.protection
+    }
+
+    pub fn get_protection_conf(&self) -> &ConfigProtection{
+        &self.protection
+    }
+
+    pub fn set_switches_conf(&mut self) -> &mut ConfigSwitches{
+        &mut self.switches
+    }
+
+    pub fn get_switches_conf(&self) -> &ConfigSwitches{
+        &self.switches
+    }
+
+    pub fn set_limits_conf(&mut self) -> &mut ConfigLimits{
+        &mut self.limits
+    }
+
+    pub fn get_limits_conf(&self) -> &ConfigLimits{
+        &self.limits
+    }
+
+    pub fn set_timing_conf(&mut self) -> &mut ConfigTiming{
+        &mut self.timing
+    }
+
+    pub fn get_timing_conf(&self) -> &ConfigTiming{
+        &self.timing
+    }
+
+    pub fn set_fixed_conf(&mut self) -> &mut ConfigFixed{
+        &mut self.fixed
+    }
+
+    pub fn get_fixed_conf(&self) -> &ConfigFixed{
+        &self.fixed
+    }
+
+    pub fn set_external_conf(&mut self) -> &mut ConfigExternal{
+        &mut self.external
+    }
+
+    pub fn get_external_conf(&self) -> &ConfigExternal{
+        &self.external
+    }
 }
 
 impl Controller{
-    pub fn new(config: Config, plotpoints: Arc<Mutex<PlotPnts>>, target: Arc<Mutex<f64>>) -> Self{
+    pub fn new(config: Config, plotpoints: Arc<Mutex<PlotPnts>>, target: Arc<Mutex<f64>>, protection: Arc<Mutex<ProtectionStatus>>,
+        drive_objects: Arc<Mutex<DriveObjects>>, timing_stats: Arc<Mutex<TimingStats>>, registry: Arc<LawRegistry>) -> Self{
         let time = Time::new(config.get_controller_conf().get_frequency());
-        Self {motor: Motor::new(config.motor), time, target,
-             pos_pid: Pid::new(config.pid_conf[0]),
-             vel_pid: Pid::new(config.pid_conf[1]),
-             trq_pid: Pid::new(config.pid_conf[2]), config: config.controller, plotpoints}
+        timing_stats.lock().unwrap().reset(time.get_period());
+        let supervisor = Supervisor::new(config.protection, config.controller.control_option, Arc::clone(&protection));
+        let drive = Cia402::new(Arc::clone(&drive_objects), config.motor.get_k());
+        Self {motor: Motor::new(config.motor), time, target, law: registry.build(&config), registry,
+             config: config.controller, excitation: config.excitation, faults: FaultInjector::new(config.faults), supervisor, protection,
+             drive, drive_objects, switches: Switches::new(config.switches), limits: config.limits, timing: TimingEmulator::new(config.timing), timing_stats,
+             fixed: FixedCascade::new(&config.pid_conf, config.controller, &config.fixed), plotpoints}
+    }
+
+    pub fn new_headless(config: Config, plotpoints: Arc<Mutex<PlotPnts>>, registry: Arc<LawRegistry>) -> Self{
+        let protection = Arc::new(Mutex::new(ProtectionStatus::default()));
+        let drive_objects = Arc::new(Mutex::new(DriveObjects::default()));
+        let timing_stats = Arc::new(Mutex::new(TimingStats::default()));
+        let mut controller = Controller::new(config, plotpoints, Arc::new(Mutex::new(180.0)), protection, drive_objects, timing_stats, registry);
+        controller.time = Time::new_headless(config.get_controller_conf().get_frequency());
+        controller
     }
 
     pub fn reset(&mut self, config: Config){
         self.config = config.controller;
+        self.excitation = config.excitation;
+        self.faults = FaultInjector::new(config.faults);
+        self.supervisor = Supervisor::new(config.protection, config.controller.control_option, Arc::clone(&self.protection));
+        self.drive = Cia402::new(Arc::clone(&self.drive_objects), config.motor.get_k());
+        self.switches = Switches::new(config.switches);
+        self.limits = config.limits;
+        self.timing = TimingEmulator::new(config.timing);
         self.motor.reset(config.motor);
-        self.pos_pid.reset(config.pid_conf[0]);
-        self.vel_pid.reset(config.pid_conf[1]);
-        self.trq_pid.reset(config.pid_conf[2]);
+        self.law = self.registry.build(&config);
+        self.fixed = FixedCascade::new(&config.pid_conf, config.controller, &config.fixed);
         self.plotpoints.lock().unwrap().reset();
         self.time = Time::new(self.config.get_frequency());
+        self.timing_stats.lock().unwrap().reset(self.time.get_period());
     }
 
-    pub fn generate_control(&mut self, delta: f64) -> f64{
-        let input = match self.config.calib_option{
-            Some(pid) =>{
-                match pid{
-                    TypePid::Pos =>{
-                        match self.config.control_option{
-                            ControlType::Pos => {
-                                let target = 180.0;
-                                self.pos_pid.generate_control(self.motor.get_position(), target, delta, self.config.vltg_bound)
-                            }
-                            ControlType::PosVelTrq => {
-                                let target = 180.0;
-                                let vel = self.pos_pid.generate_control(self.motor.get_position(), target, delta, self.config.vel_bound);
-                                let trq = self.vel_pid.generate_control(self.motor.get_velocity(), vel, delta, self.config.trq_bound);
-                                let vltg = self.trq_pid.generate_control(self.motor.get_torque(), trq, delta, self.config.vltg_bound);
-                                vltg
-                            } 
-                        }
-                    }
-                    TypePid::Vel =>{
-                        let target = self.config.vel_bound/2.;
-                        let trq = self.vel_pid.generate_control(self.motor.get_velocity(), target, delta, self.config.trq_bound);
-                        let vltg = self.trq_pid.generate_control(self.motor.get_torque(), trq, delta, self.config.vltg_bound);
-                        vltg
-                    }
-                    TypePid::Trq =>{
-                        let target = self.config.trq_bound/2.;
-                        let vltg = self.trq_pid.generate_control(self.motor.get_torque(), target, delta, self.config.vltg_bound);
-                        vltg
-                    }
-                }
+    // A run starts or resumes, the pause before it counts neither as a period nor in the statistics.
+    pub fn start_run(&mut self){
+        self.time.restart_period();
+        self.timing_stats.lock().unwrap().reset(self.time.get_period());
+    }
+
+    // Sensed values after the sensor faults and the transport delay.
+    pub fn get_measurements(&mut self) -> Measurements{
+        let measurements = Measurements::new(self.motor.get_position(), self.motor.get_velocity(), self.motor.get_torque());
+        let measurements = self.faults.measure(self.time.get_time_from_start(), measurements, self.motor.get_config().get_k());
+        self.timing.measure(measurements)
+    }
+
+    // Voltage the power stage applies for the commanded one, any law is bounded by the supply.
+    fn power_stage(&mut self, time: f64, voltage: f64) -> f64{
+        let voltage = voltage.clamp(-self.config.vltg_bound, self.config.vltg_bound);
+        let voltage = self.faults.actuate(time, &mut self.motor, voltage, self.config.vltg_bound);
+        let voltage = self.supervisor.actuate(&mut self.motor, voltage);
+        if self.config.cia402 && self.config.calib_option.is_none() {self.drive.actuate(&mut self.motor, voltage)} else {voltage}
+    }
+
+    // Whole turns from the sensed wrapped angle to the unwrapped one, taken around the motor angle.
+    fn unwrap_offset(&self, measurements: &Measurements) -> f64{
+        360.0*((self.motor.get_unwrapped_position() - measurements.get_position())/360.0).round()
+    }
+
+    pub fn get_references(&mut self, measurements: &Measurements, delta: f64) -> References{
+        if let Some(target) = self.config.get_calib_target(){
+            let target = target + self.excitation.get_value(self.time.get_time_from_start(), self.config.duration, target.abs());
+            return match self.config.calib_option{
+                Some(TypePid::Vel) => References::velocity(target),
+                Some(TypePid::Trq) => References::torque(target),
+                _ => References::position(target),
+            };
+        }
+
+        // the drive and the limits run on the unwrapped angle as the switches, the law on the wrapped one
+        let offset = self.unwrap_offset(measurements);
+        let unwrapped = Measurements::new(measurements.get_position() + offset, measurements.get_velocity(), measurements.get_torque());
+        let references = if self.config.cia402{
+            let inputs = self.switches.update(self.motor.get_unwrapped_position());
+            let references = self.drive.update(delta, &unwrapped, &inputs, self.supervisor.get_state());
+            if self.drive.take_fault_reset(){
+                *self.protection.lock().unwrap().set_reset_request() = true;
             }
-            None => {
-                match self.config.control_option{
-                    ControlType::Pos => {
-                        self.pos_pid.generate_control(self.motor.get_position(), *(self.target.lock().unwrap()), delta, self.config.vltg_bound)
-                    }
-                    ControlType::PosVelTrq => {
-                        let vel = self.pos_pid.generate_control(self.motor.get_position(), *(self.target.lock().unwrap()), delta, self.config.vel_bound);
-                        let trq = self.vel_pid.generate_control(self.motor.get_velocity(), vel, delta, self.config.trq_bound);
-                        let vltg = self.trq_pid.generate_control(self.motor.get_torque(), trq, delta, self.config.vltg_bound);
-                        vltg
-                    }
-                }
+            if let Some(position) = self.drive.take_home_event(){
+                self.plotpoints.lock().unwrap().homes.push([self.time.get_time_from_start(), position - offset]);
             }
+            references
+        } else {
+            References::position(*(self.target.lock().unwrap()) + offset)
         };
-        input
-
-
+        // positions are not known before homing
+        if self.config.cia402 && self.drive.get_mode() == MODE_HOMING{
+            return references.shift(-offset);
+        }
+        let (references, limit_active) = self.limits.apply(references, unwrapped.get_position());
+        *self.drive.set_internal_limit() = limit_active;
+        *self.protection.lock().unwrap().set_limit_active() = limit_active;
+        references.shift(-offset)
+    }
+
+    pub fn generate_control(&mut self, measurements: &Measurements, references: &References, delta: f64) -> f64{
+        self.supervisor.update(self.time.get_time_from_start(), delta, measurements, references, &self.motor);
+        let references = self.supervisor.get_references(*references, delta);
+        match &mut self.fixed{
+            Some(fixed) => fixed.generate_control(measurements, &references),
+            None => self.law.generate_control(measurements, &references, delta),
+        }
     }
 
     pub fn calculate_point(&mut self){
+        *self.time.set_jitter() = self.timing.next_jitter();
         self.time.update_state();
+        let step_start = Instant::now();
         let time_from_start = self.time.get_time_from_start();
         
         if Controller::check_point_add(&mut self.config, time_from_start){
             let delta = self.time.get_delta();
-            let input = self.generate_control(delta);
-            self.motor.update_state(delta, input);
+            // an emulated controller assumes the nominal period whatever the sampling jitter
+            let control_delta = if self.timing.get_enabled() {self.time.get_period()} else {delta};
+            let measurements = self.get_measurements();
+            let references = self.get_references(&measurements, control_delta);
+            let input = self.generate_control(&measurements, &references, control_delta);
+            let input = self.timing.actuate(input);
+            let input = self.power_stage(time_from_start, input);
+            let held = self.timing.get_held();
+            *self.timing.set_held() = input;
+            let split = self.timing.get_split();
+            if split > 0.0{
+                self.motor.update_state(split*delta, held);
+                self.motor.update_state((1.0 - split)*delta, input);
+            } else {
+                self.motor.update_state(delta, input);
+            }
             let mut points = self.plotpoints.lock().unwrap();
 
             if time_from_start >= self.config.duration{
@@ -355,12 +657,35 @@ impl Controller{
                 points.vel.pop_front();
                 points.trq.pop_front();
                 points.voltage.pop_front();
+                points.dist.pop_front();
+                points.reference.pop_front();
+                points.sensed_pos.pop_front();
+                points.sensed_vel.pop_front();
+                points.sensed_trq.pop_front();
             }
 
             points.pos.push_back([time_from_start, self.motor.get_position()]);
             points.vel.push_back([time_from_start, self.motor.get_velocity()]);
             points.voltage.push_back([time_from_start, input]);
             points.trq.push_back([time_from_start, self.motor.get_torque()]);
+            // the reference acts on the state of the previous period
+            points.reference.push_back([time_from_start - delta, references.get_value()]);
+            if self.faults.get_sensor_faults(){
+                points.sensed_pos.push_back([time_from_start - delta, measurements.get_position()]);
+                points.sensed_vel.push_back([time_from_start - delta, measurements.get_velocity()]);
+                points.sensed_trq.push_back([time_from_start - delta, measurements.get_torque()]);
+            }
+            if let Some(dist) = self.law.get_disturbance(){
+                points.dist.push_back([time_from_start, dist]);
+            }
+            if let Some(fixed) = &self.fixed{
+                points.overflows = fixed.get_overflows();
+            }
+            drop(points);
+            self.timing_stats.lock().unwrap().record(delta, step_start.elapsed().as_secs_f64());
+        } else if let (Some(calib), Some(target)) = (self.config.calib_option, self.config.get_calib_target()){
+            let mut points = self.plotpoints.lock().unwrap();
+            points.metrics = Some(metrics::step_metrics(&points, calib, target, self.config.settling_band, self.config.vltg_bound));
         }
     }
 
@@ -385,4 +710,25 @@ impl Controller{
     }
 }
 
+// Runs a calibration step in virtual time and returns the recorded curves.
+// Without a calibration option the angle step is used, faults only apply when the config asks for them.
+pub fn simulate(mut config: Config, registry: Arc<LawRegistry>) -> PlotPnts{
+    if !config.headless_faults{
+        for fault in config.set_faults(){
+            *fault.set_enabled() = false;
+        }
+    }
+    let controller_conf = config.set_controller_conf();
+    *controller_conf.set_start_flag() = true;
+    if controller_conf.get_calib_option().is_none(){
+        *controller_conf.set_calib_option() = Some(TypePid::Pos);
+    }
 
+    let plotpoints = Arc::new(Mutex::new(PlotPnts::default()));
+    let mut controller = Controller::new_headless(config, Arc::clone(&plotpoints), registry);
+    while *controller.get_controller_conf().get_start_flag(){
+        controller.calculate_point();
+    }
+    let mut points = plotpoints.lock().unwrap();
+    std::mem::take(&mut *points)
+}
\ No newline at end of file
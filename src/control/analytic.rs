@@ -0,0 +1,135 @@
+use std::f64::consts::PI;
+
+use super::{ConfigPid, TypePid, motor::ConfigMotor};
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum SpeedTuning{
+    SymmetricOptimum,
+    TechnicalOptimum,
+}
+
+// Requested closed loop bandwidths in rad/s.
+#[derive(Copy, Clone)]
+pub struct ConfigAnalytic{
+    trq_bandwidth: f64,
+    vel_bandwidth: f64,
+    pos_bandwidth: f64,
+    speed_tuning: SpeedTuning,
+}
+
+impl Default for ConfigAnalytic{
+    fn default() -> Self {
+        Self{trq_bandwidth: 1000.0, vel_bandwidth: 100.0, pos_bandwidth: 10.0, speed_tuning: SpeedTuning::SymmetricOptimum}
+    }
+}
+
+impl SpeedTuning{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            SpeedTuning::SymmetricOptimum => "Symmetric optimum",
+            SpeedTuning::TechnicalOptimum => "Technical optimum",
+        }
+    }
+}
+
+impl ConfigAnalytic{
+    pub fn set_trq_bandwidth(&mut self) -> &mut f64{
+        &mut self.trq_bandwidth
+    }
+
+    pub fn set_vel_bandwidth(&mut self) -> &mut f64{
+        &mut self.vel_bandwidth
+    }
+
+    pub fn set_pos_bandwidth(&mut self) -> &mut f64{
+        &mut self.pos_bandwidth
+    }
+
+    pub fn set_speed_tuning(&mut self) -> &mut SpeedTuning{
+        &mut self.speed_tuning
+    }
+
+    // Each loop has to be noticeably slower than the one it wraps and than the sampling.
+    pub fn get_warnings(&self, frequency: f64) -> Vec<&'static str>{
+        let mut warnings = vec![];
+        if self.trq_bandwidth > 2.0*PI*frequency/10.0{
+            warnings.push("Torque bandwidth is above a tenth of the sampling frequency");
+        }
+        if self.vel_bandwidth*2.0 > self.trq_bandwidth{
+            warnings.push("Speed bandwidth should be at most half of the torque bandwidth");
+        }
+        if self.pos_bandwidth*2.0 > self.vel_bandwidth{
+            warnings.push("Angle bandwidth should be at most half of the speed bandwidth");
+        }
+        warnings
+    }
+}
+
+// Torque loop: PI zero cancels the electrical pole r/l, so the open loop is kp*k/(l*s).
+// Speed loop: the closed torque loop is a lag 1/(s/trq_bandwidth+1) in front of 1/(j*s+b).
+// Angle loop: the closed speed loop is treated as unity, angle in deg integrates speed in rpm with gain 6.
+pub fn analytic_gains(motor: &ConfigMotor, analytic: &ConfigAnalytic) -> [ConfigPid; 3]{
+    let trq_kp = analytic.trq_bandwidth*motor.get_l()/motor.get_k();
+    let trq_ki = analytic.trq_bandwidth*motor.get_r()/motor.get_k();
+
+    let rpm_per_rads = 30.0/PI;
+    let vel_kp = analytic.vel_bandwidth*motor.get_j()/rpm_per_rads;
+    let vel_ki = match analytic.speed_tuning{
+        SpeedTuning::SymmetricOptimum => vel_kp*analytic.vel_bandwidth.powi(2)/analytic.trq_bandwidth,
+        SpeedTuning::TechnicalOptimum => vel_kp*motor.get_b()/motor.get_j(),
+    };
+
+    let pos_kp = analytic.pos_bandwidth/6.0;
+
+    [ConfigPid::new(pos_kp, 0.0, 0.0, TypePid::Pos),
+        ConfigPid::new(vel_kp, vel_ki, 0.0, TypePid::Vel),
+        ConfigPid::new(trq_kp, trq_ki, 0.0, TypePid::Trq)]
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::analysis::freq::{self, FreqResponse, Margins};
+    use crate::control::Config;
+
+    // Gain crossovers in rad/s of the angle, speed and torque loops with the analytic gains.
+    fn crossovers(analytic: &ConfigAnalytic, frequency: f64) -> Vec<f64>{
+        let mut config = Config::default();
+        *config.set_controller_conf().set_frequency() = frequency;
+        *config.set_pid_conf() = analytic_gains(config.get_motor_conf(), analytic);
+        freq::loop_tfs(&config).iter().map(|tf|{
+            let tf = tf.as_ref().unwrap();
+            let margins = Margins::new(&FreqResponse::new(tf.get_open(), frequency, 0.01, 5000),
+                &FreqResponse::new(tf.get_closed(), frequency, 0.01, 5000));
+            2.0*PI*margins.get_gain_crossover().unwrap()
+        }).collect()
+    }
+
+    #[test]
+    fn bandwidths(){
+        // sampled fast enough for the continuous design to hold
+        let analytic = ConfigAnalytic::default();
+        let crossovers = crossovers(&analytic, 50000.0);
+        for (crossover, bandwidth) in crossovers.iter().zip([analytic.pos_bandwidth, analytic.vel_bandwidth, analytic.trq_bandwidth]){
+            assert!((crossover/bandwidth - 1.0).abs() < 0.1, "crossover {} for {}", crossover, bandwidth);
+        }
+    }
+
+    #[test]
+    fn technical_optimum(){
+        let analytic = ConfigAnalytic{speed_tuning: SpeedTuning::TechnicalOptimum, ..Default::default()};
+        let gains = analytic_gains(&ConfigMotor::default(), &analytic);
+        // the PI zero sits on the mechanical pole b/j
+        let motor = ConfigMotor::default();
+        assert!((gains[1].get_ki()/gains[1].get_kp() - motor.get_b()/motor.get_j()).abs() < 1e-12);
+        assert!((crossovers(&analytic, 50000.0)[1]/analytic.vel_bandwidth - 1.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn warnings(){
+        assert_eq!(ConfigAnalytic::default().get_warnings(1000.0), vec!["Torque bandwidth is above a tenth of the sampling frequency"]);
+        assert!(ConfigAnalytic::default().get_warnings(2000.0).is_empty());
+        let analytic = ConfigAnalytic{trq_bandwidth: 1000.0, vel_bandwidth: 600.0, pos_bandwidth: 400.0, ..Default::default()};
+        assert_eq!(analytic.get_warnings(100.0).len(), 3);
+    }
+}
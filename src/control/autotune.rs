@@ -0,0 +1,206 @@
+use super::{Config, ConfigPid, ControlType, Motor, Pid, TypePid};
+
+// velocity reference of the angle relay in the cascade, rpm, keeps the swing within a few revolutions
+const RELAY_VELOCITY: f64 = 100.0;
+// relay switches when the error passes this part of the setpoint, against chattering
+const HYSTERESIS: f64 = 0.0001;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TuningRule{
+    ZieglerNichols,
+    ZieglerNicholsPi,
+    TyreusLuyben,
+    TyreusLuybenPid,
+    SomeOvershoot,
+    NoOvershoot,
+}
+
+pub const TUNING_RULES: [TuningRule; 6] = [TuningRule::ZieglerNichols, TuningRule::ZieglerNicholsPi,
+    TuningRule::TyreusLuyben, TuningRule::TyreusLuybenPid, TuningRule::SomeOvershoot, TuningRule::NoOvershoot];
+
+#[derive(Copy, Clone)]
+pub struct RelayResult{
+    option: TypePid,
+    ku: f64,
+    pu: f64,
+}
+
+impl TuningRule{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            TuningRule::ZieglerNichols => "Ziegler-Nichols PID",
+            TuningRule::ZieglerNicholsPi => "Ziegler-Nichols PI",
+            TuningRule::TyreusLuyben => "Tyreus-Luyben PI",
+            TuningRule::TyreusLuybenPid => "Tyreus-Luyben PID",
+            TuningRule::SomeOvershoot => "Some overshoot PID",
+            TuningRule::NoOvershoot => "No overshoot PID",
+        }
+    }
+
+    // (kp/ku, ti/pu, td/pu)
+    fn get_coefficients(&self) -> (f64, f64, f64){
+        match self{
+            TuningRule::ZieglerNichols => (0.6, 0.5, 0.125),
+            TuningRule::ZieglerNicholsPi => (0.45, 1.0/1.2, 0.0),
+            TuningRule::TyreusLuyben => (1.0/3.2, 2.2, 0.0),
+            TuningRule::TyreusLuybenPid => (1.0/2.2, 2.2, 1.0/6.3),
+            TuningRule::SomeOvershoot => (1.0/3.0, 0.5, 1.0/3.0),
+            TuningRule::NoOvershoot => (0.2, 0.5, 1.0/3.0),
+        }
+    }
+}
+
+impl RelayResult{
+    pub fn get_option(&self) -> TypePid{
+        self.option
+    }
+
+    pub fn get_ku(&self) -> f64{
+        self.ku
+    }
+
+    pub fn get_pu(&self) -> f64{
+        self.pu
+    }
+
+    pub fn get_pid(&self, rule: TuningRule) -> ConfigPid{
+        let (kp, ti, td) = rule.get_coefficients();
+        let kp = kp*self.ku;
+        ConfigPid::new(kp, kp/(ti*self.pu), kp*td*self.pu, self.option)
+    }
+}
+
+// Astrom-Hagglund relay experiment on every loop of the cascade, inner loops first.
+// Each outer experiment runs with the gains proposed for the inner loops.
+pub fn relay_autotune(config: &Config, rule: TuningRule) -> [Option<RelayResult>; 3]{
+    let mut gains = *config.get_pid_conf();
+    let mut results = [None; 3];
+    let loops = match config.get_controller_conf().get_control_option(){
+        ControlType::Pos => vec![TypePid::Pos],
+        _ => vec![TypePid::Trq, TypePid::Vel, TypePid::Pos],
+    };
+
+    for option in loops{
+        let index = option as usize;
+        results[index] = relay_test(config, &gains, option);
+        if let Some(result) = results[index]{
+            gains[index] = result.get_pid(rule);
+        }
+    }
+    results
+}
+
+fn relay_test(config: &Config, gains: &[ConfigPid; 3], option: TypePid) -> Option<RelayResult>{
+    let conf = config.get_controller_conf();
+    let delta = 1.0/conf.get_frequency();
+    let steps = (conf.get_duration()/delta) as usize;
+    let setpoint = match option{
+        TypePid::Pos => 180.0,
+        TypePid::Vel => conf.vel_bound/2.,
+        TypePid::Trq => conf.trq_bound/2.,
+    };
+    let amplitude = match (option, conf.control_option){
+        (TypePid::Pos, ControlType::Pos) | (TypePid::Trq, _) => conf.vltg_bound,
+        (TypePid::Pos, _) => conf.vel_bound.min(RELAY_VELOCITY),
+        (TypePid::Vel, _) => conf.trq_bound,
+    };
+
+    let mut motor = Motor::new(*config.get_motor_conf());
+    let mut vel_pid = Pid::new(gains[1]);
+    let mut trq_pid = Pid::new(gains[2]);
+    let mut crossings = vec![];
+    let mut extremes = vec![];
+    let (mut min, mut max) = (f64::MAX, f64::MIN);
+    let hysteresis = HYSTERESIS*setpoint.abs();
+    let mut relay = amplitude;
+
+    for step in 0..steps{
+        let output = match option{
+            // the relay turns the rotor over many revolutions
+            TypePid::Pos => motor.get_unwrapped_position(),
+            TypePid::Vel => motor.get_velocity(),
+            TypePid::Trq => motor.get_torque(),
+        };
+        let error = setpoint - output;
+        let prev_relay = relay;
+        if error > hysteresis{
+            relay = amplitude;
+        } else if error < -hysteresis{
+            relay = -amplitude;
+        }
+
+        let voltage = match (option, conf.control_option){
+            (TypePid::Pos, ControlType::Pos) | (TypePid::Trq, _) => relay,
+            (TypePid::Pos, _) => {
+                let trq = vel_pid.generate_control(motor.get_velocity(), relay, delta, conf.trq_bound);
+                trq_pid.generate_control(motor.get_torque(), trq, delta, conf.vltg_bound)
+            }
+            (TypePid::Vel, _) => trq_pid.generate_control(motor.get_torque(), relay, delta, conf.vltg_bound),
+        };
+        motor.update_state(delta, voltage);
+
+        min = min.min(output);
+        max = max.max(output);
+        if prev_relay > 0.0 && relay < 0.0{
+            crossings.push(step as f64*delta);
+            extremes.push((max - min)/2.0);
+            min = f64::MAX;
+            max = f64::MIN;
+        }
+    }
+
+    // the first half of the cycles is treated as the start-up transient
+    if crossings.len() < 4{
+        return None;
+    }
+    let from = crossings.len()/2;
+    let periods = crossings.len() - 1 - from;
+    let pu = (crossings[crossings.len() - 1] - crossings[from])/periods as f64;
+    let a = extremes[from + 1..].iter().sum::<f64>()/periods as f64;
+    if pu <= 0.0 || a <= 0.0{
+        return None;
+    }
+
+    Some(RelayResult{option, ku: 4.0*amplitude/(std::f64::consts::PI*a), pu})
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::analysis::freq::{self, FreqResponse, Margins};
+
+    // Ultimate gain and period of the loop with a unit proportional controller, from its margins.
+    fn ultimate(config: &Config, option: TypePid) -> (f64, f64){
+        let mut config = *config;
+        config.set_pid_conf()[option as usize] = ConfigPid::new(1.0, 0.0, 0.0, option);
+        let tf = freq::loop_tfs(&config)[option as usize].clone().unwrap();
+        let frequency = config.get_controller_conf().get_frequency();
+        let margins = Margins::new(&FreqResponse::new(tf.get_open(), frequency, 0.1, 20000),
+            &FreqResponse::new(tf.get_closed(), frequency, 0.1, 20000));
+        (10f64.powf(margins.get_gain_margin().unwrap()/20.0), 1.0/margins.get_phase_crossover().unwrap())
+    }
+
+    // Relative tolerances of ku and pu, the describing function neglects the harmonics of the relay.
+    fn check(config: &Config, option: TypePid, tolerance: (f64, f64)){
+        let result = relay_test(config, config.get_pid_conf(), option).unwrap();
+        let (ku, pu) = ultimate(config, option);
+        assert!((result.get_ku()/ku - 1.0).abs() < tolerance.0, "ku {} against {}", result.get_ku(), ku);
+        assert!((result.get_pu()/pu - 1.0).abs() < tolerance.1, "pu {} against {}", result.get_pu(), pu);
+    }
+
+    #[test]
+    fn angle_relay_on_voltage(){
+        let mut config = Config::default();
+        *config.set_controller_conf().set_control_option() = ControlType::Pos;
+        // electrical and mechanical time constants alike, the phase falls steeply through -180 deg
+        *config.set_motor_conf().set_l() = 0.1;
+        *config.set_controller_conf().set_duration() = 20.0;
+        check(&config, TypePid::Pos, (0.03, 0.03));
+    }
+
+    #[test]
+    fn angle_relay_in_cascade(){
+        // the phase of the closed speed loop with the integrator stays near -180 deg, small errors move the crossover
+        check(&Config::default(), TypePid::Pos, (0.2, 0.1));
+    }
+}
@@ -0,0 +1,686 @@
+use std::sync::{Arc, Mutex};
+
+use super::{law::{Measurements, References}, protection::DriveState, switches::SwitchInputs, Motor};
+
+// controlword bits
+pub const CW_SWITCH_ON: u16 = 1;
+pub const CW_ENABLE_VOLTAGE: u16 = 1 << 1;
+pub const CW_QUICK_STOP: u16 = 1 << 2;
+pub const CW_ENABLE_OPERATION: u16 = 1 << 3;
+// new set-point in profile position, homing start in homing
+pub const CW_NEW_SETPOINT: u16 = 1 << 4;
+pub const CW_RELATIVE: u16 = 1 << 6;
+pub const CW_FAULT_RESET: u16 = 1 << 7;
+pub const CW_HALT: u16 = 1 << 8;
+
+// controlword commands
+pub const SHUTDOWN: u16 = CW_QUICK_STOP | CW_ENABLE_VOLTAGE;
+pub const SWITCH_ON: u16 = CW_QUICK_STOP | CW_ENABLE_VOLTAGE | CW_SWITCH_ON;
+pub const ENABLE_OPERATION: u16 = CW_ENABLE_OPERATION | CW_QUICK_STOP | CW_ENABLE_VOLTAGE | CW_SWITCH_ON;
+pub const DISABLE_VOLTAGE: u16 = 0;
+pub const QUICK_STOP: u16 = CW_ENABLE_VOLTAGE;
+
+// statusword bits
+pub const SW_READY_TO_SWITCH_ON: u16 = 1;
+pub const SW_SWITCHED_ON: u16 = 1 << 1;
+pub const SW_OPERATION_ENABLED: u16 = 1 << 2;
+pub const SW_FAULT: u16 = 1 << 3;
+pub const SW_VOLTAGE_ENABLED: u16 = 1 << 4;
+pub const SW_QUICK_STOP: u16 = 1 << 5;
+pub const SW_SWITCH_ON_DISABLED: u16 = 1 << 6;
+pub const SW_REMOTE: u16 = 1 << 9;
+pub const SW_TARGET_REACHED: u16 = 1 << 10;
+pub const SW_INTERNAL_LIMIT: u16 = 1 << 11;
+// set-point acknowledge, speed zero or homing attained depending on the mode
+pub const SW_MODE_12: u16 = 1 << 12;
+// homing error
+pub const SW_MODE_13: u16 = 1 << 13;
+
+// modes of operation
+pub const MODE_PROFILE_POSITION: i8 = 1;
+pub const MODE_PROFILE_VELOCITY: i8 = 3;
+pub const MODE_HOMING: i8 = 6;
+pub const MODE_CSP: i8 = 8;
+pub const MODES: [i8; 4] = [MODE_PROFILE_POSITION, MODE_PROFILE_VELOCITY, MODE_HOMING, MODE_CSP];
+
+// homing methods, the negative ones run into a mechanical stop
+pub const HOMING_NEGATIVE_LIMIT_INDEX: i8 = 1;
+pub const HOMING_POSITIVE_LIMIT_INDEX: i8 = 2;
+pub const HOMING_HOME_SWITCH_INDEX: i8 = 3;
+pub const HOMING_NEGATIVE_LIMIT: i8 = 17;
+pub const HOMING_POSITIVE_LIMIT: i8 = 18;
+pub const HOMING_HOME_SWITCH: i8 = 19;
+pub const HOMING_INDEX_NEGATIVE: i8 = 33;
+pub const HOMING_INDEX_POSITIVE: i8 = 34;
+pub const HOMING_CURRENT_POSITION: i8 = 37;
+pub const HOMING_HARD_STOP_NEGATIVE: i8 = -1;
+pub const HOMING_HARD_STOP_POSITIVE: i8 = -2;
+pub const HOMING_METHODS: [i8; 11] = [HOMING_NEGATIVE_LIMIT_INDEX, HOMING_POSITIVE_LIMIT_INDEX, HOMING_HOME_SWITCH_INDEX, HOMING_NEGATIVE_LIMIT,
+    HOMING_POSITIVE_LIMIT, HOMING_HOME_SWITCH, HOMING_INDEX_NEGATIVE, HOMING_INDEX_POSITIVE, HOMING_CURRENT_POSITION, HOMING_HARD_STOP_NEGATIVE,
+    HOMING_HARD_STOP_POSITIVE];
+
+// digital inputs bits
+pub const DI_NEGATIVE_LIMIT: u32 = 1;
+pub const DI_POSITIVE_LIMIT: u32 = 1 << 1;
+pub const DI_HOME_SWITCH: u32 = 1 << 2;
+
+// target reached windows, deg and rpm
+const POSITION_WINDOW: f64 = 1.0;
+const VELOCITY_WINDOW: f64 = 10.0;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PowerState{
+    NotReadyToSwitchOn,
+    SwitchOnDisabled,
+    ReadyToSwitchOn,
+    SwitchedOn,
+    OperationEnabled,
+    QuickStopActive,
+    FaultReactionActive,
+    Fault,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum HomingPhase{
+    Idle,
+    // towards the switch or the stop at the switch speed, direction +-1
+    Search(f64),
+    // off the switch at the zero speed, homes on its edge
+    Release(f64),
+    // on to the next index pulse at the zero speed
+    Index(f64),
+}
+
+// Object dictionary shared with the master. Positions in deg, velocities in rpm, accelerations in rpm/s.
+#[derive(Copy, Clone)]
+pub struct DriveObjects{
+    controlword: u16,
+    statusword: u16,
+    modes_of_operation: i8,
+    modes_of_operation_display: i8,
+    target_position: f64,
+    target_velocity: f64,
+    profile_velocity: f64,
+    profile_acceleration: f64,
+    profile_deceleration: f64,
+    quick_stop_deceleration: f64,
+    home_offset: f64,
+    homing_method: i8,
+    // rpm, rpm/s and A
+    homing_speed_switch: f64,
+    homing_speed_zero: f64,
+    homing_acceleration: f64,
+    homing_current_threshold: f64,
+    position_actual: f64,
+    velocity_actual: f64,
+    digital_inputs: u32,
+}
+
+// Power state machine and operation modes in front of the control law, runs in the controller thread.
+pub struct Cia402{
+    objects: Arc<Mutex<DriveObjects>>,
+    state: PowerState,
+    mode: i8,
+    prev_controlword: u16,
+    // position reference of the profile generator, deg and rpm
+    profile_pos: f64,
+    profile_vel: f64,
+    setpoint: f64,
+    setpoint_ack: bool,
+    // internal position of the user zero
+    home_position: f64,
+    homing: HomingPhase,
+    homing_method: i8,
+    homing_attained: bool,
+    homing_error: bool,
+    home_event: Option<f64>,
+    fault_reset: bool,
+    // software limit acting on the references of the previous period
+    internal_limit: bool,
+    // torque constant for the hard stop current
+    k: f64,
+}
+
+impl Default for DriveObjects{
+    fn default() -> Self {
+        Self{controlword: 0, statusword: 0, modes_of_operation: MODE_PROFILE_POSITION, modes_of_operation_display: MODE_PROFILE_POSITION,
+            target_position: 0.0, target_velocity: 0.0, profile_velocity: 1000.0, profile_acceleration: 10000.0, profile_deceleration: 10000.0,
+            quick_stop_deceleration: 20000.0, home_offset: 0.0, homing_method: HOMING_CURRENT_POSITION,
+            homing_speed_switch: 300.0, homing_speed_zero: 30.0, homing_acceleration: 5000.0, homing_current_threshold: 2.0,
+            position_actual: 0.0, velocity_actual: 0.0, digital_inputs: 0}
+    }
+}
+
+impl PowerState{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            PowerState::NotReadyToSwitchOn => "Not ready to switch on",
+            PowerState::SwitchOnDisabled => "Switch on disabled",
+            PowerState::ReadyToSwitchOn => "Ready to switch on",
+            PowerState::SwitchedOn => "Switched on",
+            PowerState::OperationEnabled => "Operation enabled",
+            PowerState::QuickStopActive => "Quick stop active",
+            PowerState::FaultReactionActive => "Fault reaction active",
+            PowerState::Fault => "Fault",
+        }
+    }
+
+    pub fn from_statusword(statusword: u16) -> Self{
+        match (statusword & 0x6f, statusword & 0x4f){
+            (0x21, _) => PowerState::ReadyToSwitchOn,
+            (0x23, _) => PowerState::SwitchedOn,
+            (0x27, _) => PowerState::OperationEnabled,
+            (0x07, _) => PowerState::QuickStopActive,
+            (_, 0x40) => PowerState::SwitchOnDisabled,
+            (_, 0x0f) => PowerState::FaultReactionActive,
+            (_, 0x08) => PowerState::Fault,
+            _ => PowerState::NotReadyToSwitchOn,
+        }
+    }
+
+    fn get_statusword(&self) -> u16{
+        match self{
+            PowerState::NotReadyToSwitchOn => 0,
+            PowerState::SwitchOnDisabled => SW_SWITCH_ON_DISABLED,
+            PowerState::ReadyToSwitchOn => SW_QUICK_STOP | SW_READY_TO_SWITCH_ON,
+            PowerState::SwitchedOn => SW_QUICK_STOP | SW_SWITCHED_ON | SW_READY_TO_SWITCH_ON,
+            PowerState::OperationEnabled => SW_QUICK_STOP | SW_OPERATION_ENABLED | SW_SWITCHED_ON | SW_READY_TO_SWITCH_ON,
+            PowerState::QuickStopActive => SW_OPERATION_ENABLED | SW_SWITCHED_ON | SW_READY_TO_SWITCH_ON,
+            PowerState::FaultReactionActive => SW_FAULT | SW_OPERATION_ENABLED | SW_SWITCHED_ON | SW_READY_TO_SWITCH_ON,
+            PowerState::Fault => SW_FAULT,
+        }
+    }
+}
+
+pub fn get_homing_method_name(method: i8) -> &'static str{
+    match method{
+        HOMING_NEGATIVE_LIMIT_INDEX => "Negative limit switch and index",
+        HOMING_POSITIVE_LIMIT_INDEX => "Positive limit switch and index",
+        HOMING_HOME_SWITCH_INDEX => "Home switch and index",
+        HOMING_NEGATIVE_LIMIT => "Negative limit switch",
+        HOMING_POSITIVE_LIMIT => "Positive limit switch",
+        HOMING_HOME_SWITCH => "Home switch",
+        HOMING_INDEX_NEGATIVE => "Index, negative direction",
+        HOMING_INDEX_POSITIVE => "Index, positive direction",
+        HOMING_CURRENT_POSITION => "Current position",
+        HOMING_HARD_STOP_NEGATIVE => "Hard stop, negative direction",
+        HOMING_HARD_STOP_POSITIVE => "Hard stop, positive direction",
+        _ => "Unsupported",
+    }
+}
+
+// switch a homing method searches and releases
+fn homing_switch(method: i8, inputs: &SwitchInputs) -> bool{
+    match method{
+        HOMING_NEGATIVE_LIMIT_INDEX | HOMING_NEGATIVE_LIMIT => inputs.get_negative_limit(),
+        HOMING_POSITIVE_LIMIT_INDEX | HOMING_POSITIVE_LIMIT => inputs.get_positive_limit(),
+        HOMING_HOME_SWITCH_INDEX | HOMING_HOME_SWITCH => inputs.get_home(),
+        _ => false,
+    }
+}
+
+pub fn get_mode_name(mode: i8) -> &'static str{
+    match mode{
+        MODE_PROFILE_POSITION => "Profile position",
+        MODE_PROFILE_VELOCITY => "Profile velocity",
+        MODE_HOMING => "Homing",
+        MODE_CSP => "Cyclic synchronous position",
+        _ => "Unsupported",
+    }
+}
+
+impl DriveObjects{
+    pub fn set_controlword(&mut self) -> &mut u16{
+        &mut self.controlword
+    }
+
+    pub fn get_controlword(&self) -> u16{
+        self.controlword
+    }
+
+    pub fn get_statusword(&self) -> u16{
+        self.statusword
+    }
+
+    pub fn set_modes_of_operation(&mut self) -> &mut i8{
+        &mut self.modes_of_operation
+    }
+
+    pub fn get_modes_of_operation_display(&self) -> i8{
+        self.modes_of_operation_display
+    }
+
+    pub fn set_target_position(&mut self) -> &mut f64{
+        &mut self.target_position
+    }
+
+    pub fn set_target_velocity(&mut self) -> &mut f64{
+        &mut self.target_velocity
+    }
+
+    pub fn set_profile_velocity(&mut self) -> &mut f64{
+        &mut self.profile_velocity
+    }
+
+    pub fn set_profile_acceleration(&mut self) -> &mut f64{
+        &mut self.profile_acceleration
+    }
+
+    pub fn set_profile_deceleration(&mut self) -> &mut f64{
+        &mut self.profile_deceleration
+    }
+
+    pub fn set_quick_stop_deceleration(&mut self) -> &mut f64{
+        &mut self.quick_stop_deceleration
+    }
+
+    pub fn set_home_offset(&mut self) -> &mut f64{
+        &mut self.home_offset
+    }
+
+    pub fn set_homing_method(&mut self) -> &mut i8{
+        &mut self.homing_method
+    }
+
+    pub fn set_homing_speed_switch(&mut self) -> &mut f64{
+        &mut self.homing_speed_switch
+    }
+
+    pub fn set_homing_speed_zero(&mut self) -> &mut f64{
+        &mut self.homing_speed_zero
+    }
+
+    pub fn set_homing_acceleration(&mut self) -> &mut f64{
+        &mut self.homing_acceleration
+    }
+
+    pub fn set_homing_current_threshold(&mut self) -> &mut f64{
+        &mut self.homing_current_threshold
+    }
+
+    pub fn get_position_actual(&self) -> f64{
+        self.position_actual
+    }
+
+    pub fn get_velocity_actual(&self) -> f64{
+        self.velocity_actual
+    }
+
+    pub fn get_digital_inputs(&self) -> u32{
+        self.digital_inputs
+    }
+}
+
+impl Cia402{
+    pub fn new(objects: Arc<Mutex<DriveObjects>>, k: f64) -> Self{
+        let mode = objects.lock().unwrap().modes_of_operation;
+        Self{objects, state: PowerState::NotReadyToSwitchOn, mode, prev_controlword: 0, profile_pos: 0.0, profile_vel: 0.0,
+            setpoint: 0.0, setpoint_ack: false, home_position: 0.0, homing: HomingPhase::Idle, homing_method: HOMING_CURRENT_POSITION,
+            homing_attained: false, homing_error: false, home_event: None, fault_reset: false, internal_limit: false, k}
+    }
+
+    pub fn get_state(&self) -> PowerState{
+        self.state
+    }
+
+    pub fn get_mode(&self) -> i8{
+        self.mode
+    }
+
+    // Rising edge of the fault reset bit in the fault state, for the protection to be reset.
+    pub fn take_fault_reset(&mut self) -> bool{
+        std::mem::take(&mut self.fault_reset)
+    }
+
+    pub fn set_internal_limit(&mut self) -> &mut bool{
+        &mut self.internal_limit
+    }
+
+    // Position the drive homed at since the last call, in the controller frame.
+    pub fn take_home_event(&mut self) -> Option<f64>{
+        self.home_event.take()
+    }
+
+    fn transition(&mut self, controlword: u16, protection: DriveState, measurements: &Measurements){
+        let rising = controlword & !self.prev_controlword;
+        let command = controlword & 0x0f;
+        let disable_voltage = controlword & CW_ENABLE_VOLTAGE == 0;
+        let quick_stop = !disable_voltage && controlword & CW_QUICK_STOP == 0;
+        let prev = self.state;
+
+        self.state = match (self.state, protection){
+            (PowerState::Fault, _) if rising & CW_FAULT_RESET != 0 => {
+                self.fault_reset = true;
+                PowerState::SwitchOnDisabled
+            }
+            (PowerState::Fault, _) => PowerState::Fault,
+            (_, DriveState::Faulted(_)) => PowerState::Fault,
+            (_, DriveState::Stopping(_)) => PowerState::FaultReactionActive,
+            (PowerState::FaultReactionActive, DriveState::Running) => PowerState::Fault,
+            (PowerState::NotReadyToSwitchOn, _) => PowerState::SwitchOnDisabled,
+            (_, _) if disable_voltage => PowerState::SwitchOnDisabled,
+            (PowerState::OperationEnabled, _) if quick_stop => PowerState::QuickStopActive,
+            (PowerState::QuickStopActive, _) if command & 0x0f == ENABLE_OPERATION => PowerState::OperationEnabled,
+            (PowerState::QuickStopActive, _) => PowerState::QuickStopActive,
+            (_, _) if quick_stop => PowerState::SwitchOnDisabled,
+            (PowerState::SwitchOnDisabled, _) if command & 0x07 == SHUTDOWN => PowerState::ReadyToSwitchOn,
+            (PowerState::ReadyToSwitchOn, _) if command == ENABLE_OPERATION => PowerState::OperationEnabled,
+            (PowerState::ReadyToSwitchOn, _) if command & 0x07 == SWITCH_ON => PowerState::SwitchedOn,
+            (PowerState::SwitchedOn, _) if command == ENABLE_OPERATION => PowerState::OperationEnabled,
+            (PowerState::SwitchedOn | PowerState::OperationEnabled, _) if command & 0x07 == SHUTDOWN => PowerState::ReadyToSwitchOn,
+            (PowerState::OperationEnabled, _) if command & 0x0f == SWITCH_ON => PowerState::SwitchedOn,
+            (state, _) => state,
+        };
+
+        if self.state != prev{
+            match self.state{
+                // the profile starts from the standing motor when the power stage is enabled
+                PowerState::OperationEnabled if prev != PowerState::QuickStopActive => {
+                    self.profile_pos = measurements.get_position();
+                    self.profile_vel = 0.0;
+                    self.setpoint = self.profile_pos;
+                }
+                // and the quick stop ramp from the moving one
+                PowerState::QuickStopActive => {
+                    self.profile_pos = measurements.get_position();
+                    self.profile_vel = measurements.get_velocity();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Moves the profile reference towards target with the velocity limit and the acceleration limits.
+    fn profile_step(&mut self, target: Option<f64>, delta: f64, velocity: f64, acceleration: f64, deceleration: f64){
+        // a negative limit written by the master counts by its magnitude
+        let (acceleration, deceleration) = (acceleration.abs(), deceleration.abs());
+        // deg/s from rpm, as the angle integrates the velocity with gain 6
+        let desired = match target{
+            Some(target) => {
+                let distance = target - self.profile_pos;
+                distance.signum()*velocity.abs().min((2.0*deceleration*distance.abs()/6.0).sqrt())
+            }
+            None => velocity,
+        };
+        let limit = if desired.abs() > self.profile_vel.abs() && desired*self.profile_vel >= 0.0 {acceleration} else {deceleration};
+        self.profile_vel += (desired - self.profile_vel).clamp(-limit*delta, limit*delta);
+
+        let step = 6.0*self.profile_vel*delta;
+        match target{
+            Some(target) if (target - self.profile_pos).abs() <= step.abs().max(1e-9) && desired.abs() <= limit*delta => {
+                self.profile_pos = target;
+                self.profile_vel = 0.0;
+            }
+            _ => self.profile_pos += step,
+        }
+    }
+
+    fn set_home(&mut self, measurements: &Measurements){
+        self.home_position = measurements.get_position();
+        self.profile_pos = self.home_position;
+        self.profile_vel = 0.0;
+        self.setpoint = self.home_position;
+        self.homing = HomingPhase::Idle;
+        self.homing_attained = true;
+        self.home_event = Some(self.home_position);
+    }
+
+    fn start_homing(&mut self, method: i8, measurements: &Measurements, inputs: &SwitchInputs){
+        self.homing_method = method;
+        self.homing_attained = false;
+        self.homing_error = false;
+        self.homing = match method{
+            HOMING_NEGATIVE_LIMIT_INDEX | HOMING_NEGATIVE_LIMIT | HOMING_HARD_STOP_NEGATIVE => HomingPhase::Search(-1.0),
+            HOMING_POSITIVE_LIMIT_INDEX | HOMING_POSITIVE_LIMIT | HOMING_HARD_STOP_POSITIVE => HomingPhase::Search(1.0),
+            // the home switch edge is always passed in the negative direction
+            HOMING_HOME_SWITCH_INDEX | HOMING_HOME_SWITCH if inputs.get_home() => HomingPhase::Release(-1.0),
+            HOMING_HOME_SWITCH_INDEX | HOMING_HOME_SWITCH => HomingPhase::Search(1.0),
+            HOMING_INDEX_NEGATIVE => HomingPhase::Index(-1.0),
+            HOMING_INDEX_POSITIVE => HomingPhase::Index(1.0),
+            HOMING_CURRENT_POSITION => {
+                self.set_home(measurements);
+                HomingPhase::Idle
+            }
+            _ => {
+                self.homing_error = true;
+                HomingPhase::Idle
+            }
+        };
+    }
+
+    // Velocity references of the running homing phase, the motor holds its position otherwise.
+    fn homing_step(&mut self, delta: f64, objects: &DriveObjects, measurements: &Measurements, inputs: &SwitchInputs, halt: bool) -> References{
+        let (direction, speed) = match self.homing{
+            HomingPhase::Idle => return References::position(self.profile_pos),
+            HomingPhase::Search(direction) => (direction, objects.homing_speed_switch),
+            HomingPhase::Release(direction) | HomingPhase::Index(direction) => (direction, objects.homing_speed_zero),
+        };
+        let velocity = if halt {0.0} else {direction*speed.abs()};
+        self.profile_step(None, delta, velocity, objects.homing_acceleration, objects.homing_acceleration);
+        self.profile_pos = measurements.get_position();
+
+        let method = self.homing_method;
+        let hard_stop = matches!(method, HOMING_HARD_STOP_NEGATIVE | HOMING_HARD_STOP_POSITIVE);
+        // the current also rises while accelerating, the stop is only detected once the motor is held back
+        let stopped = hard_stop && self.profile_vel == velocity && velocity != 0.0 && measurements.get_velocity().abs() < 0.1*speed.abs()
+            && (measurements.get_torque()/self.k).abs() > objects.homing_current_threshold;
+        // limit switches are passed on the way to a mechanical stop
+        let blocked = !hard_stop && ((direction < 0.0 && inputs.get_negative_limit()) || (direction > 0.0 && inputs.get_positive_limit()));
+
+        match self.homing{
+            HomingPhase::Search(_) if stopped => self.set_home(measurements),
+            HomingPhase::Search(direction) if homing_switch(method, inputs) => self.homing = HomingPhase::Release(-direction),
+            HomingPhase::Release(direction) if !homing_switch(method, inputs) => match method{
+                HOMING_NEGATIVE_LIMIT_INDEX | HOMING_POSITIVE_LIMIT_INDEX | HOMING_HOME_SWITCH_INDEX => self.homing = HomingPhase::Index(direction),
+                _ => self.set_home(measurements),
+            },
+            HomingPhase::Index(_) if inputs.get_index() => self.set_home(measurements),
+            _ if blocked => {
+                self.homing_error = true;
+                self.homing = HomingPhase::Idle;
+                self.profile_vel = 0.0;
+            }
+            _ => {}
+        }
+        match self.homing{
+            HomingPhase::Idle => References::position(self.profile_pos),
+            _ => References::velocity(self.profile_vel),
+        }
+    }
+
+    // References for the control law, the measured position is in the controller frame.
+    pub fn update(&mut self, delta: f64, measurements: &Measurements, inputs: &SwitchInputs, protection: DriveState) -> References{
+        let objects = *self.objects.lock().unwrap();
+        let controlword = objects.controlword;
+        self.transition(controlword, protection, measurements);
+        let rising = controlword & !self.prev_controlword;
+        self.prev_controlword = controlword;
+
+        if self.state != PowerState::OperationEnabled{
+            self.mode = objects.modes_of_operation;
+        } else if objects.modes_of_operation != self.mode{
+            // mode change while enabled, continue from where the motor is
+            self.mode = objects.modes_of_operation;
+            self.profile_pos = measurements.get_position();
+            self.profile_vel = 0.0;
+            self.setpoint = self.profile_pos;
+        }
+        // homing stops with the power stage, a mode change or the start bit cleared
+        if self.state != PowerState::OperationEnabled || self.mode != MODE_HOMING || controlword & CW_NEW_SETPOINT == 0{
+            self.homing = HomingPhase::Idle;
+        }
+
+        let to_internal = |position: f64| position + self.home_position - objects.home_offset;
+        let halt = controlword & CW_HALT != 0;
+        let mut target_reached = false;
+        let mut references = References::position(self.profile_pos);
+
+        match self.state{
+            PowerState::QuickStopActive => {
+                self.profile_step(None, delta, 0.0, objects.quick_stop_deceleration, objects.quick_stop_deceleration);
+                references = References::position(self.profile_pos);
+                target_reached = self.profile_vel == 0.0;
+            }
+            PowerState::OperationEnabled => match self.mode{
+                MODE_PROFILE_POSITION => {
+                    if rising & CW_NEW_SETPOINT != 0{
+                        self.setpoint = if controlword & CW_RELATIVE != 0 {self.setpoint + objects.target_position} else {to_internal(objects.target_position)};
+                    }
+                    self.setpoint_ack = controlword & CW_NEW_SETPOINT != 0;
+                    let target = if halt {None} else {Some(self.setpoint)};
+                    let velocity = if halt {0.0} else {objects.profile_velocity};
+                    self.profile_step(target, delta, velocity, objects.profile_acceleration, objects.profile_deceleration);
+                    references = References::position(self.profile_pos);
+                    target_reached = (halt && self.profile_vel == 0.0) || (self.profile_pos == self.setpoint && (measurements.get_position() - self.setpoint).abs() < POSITION_WINDOW);
+                }
+                MODE_PROFILE_VELOCITY => {
+                    let velocity = if halt {0.0} else {objects.target_velocity};
+                    self.profile_step(None, delta, velocity, objects.profile_acceleration, objects.profile_deceleration);
+                    self.profile_pos = measurements.get_position();
+                    references = References::velocity(self.profile_vel);
+                    target_reached = (measurements.get_velocity() - velocity).abs() < VELOCITY_WINDOW;
+                }
+                MODE_CSP => {
+                    self.profile_pos = to_internal(objects.target_position);
+                    self.profile_vel = 0.0;
+                    references = References::position(self.profile_pos);
+                    target_reached = (measurements.get_position() - self.profile_pos).abs() < POSITION_WINDOW;
+                }
+                MODE_HOMING => {
+                    if rising & CW_NEW_SETPOINT != 0{
+                        self.start_homing(objects.homing_method, measurements, inputs);
+                    }
+                    references = self.homing_step(delta, &objects, measurements, inputs, halt);
+                    target_reached = self.homing == HomingPhase::Idle;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+
+        // homing results are reported while the start bit is held
+        let homing_start = controlword & CW_NEW_SETPOINT != 0;
+        let mode_12 = match self.mode{
+            MODE_PROFILE_POSITION => self.setpoint_ack,
+            MODE_PROFILE_VELOCITY => measurements.get_velocity().abs() < VELOCITY_WINDOW,
+            MODE_HOMING => self.homing_attained && homing_start,
+            MODE_CSP => self.state == PowerState::OperationEnabled,
+            _ => false,
+        };
+        let mut statusword = self.state.get_statusword() | SW_REMOTE;
+        if !matches!(self.state, PowerState::NotReadyToSwitchOn | PowerState::SwitchOnDisabled | PowerState::Fault){
+            statusword |= SW_VOLTAGE_ENABLED;
+        }
+        if target_reached{
+            statusword |= SW_TARGET_REACHED;
+        }
+        if self.internal_limit{
+            statusword |= SW_INTERNAL_LIMIT;
+        }
+        if mode_12{
+            statusword |= SW_MODE_12;
+        }
+        if self.mode == MODE_HOMING && self.homing_error && homing_start{
+            statusword |= SW_MODE_13;
+        }
+
+        let mut objects = self.objects.lock().unwrap();
+        objects.statusword = statusword;
+        objects.modes_of_operation_display = self.mode;
+        objects.position_actual = measurements.get_position() - self.home_position + objects.home_offset;
+        objects.velocity_actual = measurements.get_velocity();
+        objects.digital_inputs = [(inputs.get_negative_limit(), DI_NEGATIVE_LIMIT), (inputs.get_positive_limit(), DI_POSITIVE_LIMIT), (inputs.get_home(), DI_HOME_SWITCH)]
+            .iter().filter(|(active, _)| *active).fold(0, |bits, (_, bit)| bits | bit);
+        references
+    }
+
+    // Voltage that reaches the motor, the power stage is only on when operation is enabled.
+    pub fn actuate(&self, motor: &mut Motor, voltage: f64) -> f64{
+        match self.state{
+            PowerState::OperationEnabled | PowerState::QuickStopActive | PowerState::FaultReactionActive => voltage,
+            _ => {
+                *motor.set_open_circuit() = true;
+                0.0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::protection::Trip;
+
+    fn drive() -> Cia402{
+        Cia402::new(Arc::new(Mutex::new(DriveObjects::default())), 0.1)
+    }
+
+    // Applies the controlwords in turn and returns the state after each.
+    fn run(drive: &mut Cia402, controlwords: &[u16], protection: DriveState) -> Vec<PowerState>{
+        let measurements = Measurements::new(12.0, 0.0, 0.0);
+        controlwords.iter().map(|controlword|{
+            drive.transition(*controlword, protection, &measurements);
+            drive.prev_controlword = *controlword;
+            drive.state
+        }).collect()
+    }
+
+    #[test]
+    fn enable_sequence(){
+        let mut drive = drive();
+        assert_eq!(run(&mut drive, &[DISABLE_VOLTAGE, SHUTDOWN, SWITCH_ON, ENABLE_OPERATION], DriveState::Running),
+            vec![PowerState::SwitchOnDisabled, PowerState::ReadyToSwitchOn, PowerState::SwitchedOn, PowerState::OperationEnabled]);
+        // the profile starts at the motor
+        assert_eq!(drive.profile_pos, 12.0);
+        assert_eq!(run(&mut drive, &[SWITCH_ON, SHUTDOWN, DISABLE_VOLTAGE], DriveState::Running),
+            vec![PowerState::SwitchedOn, PowerState::ReadyToSwitchOn, PowerState::SwitchOnDisabled]);
+        // enable operation straight from ready to switch on
+        assert_eq!(run(&mut drive, &[SHUTDOWN, ENABLE_OPERATION], DriveState::Running),
+            vec![PowerState::ReadyToSwitchOn, PowerState::OperationEnabled]);
+    }
+
+    #[test]
+    fn quick_stop(){
+        let mut drive = drive();
+        run(&mut drive, &[DISABLE_VOLTAGE, SHUTDOWN, ENABLE_OPERATION], DriveState::Running);
+        assert_eq!(run(&mut drive, &[QUICK_STOP, QUICK_STOP, ENABLE_OPERATION], DriveState::Running),
+            vec![PowerState::QuickStopActive, PowerState::QuickStopActive, PowerState::OperationEnabled]);
+        assert_eq!(run(&mut drive, &[QUICK_STOP, DISABLE_VOLTAGE], DriveState::Running),
+            vec![PowerState::QuickStopActive, PowerState::SwitchOnDisabled]);
+        // outside operation a quick stop disables
+        assert_eq!(run(&mut drive, &[SHUTDOWN, QUICK_STOP], DriveState::Running),
+            vec![PowerState::ReadyToSwitchOn, PowerState::SwitchOnDisabled]);
+    }
+
+    #[test]
+    fn fault_and_reset(){
+        let mut drive = drive();
+        run(&mut drive, &[DISABLE_VOLTAGE, SHUTDOWN, ENABLE_OPERATION], DriveState::Running);
+        assert_eq!(run(&mut drive, &[ENABLE_OPERATION], DriveState::Stopping(Trip::Overcurrent)), vec![PowerState::FaultReactionActive]);
+        assert_eq!(run(&mut drive, &[ENABLE_OPERATION], DriveState::Faulted(Trip::Overcurrent)), vec![PowerState::Fault]);
+        // the fault stays until a rising edge of the reset bit
+        assert_eq!(run(&mut drive, &[ENABLE_OPERATION], DriveState::Running), vec![PowerState::Fault]);
+        assert!(!drive.take_fault_reset());
+        assert_eq!(run(&mut drive, &[CW_FAULT_RESET], DriveState::Running), vec![PowerState::SwitchOnDisabled]);
+        assert!(drive.take_fault_reset());
+        assert_eq!(run(&mut drive, &[CW_FAULT_RESET | SHUTDOWN], DriveState::Running), vec![PowerState::ReadyToSwitchOn]);
+        // a protection back to running ends the fault reaction in the fault state
+        run(&mut drive, &[ENABLE_OPERATION], DriveState::Stopping(Trip::Stall));
+        assert_eq!(run(&mut drive, &[ENABLE_OPERATION], DriveState::Running), vec![PowerState::Fault]);
+    }
+
+    #[test]
+    fn statusword_round_trip(){
+        for state in [PowerState::NotReadyToSwitchOn, PowerState::SwitchOnDisabled, PowerState::ReadyToSwitchOn, PowerState::SwitchedOn,
+            PowerState::OperationEnabled, PowerState::QuickStopActive, PowerState::FaultReactionActive, PowerState::Fault]{
+            assert_eq!(PowerState::from_statusword(state.get_statusword() | SW_VOLTAGE_ENABLED | SW_REMOTE), state);
+        }
+    }
+
+    #[test]
+    fn negative_acceleration(){
+        let mut drive = drive();
+        drive.profile_step(Some(90.0), 0.001, 1000.0, -10000.0, -10000.0);
+        assert!(drive.profile_vel > 0.0 && drive.profile_vel.is_finite());
+    }
+}
@@ -0,0 +1,217 @@
+use super::{Config, ControlType};
+
+// Tuned gains of one loop, in the units of the simulator.
+struct Loop{
+    name: &'static str,
+    kp: f64,
+    ki: f64,
+    kd: f64,
+}
+
+// Shortest literal that reads back to the same value, valid in C and Rust for the values checked by loops.
+fn literal(value: f64) -> String{
+    let text = format!("{:?}", value);
+    if text.contains(['.', 'e']) {text} else {text + ".0"}
+}
+
+// Loops the control structure uses, the position one alone drives the voltage in Pos control.
+// Every exported value has to be a finite f32, there are no portable literals for the others.
+fn loops(config: &Config) -> Result<Vec<Loop>, String>{
+    let pid_conf = config.get_pid_conf();
+    let pid_loop = |name, index: usize| Loop{name, kp: pid_conf[index].get_kp(), ki: pid_conf[index].get_ki(), kd: pid_conf[index].get_kd()};
+    let loops = match config.controller.control_option{
+        ControlType::Pos => vec![pid_loop("POS", 0)],
+        ControlType::PosVelTrq => vec![pid_loop("POS", 0), pid_loop("VEL", 1), pid_loop("TRQ", 2)],
+        ControlType::Custom(_) => return Err("Custom control laws can not be exported".to_string()),
+    };
+    let frequency = config.controller.frequency;
+    let values = loops.iter().flat_map(|pid_loop| [(pid_loop.name, "gain", pid_loop.kp), (pid_loop.name, "gain", pid_loop.ki), (pid_loop.name, "gain", pid_loop.kd)])
+        .chain(bounds(config).into_iter().map(|(name, bound)| (name, "bound", bound)))
+        .chain([("FREQUENCY", "frequency", frequency), ("HALF_PERIOD", "frequency", 0.5/frequency)]);
+    for (name, kind, value) in values{
+        if !value.is_finite() || value.abs() > f32::MAX as f64{
+            return Err(format!("The {} of {} is {}, only finite single precision values can be exported", kind, name, value));
+        }
+    }
+    Ok(loops)
+}
+
+// Output bounds the loops clamp to.
+fn bounds(config: &Config) -> Vec<(&'static str, f64)>{
+    let controller = config.controller;
+    match controller.control_option{
+        ControlType::Pos => vec![("VLTG_BOUND", controller.vltg_bound)],
+        _ => vec![("VEL_BOUND", controller.vel_bound), ("TRQ_BOUND", controller.trq_bound), ("VLTG_BOUND", controller.vltg_bound)],
+    }
+}
+
+fn summary(config: &Config) -> Vec<String>{
+    let controller = config.controller;
+    let structure = match controller.control_option{
+        ControlType::Pos => "position loop driving the voltage",
+        _ => "position, speed and torque loops in cascade",
+    };
+    let mut lines = vec![format!("Generated by motorsim: {} at {} hz.", structure, controller.get_frequency()),
+        "Angles in deg, speeds in rpm, torques in N*m, voltages in V.".to_string(),
+        "Every PID is kp*e + kd*de/dt + ki*integral(e), trapezoidal integral and backward difference,".to_string(),
+        "the output is clamped to the bound of its loop. Call one step function per period.".to_string()];
+    if config.get_dob_conf().get_enabled(){
+        lines.push("The disturbance observer of the simulator is not exported.".to_string());
+    }
+    lines
+}
+
+// Header and source of a C99 module for name.h and name.c, the name also prefixes the types and functions.
+pub fn to_c(config: &Config, name: &str) -> Result<(String, String), String>{
+    let loops = loops(config)?;
+    let controller = config.controller;
+    let prefix: String = name.chars().map(|c| if c.is_ascii_alphanumeric() {c} else {'_'}).collect();
+    let upper = prefix.to_uppercase();
+
+    let mut header = String::new();
+    for line in summary(config){
+        header += &format!("/* {} */\n", line);
+    }
+    header += &format!("#ifndef {upper}_H\n#define {upper}_H\n\n");
+    header += &format!("typedef struct {{\n    float prev_error;\n    float integral;\n}} {prefix}_pid_t;\n\n");
+    header += &format!("typedef struct {{\n    {prefix}_pid_t pos;\n    {prefix}_pid_t vel;\n    {prefix}_pid_t trq;\n}} {prefix}_t;\n\n");
+    header += &format!("void {prefix}_reset({prefix}_t *controller);\n");
+    header += "/* voltage for a position reference */\n";
+    header += &format!("float {prefix}_position_step({prefix}_t *controller, float pos_ref, float pos, float vel, float trq);\n");
+    if loops.len() > 1{
+        header += "/* voltage for a speed reference */\n";
+        header += &format!("float {prefix}_velocity_step({prefix}_t *controller, float vel_ref, float vel, float trq);\n");
+        header += "/* voltage for a torque reference */\n";
+        header += &format!("float {prefix}_torque_step({prefix}_t *controller, float trq_ref, float trq);\n");
+    }
+    header += "\n#endif\n";
+
+    let mut source = format!("#include \"{name}.h\"\n\n");
+    source += &format!("#define FREQUENCY {}f\n#define HALF_PERIOD {}f\n\n", literal(controller.frequency), literal(0.5/controller.frequency));
+    for pid_loop in &loops{
+        source += &format!("#define {name}_KP {}f\n#define {name}_KI {}f\n#define {name}_KD {}f\n", literal(pid_loop.kp), literal(pid_loop.ki), literal(pid_loop.kd), name = pid_loop.name);
+    }
+    source += "\n";
+    for (name, bound) in bounds(config){
+        source += &format!("#define {} {}f\n", name, literal(bound));
+    }
+    source += "\n";
+    source += &format!("static float pid_step({prefix}_pid_t *pid, float kp, float ki, float kd, float error, float bound)\n{{\n");
+    source += "    float derivative = (error - pid->prev_error)*FREQUENCY;\n";
+    source += "    float result;\n";
+    source += "    pid->integral += HALF_PERIOD*(error + pid->prev_error);\n";
+    source += "    pid->prev_error = error;\n";
+    source += "    result = kp*error + kd*derivative + ki*pid->integral;\n";
+    source += "    if (result > bound) return bound;\n";
+    source += "    if (result < -bound) return -bound;\n";
+    source += "    return result;\n}\n\n";
+    source += &format!("void {prefix}_reset({prefix}_t *controller)\n{{\n");
+    for pid in ["pos", "vel", "trq"]{
+        source += &format!("    controller->{pid}.prev_error = 0.0f;\n    controller->{pid}.integral = 0.0f;\n");
+    }
+    source += "}\n\n";
+    match controller.control_option{
+        ControlType::Pos => {
+            source += &format!("float {prefix}_position_step({prefix}_t *controller, float pos_ref, float pos, float vel, float trq)\n{{\n");
+            source += "    (void)vel;\n    (void)trq;\n";
+            source += "    return pid_step(&controller->pos, POS_KP, POS_KI, POS_KD, pos_ref - pos, VLTG_BOUND);\n}\n";
+        }
+        _ => {
+            source += &format!("float {prefix}_torque_step({prefix}_t *controller, float trq_ref, float trq)\n{{\n");
+            source += "    return pid_step(&controller->trq, TRQ_KP, TRQ_KI, TRQ_KD, trq_ref - trq, VLTG_BOUND);\n}\n\n";
+            source += &format!("float {prefix}_velocity_step({prefix}_t *controller, float vel_ref, float vel, float trq)\n{{\n");
+            source += "    float trq_ref = pid_step(&controller->vel, VEL_KP, VEL_KI, VEL_KD, vel_ref - vel, TRQ_BOUND);\n";
+            source += &format!("    return {prefix}_torque_step(controller, trq_ref, trq);\n}}\n\n");
+            source += &format!("float {prefix}_position_step({prefix}_t *controller, float pos_ref, float pos, float vel, float trq)\n{{\n");
+            source += "    float vel_ref = pid_step(&controller->pos, POS_KP, POS_KI, POS_KD, pos_ref - pos, VEL_BOUND);\n";
+            source += &format!("    return {prefix}_velocity_step(controller, vel_ref, vel, trq);\n}}\n");
+        }
+    }
+    Ok((header, source))
+}
+
+// Module for no_std crates, only uses core.
+pub fn to_rust(config: &Config) -> Result<String, String>{
+    let loops = loops(config)?;
+    let controller = config.controller;
+    let pids: Vec<String> = loops.iter().map(|pid_loop| pid_loop.name.to_lowercase()).collect();
+
+    let mut source = String::new();
+    for line in summary(config){
+        source += &format!("// {}\n", line);
+    }
+    source += &format!("\nconst FREQUENCY: f32 = {};\nconst HALF_PERIOD: f32 = {};\n\n", literal(controller.frequency), literal(0.5/controller.frequency));
+    for pid_loop in &loops{
+        source += &format!("const {name}: Gains = Gains{{kp: {}, ki: {}, kd: {}}};\n", literal(pid_loop.kp), literal(pid_loop.ki), literal(pid_loop.kd), name = pid_loop.name);
+    }
+    source += "\n";
+    for (name, bound) in bounds(config){
+        source += &format!("pub const {}: f32 = {};\n", name, literal(bound));
+    }
+    source += "\n";
+    source += "struct Gains{\n    kp: f32,\n    ki: f32,\n    kd: f32,\n}\n\n";
+    source += "#[derive(Clone, Copy)]\nstruct Pid{\n    prev_error: f32,\n    integral: f32,\n}\n\n";
+    source += "#[derive(Clone, Copy)]\npub struct Controller{\n";
+    for pid in &pids{
+        source += &format!("    {pid}: Pid,\n");
+    }
+    source += "}\n\n";
+    source += "impl Pid{\n    fn step(&mut self, gains: &Gains, error: f32, bound: f32) -> f32{\n";
+    source += "        let derivative = (error - self.prev_error)*FREQUENCY;\n";
+    source += "        self.integral += HALF_PERIOD*(error + self.prev_error);\n";
+    source += "        self.prev_error = error;\n";
+    source += "        (gains.kp*error + gains.kd*derivative + gains.ki*self.integral).clamp(-bound, bound)\n    }\n}\n\n";
+    source += "impl Controller{\n";
+    let fields: Vec<String> = pids.iter().map(|pid| format!("{pid}: Pid{{prev_error: 0.0, integral: 0.0}}")).collect();
+    source += &format!("    pub const fn new() -> Self{{\n        Self{{{}}}\n    }}\n\n", fields.join(", "));
+    source += "    pub fn reset(&mut self){\n        *self = Self::new();\n    }\n\n";
+    match controller.control_option{
+        ControlType::Pos => {
+            source += "    // voltage for a position reference\n";
+            source += "    pub fn position_step(&mut self, pos_ref: f32, pos: f32, _vel: f32, _trq: f32) -> f32{\n";
+            source += "        self.pos.step(&POS, pos_ref - pos, VLTG_BOUND)\n    }\n";
+        }
+        _ => {
+            source += "    // voltage for a position reference\n";
+            source += "    pub fn position_step(&mut self, pos_ref: f32, pos: f32, vel: f32, trq: f32) -> f32{\n";
+            source += "        let vel_ref = self.pos.step(&POS, pos_ref - pos, VEL_BOUND);\n";
+            source += "        self.velocity_step(vel_ref, vel, trq)\n    }\n\n";
+            source += "    // voltage for a speed reference\n";
+            source += "    pub fn velocity_step(&mut self, vel_ref: f32, vel: f32, trq: f32) -> f32{\n";
+            source += "        let trq_ref = self.vel.step(&VEL, vel_ref - vel, TRQ_BOUND);\n";
+            source += "        self.torque_step(trq_ref, trq)\n    }\n\n";
+            source += "    // voltage for a torque reference\n";
+            source += "    pub fn torque_step(&mut self, trq_ref: f32, trq: f32) -> f32{\n";
+            source += "        self.trq.step(&TRQ, trq_ref - trq, VLTG_BOUND)\n    }\n";
+        }
+    }
+    source += "}\n\n";
+    source += "impl Default for Controller{\n    fn default() -> Self{\n        Self::new()\n    }\n}\n";
+    Ok(source)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn literals(){
+        assert_eq!(literal(40.0), "40.0");
+        assert_eq!(literal(0.001), "0.001");
+        assert_eq!(literal(1e-7), "1e-7");
+    }
+
+    #[test]
+    fn rejects_non_finite(){
+        let mut config = Config::default();
+        assert!(to_rust(&config).is_ok());
+        *config.set_pid_conf()[1].set_ki() = f64::INFINITY;
+        assert!(to_c(&config, "drive").unwrap_err().contains("VEL"));
+        let mut config = Config::default();
+        *config.set_controller_conf().set_trq_bound() = f64::NAN;
+        assert!(to_rust(&config).unwrap_err().contains("TRQ_BOUND"));
+        let mut config = Config::default();
+        *config.set_controller_conf().set_vltg_bound() = 1e300;
+        assert!(to_rust(&config).is_err());
+    }
+}
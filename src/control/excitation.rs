@@ -0,0 +1,95 @@
+use std::f64::consts::PI;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ExcitationType{
+    Chirp,
+    SteppedSine,
+}
+
+// Signal added to the calibration reference of the chosen loop, spans the calibration duration.
+#[derive(Copy, Clone)]
+pub struct ConfigExcitation{
+    enabled: bool,
+    option: ExcitationType,
+    f_start: f64,
+    f_end: f64,
+    amplitude: f64,
+    steps: usize,
+}
+
+impl Default for ConfigExcitation{
+    fn default() -> Self {
+        Self{enabled: false, option: ExcitationType::Chirp, f_start: 1.0, f_end: 200.0, amplitude: 0.05, steps: 20}
+    }
+}
+
+impl ExcitationType{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            ExcitationType::Chirp => "Chirp",
+            ExcitationType::SteppedSine => "Stepped sine",
+        }
+    }
+}
+
+impl ConfigExcitation{
+    pub fn set_enabled(&mut self) -> &mut bool{
+        &mut self.enabled
+    }
+
+    pub fn get_enabled(&self) -> bool{
+        self.enabled
+    }
+
+    pub fn set_option(&mut self) -> &mut ExcitationType{
+        &mut self.option
+    }
+
+    pub fn set_f_start(&mut self) -> &mut f64{
+        &mut self.f_start
+    }
+
+    pub fn get_f_start(&self) -> f64{
+        self.f_start
+    }
+
+    pub fn set_f_end(&mut self) -> &mut f64{
+        &mut self.f_end
+    }
+
+    pub fn get_f_end(&self) -> f64{
+        self.f_end
+    }
+
+    // relative to the calibration step
+    pub fn set_amplitude(&mut self) -> &mut f64{
+        &mut self.amplitude
+    }
+
+    pub fn set_steps(&mut self) -> &mut usize{
+        &mut self.steps
+    }
+
+    // Logarithmic sweep or log spaced sine dwells from f_start to f_end over duration.
+    pub fn get_value(&self, time: f64, duration: f64, step: f64) -> f64{
+        if !self.enabled || time > duration{
+            return 0.0;
+        }
+        let amplitude = self.amplitude*step;
+        let ratio = self.f_end/self.f_start;
+        match self.option{
+            ExcitationType::Chirp => {
+                // the limit of the sweep for equal frequencies is a plain sine
+                let phase = if ratio == 1.0 {2.0*PI*self.f_start*time} else {2.0*PI*self.f_start*duration/ratio.ln()*(ratio.powf(time/duration) - 1.0)};
+                amplitude*phase.sin()
+            }
+            ExcitationType::SteppedSine => {
+                let steps = self.steps.max(1);
+                let dwell = duration/steps as f64;
+                let index = ((time/dwell) as usize).min(steps - 1);
+                let freq = if steps > 1 {self.f_start*ratio.powf(index as f64/(steps - 1) as f64)} else {self.f_start};
+                amplitude*(2.0*PI*freq*(time - index as f64*dwell)).sin()
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::{collections::VecDeque, io::ErrorKind, net::UdpSocket, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use super::{Config, law::{ControlLaw, Measurements, References}};
+
+// Packets are little endian, see the README.
+pub const PACKET_MEASUREMENTS: u8 = 0x11;
+pub const PACKET_VOLTAGE: u8 = 0x91;
+// kind, sequence, time, delta, reference mask, pos, vel and trq references, position, velocity, torque
+pub const MEASUREMENTS_LEN: usize = 1 + 4 + 8 + 8 + 1 + 6*8;
+// kind, sequence, voltage V
+pub const VOLTAGE_LEN: usize = 1 + 4 + 8;
+// latencies kept for the statistics
+const LATENCIES_LEN: usize = 1000;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum TimeoutAction{
+    // the previous voltage stays applied
+    Hold,
+    Zero,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConfigExternal{
+    // of the controller process on 127.0.0.1
+    port: u16,
+    // ms
+    timeout: f64,
+    on_timeout: TimeoutAction,
+}
+
+// Of one law instance, shared with the UI. Latencies in sec.
+#[derive(Default)]
+pub struct ExternalStatus{
+    steps: usize,
+    timeouts: usize,
+    error: Option<String>,
+    max_latency: f64,
+    latencies: VecDeque<f64>,
+}
+
+// Status of the law the live controller built last.
+pub type LiveStatus = Arc<Mutex<Arc<Mutex<ExternalStatus>>>>;
+
+// Hands every control period to an external process over UDP and applies the voltage it answers.
+pub struct ExternalLaw{
+    config: ConfigExternal,
+    socket: Option<UdpSocket>,
+    status: Arc<Mutex<ExternalStatus>>,
+    sequence: u32,
+    time: f64,
+    voltage: f64,
+}
+
+impl Default for ConfigExternal{
+    fn default() -> Self {
+        Self{port: 5006, timeout: 2.0, on_timeout: TimeoutAction::Hold}
+    }
+}
+
+impl TimeoutAction{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            TimeoutAction::Hold => "Hold the voltage",
+            TimeoutAction::Zero => "Zero voltage",
+        }
+    }
+}
+
+impl ConfigExternal{
+    pub fn set_port(&mut self) -> &mut u16{
+        &mut self.port
+    }
+
+    pub fn set_timeout(&mut self) -> &mut f64{
+        &mut self.timeout
+    }
+
+    pub fn set_on_timeout(&mut self) -> &mut TimeoutAction{
+        &mut self.on_timeout
+    }
+}
+
+impl ExternalStatus{
+    fn record(&mut self, latency: Option<f64>){
+        self.steps += 1;
+        match latency{
+            Some(latency) => {
+                self.max_latency = self.max_latency.max(latency);
+                self.latencies.push_back(latency);
+                if self.latencies.len() > LATENCIES_LEN{
+                    self.latencies.pop_front();
+                }
+            }
+            None => self.timeouts += 1,
+        }
+    }
+
+    pub fn get_steps(&self) -> usize{
+        self.steps
+    }
+
+    pub fn get_timeouts(&self) -> usize{
+        self.timeouts
+    }
+
+    pub fn get_error(&self) -> &Option<String>{
+        &self.error
+    }
+
+    pub fn get_max_latency(&self) -> f64{
+        self.max_latency
+    }
+
+    pub fn get_latencies(&self) -> Vec<f64>{
+        self.latencies.iter().copied().collect()
+    }
+
+    // over the kept replies
+    pub fn get_mean_latency(&self) -> f64{
+        self.latencies.iter().fold(0.0, |sum, latency| sum + latency)/self.latencies.len().max(1) as f64
+    }
+}
+
+fn measurements_packet(sequence: u32, time: f64, delta: f64, measurements: &Measurements, references: &References) -> [u8; MEASUREMENTS_LEN]{
+    let mut packet = [0; MEASUREMENTS_LEN];
+    packet[0] = PACKET_MEASUREMENTS;
+    packet[1..5].copy_from_slice(&sequence.to_le_bytes());
+    packet[5..13].copy_from_slice(&time.to_le_bytes());
+    packet[13..21].copy_from_slice(&delta.to_le_bytes());
+    let references = [references.get_pos(), references.get_vel(), references.get_trq()];
+    packet[21] = references.iter().enumerate().fold(0, |mask, (bit, reference)| if reference.is_some() {mask | 1 << bit} else {mask});
+    let values = references.map(|reference| reference.unwrap_or(0.0)).into_iter()
+        .chain([measurements.get_position(), measurements.get_velocity(), measurements.get_torque()]);
+    for (index, value) in values.enumerate(){
+        packet[22 + 8*index..30 + 8*index].copy_from_slice(&value.to_le_bytes());
+    }
+    packet
+}
+
+fn parse_voltage(packet: &[u8]) -> Option<(u32, f64)>{
+    if packet.len() != VOLTAGE_LEN || packet[0] != PACKET_VOLTAGE{
+        return None;
+    }
+    let voltage = f64::from_le_bytes(packet[5..13].try_into().unwrap());
+    voltage.is_finite().then(|| (u32::from_le_bytes(packet[1..5].try_into().unwrap()), voltage))
+}
+
+impl ExternalLaw{
+    pub fn new(config: &Config) -> Self{
+        let external = *config.get_external_conf();
+        let socket = UdpSocket::bind("127.0.0.1:0").and_then(|socket| socket.connect(("127.0.0.1", external.port)).map(|_| socket));
+        let mut status = ExternalStatus::default();
+        let socket = match socket{
+            Ok(socket) => Some(socket),
+            Err(error) => {
+                status.error = Some(error.to_string());
+                None
+            }
+        };
+        Self{config: external, socket, status: Arc::new(Mutex::new(status)), sequence: 0, time: 0.0, voltage: 0.0}
+    }
+
+    pub fn get_status(&self) -> Arc<Mutex<ExternalStatus>>{
+        Arc::clone(&self.status)
+    }
+
+    // Voltage answered to the current sequence within the timeout, stale answers are dropped.
+    fn exchange(&self, packet: &[u8]) -> Option<f64>{
+        let socket = self.socket.as_ref()?;
+        let start = Instant::now();
+        let timeout = Duration::from_secs_f64(self.config.timeout.max(0.0)/1000.0);
+        socket.send(packet).ok()?;
+        let mut buffer = [0; 64];
+        loop{
+            let remaining = timeout.checked_sub(start.elapsed()).filter(|remaining| !remaining.is_zero())?;
+            socket.set_read_timeout(Some(remaining)).ok()?;
+            match socket.recv(&mut buffer){
+                Ok(len) => {
+                    if let Some((sequence, voltage)) = parse_voltage(&buffer[..len]){
+                        if sequence == self.sequence{
+                            return Some(voltage);
+                        }
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                // timed out, or the port was refused with no controller listening yet
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+impl ControlLaw for ExternalLaw{
+    fn generate_control(&mut self, measurements: &Measurements, references: &References, delta: f64) -> f64{
+        self.sequence = self.sequence.wrapping_add(1);
+        self.time += delta;
+        let start = Instant::now();
+        let voltage = self.exchange(&measurements_packet(self.sequence, self.time, delta, measurements, references));
+        self.status.lock().unwrap().record(voltage.map(|_| start.elapsed().as_secs_f64()));
+        self.voltage = match (voltage, self.config.on_timeout){
+            (Some(voltage), _) => voltage,
+            (None, TimeoutAction::Hold) => self.voltage,
+            (None, TimeoutAction::Zero) => 0.0,
+        };
+        self.voltage
+    }
+}
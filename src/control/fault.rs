@@ -0,0 +1,165 @@
+use super::{law::Measurements, Motor};
+
+pub const FAULT_SLOTS: usize = 4;
+
+// single sample spikes every SPIKE_INTERVAL periods
+const SPIKE_INTERVAL: usize = 10;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum FaultType{
+    EncoderLoss,
+    EncoderFreeze,
+    VelocitySpike,
+    CurrentStuck,
+    Disconnect,
+    BusDrop,
+    LoadJam,
+}
+
+pub const FAULT_TYPES: [FaultType; 7] = [FaultType::EncoderLoss, FaultType::EncoderFreeze, FaultType::VelocitySpike, FaultType::CurrentStuck,
+    FaultType::Disconnect, FaultType::BusDrop, FaultType::LoadJam];
+
+// Active from start for duration seconds of the run, value depends on the type.
+#[derive(Copy, Clone)]
+pub struct ConfigFault{
+    enabled: bool,
+    option: FaultType,
+    start: f64,
+    duration: f64,
+    value: f64,
+}
+
+pub struct FaultInjector{
+    faults: [ConfigFault; FAULT_SLOTS],
+    frozen_position: Option<f64>,
+    spike_counter: usize,
+}
+
+impl Default for ConfigFault{
+    fn default() -> Self {
+        Self{enabled: false, option: FaultType::EncoderLoss, start: 1.0, duration: 0.5, value: 0.0}
+    }
+}
+
+impl FaultType{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            FaultType::EncoderLoss => "Encoder loss",
+            FaultType::EncoderFreeze => "Encoder freeze",
+            FaultType::VelocitySpike => "Velocity spikes",
+            FaultType::CurrentStuck => "Current stuck",
+            FaultType::Disconnect => "Motor disconnect",
+            FaultType::BusDrop => "Bus voltage drop",
+            FaultType::LoadJam => "Load jam",
+        }
+    }
+
+    // meaning of ConfigFault value, None when unused
+    pub fn get_value_label(&self) -> Option<&'static str>{
+        match self{
+            FaultType::VelocitySpike => Some("Spike, rpm"),
+            FaultType::CurrentStuck => Some("Current, A"),
+            FaultType::BusDrop => Some("Remaining bus, part"),
+            _ => None,
+        }
+    }
+}
+
+impl ConfigFault{
+    pub fn set_enabled(&mut self) -> &mut bool{
+        &mut self.enabled
+    }
+
+    pub fn get_enabled(&self) -> bool{
+        self.enabled
+    }
+
+    pub fn set_option(&mut self) -> &mut FaultType{
+        &mut self.option
+    }
+
+    pub fn get_option(&self) -> FaultType{
+        self.option
+    }
+
+    pub fn set_start(&mut self) -> &mut f64{
+        &mut self.start
+    }
+
+    pub fn get_start(&self) -> f64{
+        self.start
+    }
+
+    pub fn set_duration(&mut self) -> &mut f64{
+        &mut self.duration
+    }
+
+    pub fn get_duration(&self) -> f64{
+        self.duration
+    }
+
+    pub fn set_value(&mut self) -> &mut f64{
+        &mut self.value
+    }
+
+    pub fn is_active(&self, time: f64) -> bool{
+        self.enabled && time >= self.start && time < self.start + self.duration
+    }
+}
+
+impl FaultInjector{
+    pub fn new(faults: [ConfigFault; FAULT_SLOTS]) -> Self{
+        Self{faults, frozen_position: None, spike_counter: 0}
+    }
+
+    // a fault on what the control law sees is configured
+    pub fn get_sensor_faults(&self) -> bool{
+        let sensor = [FaultType::EncoderLoss, FaultType::EncoderFreeze, FaultType::VelocitySpike, FaultType::CurrentStuck];
+        self.faults.iter().any(|fault| fault.enabled && sensor.contains(&fault.option))
+    }
+
+    fn active(&self, time: f64, option: FaultType) -> Option<&ConfigFault>{
+        self.faults.iter().find(|fault| fault.option == option && fault.is_active(time))
+    }
+
+    // Sensor faults on what the control law sees, k converts the stuck current into torque.
+    pub fn measure(&mut self, time: f64, measurements: Measurements, k: f64) -> Measurements{
+        let mut position = measurements.get_position();
+        let mut velocity = measurements.get_velocity();
+        let mut torque = measurements.get_torque();
+
+        if self.active(time, FaultType::EncoderFreeze).is_some(){
+            position = *self.frozen_position.get_or_insert(position);
+        } else {
+            self.frozen_position = None;
+        }
+        if self.active(time, FaultType::EncoderLoss).is_some(){
+            position = 0.0;
+        }
+        if let Some(fault) = self.active(time, FaultType::VelocitySpike){
+            if self.spike_counter.is_multiple_of(SPIKE_INTERVAL){
+                velocity += fault.value;
+            }
+            self.spike_counter += 1;
+        } else {
+            self.spike_counter = 0;
+        }
+        if let Some(fault) = self.active(time, FaultType::CurrentStuck){
+            torque = k*fault.value;
+        }
+        Measurements::new(position, velocity, torque)
+    }
+
+    // Power stage and mechanical faults, returns the voltage that reaches the motor.
+    pub fn actuate(&self, time: f64, motor: &mut Motor, voltage: f64, vltg_bound: f64) -> f64{
+        *motor.set_open_circuit() = self.active(time, FaultType::Disconnect).is_some();
+        *motor.set_locked() = self.active(time, FaultType::LoadJam).is_some();
+        match self.active(time, FaultType::BusDrop){
+            Some(fault) => {
+                let bound = vltg_bound*fault.value.max(0.0);
+                voltage.clamp(-bound, bound)
+            }
+            None => voltage,
+        }
+    }
+}
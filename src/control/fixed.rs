@@ -0,0 +1,474 @@
+use std::sync::Arc;
+
+use super::{simulate, Config, ConfigController, ConfigPid, ControlType, TypePid, law::{LawRegistry, Measurements, References}, metrics::StepMetrics};
+
+// keeps the shifts of the products within i128
+const MAX_GAIN_FRAC: i32 = 62;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Rounding{
+    // towards minus infinity, as an arithmetic shift
+    Truncate,
+    Nearest,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Overflow{
+    Saturate,
+    // two's complement wrap around
+    Wrap,
+}
+
+// Signals are per unit of their full scale in Q(word-1), integrator states in Q(accumulator-1),
+// every gain is normalized by its magnitude to fill a word, small gains get more fraction bits than the word.
+#[derive(Copy, Clone)]
+pub struct ConfigFixed{
+    // the fixed point cascade replaces the control law
+    enabled: bool,
+    // bits with the sign
+    word: u32,
+    accumulator: u32,
+    rounding: Rounding,
+    overflow: Overflow,
+    // full scales of angle deg, speed rpm, torque N*m and voltage V
+    scales: [f64; 4],
+}
+
+#[derive(Copy, Clone)]
+pub struct QFormat{
+    word: u32,
+    frac: u32,
+    rounding: Rounding,
+    overflow: Overflow,
+}
+
+// Integral of the error with the gain and the half period folded in, kept in the accumulator format.
+pub struct FixedIntegrator{
+    gain: i64,
+    gain_format: QFormat,
+    signal: QFormat,
+    accumulator: QFormat,
+    prev_state: i64,
+    integral: i64,
+}
+
+// Difference of the error with the gain over the period folded in.
+pub struct FixedDerivative{
+    gain: i64,
+    gain_format: QFormat,
+    signal: QFormat,
+    accumulator: QFormat,
+    prev_state: i64,
+    derivative: i64,
+}
+
+pub struct FixedPid{
+    kp: i64,
+    kp_format: QFormat,
+    integral: FixedIntegrator,
+    derivative: FixedDerivative,
+    signal: QFormat,
+    accumulator: QFormat,
+    input_scale: f64,
+    output_scale: f64,
+}
+
+// Calibration of the same config with the f64 law and with the fixed point cascade.
+pub struct Comparison{
+    float: Vec<[f64; 2]>,
+    fixed: Vec<[f64; 2]>,
+    float_voltage: Vec<[f64; 2]>,
+    fixed_voltage: Vec<[f64; 2]>,
+    float_metrics: Option<StepMetrics>,
+    fixed_metrics: Option<StepMetrics>,
+    overflows: usize,
+}
+
+// The cascade of the f64 Cascade law without the disturbance observer, at the nominal period.
+pub struct FixedCascade{
+    pos_pid: FixedPid,
+    vel_pid: FixedPid,
+    trq_pid: FixedPid,
+    config: ConfigController,
+    overflows: usize,
+}
+
+impl Default for ConfigFixed{
+    fn default() -> Self {
+        Self{enabled: false, word: 16, accumulator: 32, rounding: Rounding::Nearest, overflow: Overflow::Saturate,
+            scales: [720.0, 8000.0, 2.0, 48.0]}
+    }
+}
+
+impl Rounding{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            Rounding::Truncate => "Truncate",
+            Rounding::Nearest => "Nearest",
+        }
+    }
+}
+
+impl Overflow{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            Overflow::Saturate => "Saturate",
+            Overflow::Wrap => "Wrap",
+        }
+    }
+}
+
+impl ConfigFixed{
+    pub fn set_enabled(&mut self) -> &mut bool{
+        &mut self.enabled
+    }
+
+    pub fn get_enabled(&self) -> bool{
+        self.enabled
+    }
+
+    pub fn set_word(&mut self) -> &mut u32{
+        &mut self.word
+    }
+
+    pub fn set_accumulator(&mut self) -> &mut u32{
+        &mut self.accumulator
+    }
+
+    pub fn set_rounding(&mut self) -> &mut Rounding{
+        &mut self.rounding
+    }
+
+    pub fn set_overflow(&mut self) -> &mut Overflow{
+        &mut self.overflow
+    }
+
+    pub fn set_scales(&mut self) -> &mut [f64; 4]{
+        &mut self.scales
+    }
+
+    fn signal_format(&self) -> QFormat{
+        let word = self.word.clamp(2, 32);
+        QFormat{word, frac: word - 1, rounding: self.rounding, overflow: self.overflow}
+    }
+
+    fn accumulator_format(&self) -> QFormat{
+        let word = self.accumulator.clamp(2, 32);
+        QFormat{word, frac: word - 1, rounding: self.rounding, overflow: self.overflow}
+    }
+
+    fn gain_format(&self, gain: f64) -> QFormat{
+        let word = self.word.clamp(2, 32);
+        // bits above the binary point, negative for the leading zeros of a gain below 0.5
+        let int_bits = if gain == 0.0 || !gain.is_finite() {0} else {gain.abs().log2().floor() as i32 + 1};
+        QFormat{word, frac: (word as i32 - 1 - int_bits).clamp(0, MAX_GAIN_FRAC) as u32, rounding: self.rounding, overflow: self.overflow}
+    }
+}
+
+impl QFormat{
+    fn range(&self) -> (i128, i128){
+        (-(1i128 << (self.word - 1)), (1i128 << (self.word - 1)) - 1)
+    }
+
+    // Raw value into the word, overflows are counted.
+    fn fit(&self, raw: i128, overflows: &mut usize) -> i64{
+        let (min, max) = self.range();
+        if raw >= min && raw <= max{
+            return raw as i64;
+        }
+        *overflows += 1;
+        match self.overflow{
+            Overflow::Saturate => raw.clamp(min, max) as i64,
+            Overflow::Wrap => ((raw - min).rem_euclid(1i128 << self.word) + min) as i64,
+        }
+    }
+
+    // Drops shift fraction bits, or adds them for a negative shift.
+    fn shift(&self, raw: i128, shift: i32) -> i128{
+        match shift{
+            s if s <= 0 => raw << (-s),
+            s => match self.rounding{
+                Rounding::Truncate => raw >> s,
+                Rounding::Nearest => (raw + (1i128 << (s - 1))) >> s,
+            },
+        }
+    }
+
+    pub fn quantize(&self, value: f64, overflows: &mut usize) -> i64{
+        let scaled = value*(self.frac as f64).exp2();
+        let scaled = match self.rounding{
+            Rounding::Truncate => scaled.floor(),
+            Rounding::Nearest => scaled.round(),
+        };
+        // the cast saturates at the i128 range and maps NaN to 0
+        self.fit(scaled as i128, overflows)
+    }
+
+    pub fn to_f64(&self, raw: i64) -> f64{
+        raw as f64/(self.frac as f64).exp2()
+    }
+
+    pub fn mul(&self, a: i64, a_format: &QFormat, b: i64, b_format: &QFormat, overflows: &mut usize) -> i64{
+        let shift = (a_format.frac + b_format.frac) as i32 - self.frac as i32;
+        self.fit(self.shift(a as i128*b as i128, shift), overflows)
+    }
+
+    pub fn add(&self, a: i64, b: i64, overflows: &mut usize) -> i64{
+        self.fit(a as i128 + b as i128, overflows)
+    }
+
+    pub fn convert(&self, raw: i64, format: &QFormat, overflows: &mut usize) -> i64{
+        self.fit(self.shift(raw as i128, format.frac as i32 - self.frac as i32), overflows)
+    }
+}
+
+impl FixedIntegrator{
+    pub fn new(gain: f64, config: &ConfigFixed, overflows: &mut usize) -> Self{
+        let gain_format = config.gain_format(gain);
+        Self{gain: gain_format.quantize(gain, overflows), gain_format, signal: config.signal_format(), accumulator: config.accumulator_format(),
+            prev_state: 0, integral: 0}
+    }
+
+    pub fn integrate(&mut self, state: i64, overflows: &mut usize){
+        // the sum of two words is formed in the wider product
+        let sum = state + self.prev_state;
+        let increment = self.accumulator.mul(self.gain, &self.gain_format, sum, &self.signal, overflows);
+        self.integral = self.accumulator.add(self.integral, increment, overflows);
+        self.prev_state = state;
+    }
+
+    pub fn get_state(&self) -> i64{
+        self.integral
+    }
+}
+
+impl FixedDerivative{
+    pub fn new(gain: f64, config: &ConfigFixed, overflows: &mut usize) -> Self{
+        let gain_format = config.gain_format(gain);
+        Self{gain: gain_format.quantize(gain, overflows), gain_format, signal: config.signal_format(), accumulator: config.accumulator_format(),
+            prev_state: 0, derivative: 0}
+    }
+
+    pub fn derivate(&mut self, state: i64, overflows: &mut usize){
+        let difference = state - self.prev_state;
+        self.derivative = self.accumulator.mul(self.gain, &self.gain_format, difference, &self.signal, overflows);
+        self.prev_state = state;
+    }
+
+    pub fn get_state(&self) -> i64{
+        self.derivative
+    }
+}
+
+impl FixedPid{
+    // Gains act on per unit signals, the integral and derivative ones include the period.
+    pub fn new(config: ConfigPid, fixed: &ConfigFixed, input_scale: f64, output_scale: f64, period: f64, overflows: &mut usize) -> Self{
+        let per_unit = input_scale/output_scale;
+        let kp_format = fixed.gain_format(config.kp*per_unit);
+        Self{kp: kp_format.quantize(config.kp*per_unit, overflows), kp_format,
+            integral: FixedIntegrator::new(config.ki*per_unit*period/2.0, fixed, overflows),
+            derivative: FixedDerivative::new(config.kd*per_unit/period, fixed, overflows),
+            signal: fixed.signal_format(), accumulator: fixed.accumulator_format(), input_scale, output_scale}
+    }
+
+    pub fn generate_control(&mut self, input: f64, target: f64, bound: f64, overflows: &mut usize) -> f64{
+        let input = self.signal.quantize(input/self.input_scale, overflows);
+        let target = self.signal.quantize(target/self.input_scale, overflows);
+        let error = self.signal.add(target, -input, overflows);
+        self.derivative.derivate(error, overflows);
+        self.integral.integrate(error, overflows);
+
+        let proportional = self.accumulator.mul(self.kp, &self.kp_format, error, &self.signal, overflows);
+        let sum = self.accumulator.add(proportional, self.integral.get_state(), overflows);
+        let sum = self.accumulator.add(sum, self.derivative.get_state(), overflows);
+        let bound = self.accumulator.quantize(bound/self.output_scale, overflows);
+        let result = self.signal.convert(sum.clamp(-bound, bound), &self.accumulator, overflows);
+        self.signal.to_f64(result)*self.output_scale
+    }
+}
+
+impl FixedCascade{
+    // None for custom laws, which have no fixed point counterpart.
+    pub fn new(pid_conf: &[ConfigPid; 3], config: ConfigController, fixed: &ConfigFixed) -> Option<Self>{
+        if !fixed.enabled || matches!(config.control_option, ControlType::Custom(_)){
+            return None;
+        }
+        let period = 1.0/config.frequency;
+        let [pos, vel, trq, vltg] = fixed.scales;
+        let mut overflows = 0;
+        // the angle loop drives the voltage directly in Pos control
+        let pos_output = if config.control_option == ControlType::Pos {vltg} else {vel};
+        Some(Self{pos_pid: FixedPid::new(pid_conf[0], fixed, pos, pos_output, period, &mut overflows),
+            vel_pid: FixedPid::new(pid_conf[1], fixed, vel, trq, period, &mut overflows),
+            trq_pid: FixedPid::new(pid_conf[2], fixed, trq, vltg, period, &mut overflows), config, overflows})
+    }
+
+    pub fn get_overflows(&self) -> usize{
+        self.overflows
+    }
+
+    fn velocity_control(&mut self, measurements: &Measurements, vel: f64) -> f64{
+        let trq = self.vel_pid.generate_control(measurements.get_velocity(), vel, self.config.trq_bound, &mut self.overflows);
+        self.trq_pid.generate_control(measurements.get_torque(), trq, self.config.vltg_bound, &mut self.overflows)
+    }
+
+    pub fn generate_control(&mut self, measurements: &Measurements, references: &References) -> f64{
+        if let Some(pos) = references.get_pos(){
+            match self.config.control_option{
+                ControlType::Pos => {
+                    self.pos_pid.generate_control(measurements.get_position(), pos, self.config.vltg_bound, &mut self.overflows)
+                }
+                _ => {
+                    let vel = self.pos_pid.generate_control(measurements.get_position(), pos, self.config.vel_bound, &mut self.overflows);
+                    self.velocity_control(measurements, vel)
+                }
+            }
+        } else if let Some(vel) = references.get_vel(){
+            self.velocity_control(measurements, vel)
+        } else if let Some(trq) = references.get_trq(){
+            self.trq_pid.generate_control(measurements.get_torque(), trq, self.config.vltg_bound, &mut self.overflows)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Comparison{
+    // controlled value of the calibrated loop
+    pub fn get_float(&self) -> &Vec<[f64; 2]>{
+        &self.float
+    }
+
+    pub fn get_fixed(&self) -> &Vec<[f64; 2]>{
+        &self.fixed
+    }
+
+    pub fn get_float_metrics(&self) -> Option<StepMetrics>{
+        self.float_metrics
+    }
+
+    pub fn get_fixed_metrics(&self) -> Option<StepMetrics>{
+        self.fixed_metrics
+    }
+
+    pub fn get_overflows(&self) -> usize{
+        self.overflows
+    }
+
+    // fixed minus f64 over time
+    pub fn get_difference(&self) -> Vec<[f64; 2]>{
+        self.fixed.iter().zip(&self.float).map(|(fixed, float)| [fixed[0], fixed[1] - float[1]]).collect()
+    }
+
+    // (rms, max) of the controlled value and of the voltage differences
+    pub fn get_errors(&self) -> [(f64, f64); 2]{
+        let errors = |fixed: &Vec<[f64; 2]>, float: &Vec<[f64; 2]>|{
+            let differences: Vec<f64> = fixed.iter().zip(float).map(|(fixed, float)| fixed[1] - float[1]).collect();
+            let rms = (differences.iter().map(|difference| difference*difference).sum::<f64>()/differences.len().max(1) as f64).sqrt();
+            (rms, differences.iter().fold(0.0, |max: f64, difference| max.max(difference.abs())))
+        };
+        [errors(&self.fixed, &self.float), errors(&self.fixed_voltage, &self.float_voltage)]
+    }
+}
+
+// Both runs use the same cascade, so the f64 one goes without the disturbance observer.
+pub fn compare(config: Config, registry: Arc<LawRegistry>) -> Result<Comparison, String>{
+    if matches!(config.get_controller_conf().get_control_option(), ControlType::Custom(_)){
+        return Err("Custom control laws have no fixed point counterpart".to_string());
+    }
+    let calib = config.get_controller_conf().get_calib_option().unwrap_or(TypePid::Pos);
+    let fixed = config.fixed;
+    let mut config = config;
+    *config.set_controller_conf().set_calib_option() = Some(calib);
+    *config.set_dob_conf().set_enabled() = false;
+    *config.set_fixed_conf() = ConfigFixed{enabled: false, ..fixed};
+    let float = simulate(config, Arc::clone(&registry));
+    *config.set_fixed_conf() = ConfigFixed{enabled: true, ..fixed};
+    let fixed = simulate(config, registry);
+    Ok(Comparison{float: float.clone_loop_as_vec(calib), fixed: fixed.clone_loop_as_vec(calib), float_voltage: float.clone_voltage_as_vec(),
+        fixed_voltage: fixed.clone_voltage_as_vec(), float_metrics: float.get_metrics(), fixed_metrics: fixed.get_metrics(), overflows: fixed.get_overflows()})
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn format(word: u32, frac: u32, rounding: Rounding, overflow: Overflow) -> QFormat{
+        QFormat{word, frac, rounding, overflow}
+    }
+
+    #[test]
+    fn quantize_rounding(){
+        let mut overflows = 0;
+        let nearest = format(8, 4, Rounding::Nearest, Overflow::Saturate);
+        let truncate = format(8, 4, Rounding::Truncate, Overflow::Saturate);
+        // 0.71875 is 11.5 steps of 1/16
+        assert_eq!(nearest.quantize(0.71875, &mut overflows), 12);
+        assert_eq!(truncate.quantize(0.71875, &mut overflows), 11);
+        assert_eq!(nearest.quantize(-0.71875, &mut overflows), -12);
+        assert_eq!(truncate.quantize(-0.71875, &mut overflows), -12);
+        assert_eq!(nearest.to_f64(12), 0.75);
+        assert_eq!(overflows, 0);
+    }
+
+    #[test]
+    fn shift_rounding(){
+        let mut overflows = 0;
+        let nearest = format(16, 0, Rounding::Nearest, Overflow::Saturate);
+        let truncate = format(16, 0, Rounding::Truncate, Overflow::Saturate);
+        let source = format(16, 2, Rounding::Nearest, Overflow::Saturate);
+        // 1.5 and -1.25 in Q2
+        assert_eq!(nearest.convert(6, &source, &mut overflows), 2);
+        assert_eq!(truncate.convert(6, &source, &mut overflows), 1);
+        assert_eq!(nearest.convert(-5, &source, &mut overflows), -1);
+        assert_eq!(truncate.convert(-5, &source, &mut overflows), -2);
+        assert_eq!(source.convert(3, &nearest, &mut overflows), 12);
+        assert_eq!(overflows, 0);
+    }
+
+    #[test]
+    fn saturate(){
+        let mut overflows = 0;
+        let q = format(8, 7, Rounding::Nearest, Overflow::Saturate);
+        assert_eq!(q.quantize(2.0, &mut overflows), 127);
+        assert_eq!(q.quantize(-2.0, &mut overflows), -128);
+        assert_eq!(q.add(100, 100, &mut overflows), 127);
+        assert_eq!(q.quantize(f64::NAN, &mut overflows), 0);
+        assert_eq!(overflows, 3);
+    }
+
+    #[test]
+    fn wrap(){
+        let mut overflows = 0;
+        let q = format(8, 0, Rounding::Nearest, Overflow::Wrap);
+        assert_eq!(q.add(100, 100, &mut overflows), -56);
+        assert_eq!(q.add(-100, -100, &mut overflows), 56);
+        assert_eq!(q.add(127, 0, &mut overflows), 127);
+        assert_eq!(overflows, 2);
+    }
+
+    #[test]
+    fn mul_formats(){
+        let mut overflows = 0;
+        let a = format(16, 8, Rounding::Nearest, Overflow::Saturate);
+        let b = format(16, 12, Rounding::Nearest, Overflow::Saturate);
+        let q = format(16, 10, Rounding::Nearest, Overflow::Saturate);
+        let product = q.mul(a.quantize(1.5, &mut overflows), &a, b.quantize(-2.25, &mut overflows), &b, &mut overflows);
+        assert_eq!(q.to_f64(product), -3.375);
+        assert_eq!(overflows, 0);
+    }
+
+    #[test]
+    fn gain_format_fills_the_word(){
+        let config = ConfigFixed{word: 16, ..Default::default()};
+        assert_eq!(config.gain_format(40.0).frac, 9);
+        assert_eq!(config.gain_format(0.75).frac, 15);
+        assert_eq!(config.gain_format(0.001).frac, 24);
+        assert_eq!(config.gain_format(-0.001).frac, 24);
+        assert_eq!(config.gain_format(0.0).frac, 15);
+        let mut overflows = 0;
+        let format = config.gain_format(40.0);
+        assert_eq!(format.quantize(40.0, &mut overflows), 40 << 9);
+        assert_eq!(overflows, 0);
+    }
+}
@@ -0,0 +1,175 @@
+use super::{Config, ConfigController, ControlType, Pid, observer::Dob};
+
+// Sensor values handed to a control law: position in deg, velocity in rpm, torque in N*m.
+#[derive(Copy, Clone)]
+pub struct Measurements{
+    position: f64,
+    velocity: f64,
+    torque: f64,
+}
+
+// Only the outermost loop that has to be driven is set, the rest are left to the law.
+#[derive(Copy, Clone, Default)]
+pub struct References{
+    pos: Option<f64>,
+    vel: Option<f64>,
+    trq: Option<f64>,
+}
+
+pub trait ControlLaw: Send{
+    // Returns the motor voltage for the next period.
+    fn generate_control(&mut self, measurements: &Measurements, references: &References, delta: f64) -> f64;
+
+    fn get_disturbance(&self) -> Option<f64>{
+        None
+    }
+}
+
+pub type LawFactory = Box<dyn Fn(&Config) -> Box<dyn ControlLaw> + Send + Sync>;
+
+#[derive(Default)]
+pub struct LawRegistry{
+    // name, factory and whether the headless runs may build the law
+    laws: Vec<(String, LawFactory, bool)>,
+}
+
+pub struct Cascade{
+    pos_pid: Pid,
+    vel_pid: Pid,
+    trq_pid: Pid,
+    dob: Dob,
+    config: ConfigController,
+}
+
+impl Measurements{
+    pub fn new(position: f64, velocity: f64, torque: f64) -> Self{
+        Self{position, velocity, torque}
+    }
+
+    pub fn get_position(&self) -> f64{
+        self.position
+    }
+
+    pub fn get_velocity(&self) -> f64{
+        self.velocity
+    }
+
+    pub fn get_torque(&self) -> f64{
+        self.torque
+    }
+}
+
+impl References{
+    pub fn position(pos: f64) -> Self{
+        Self{pos: Some(pos), ..Default::default()}
+    }
+
+    pub fn velocity(vel: f64) -> Self{
+        Self{vel: Some(vel), ..Default::default()}
+    }
+
+    pub fn torque(trq: f64) -> Self{
+        Self{trq: Some(trq), ..Default::default()}
+    }
+
+    pub fn get_pos(&self) -> Option<f64>{
+        self.pos
+    }
+
+    pub fn get_vel(&self) -> Option<f64>{
+        self.vel
+    }
+
+    pub fn get_trq(&self) -> Option<f64>{
+        self.trq
+    }
+
+    // The position reference in a frame offset deg ahead, the others are kept.
+    pub fn shift(&self, offset: f64) -> Self{
+        Self{pos: self.pos.map(|pos| pos + offset), ..*self}
+    }
+
+    // value of the outermost reference
+    pub fn get_value(&self) -> f64{
+        self.pos.or(self.vel).or(self.trq).unwrap_or(0.0)
+    }
+}
+
+impl LawRegistry{
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where F: Fn(&Config) -> Box<dyn ControlLaw> + Send + Sync + 'static{
+        self.laws.push((name.to_string(), Box::new(factory), true));
+    }
+
+    // A law bound to an outside process, left out of the optimizer, sweep and Monte Carlo runs
+    // which build their laws many times and in parallel.
+    pub fn register_live<F>(&mut self, name: &str, factory: F)
+    where F: Fn(&Config) -> Box<dyn ControlLaw> + Send + Sync + 'static{
+        self.laws.push((name.to_string(), Box::new(factory), false));
+    }
+
+    pub fn get_headless(&self, option: ControlType) -> bool{
+        match option{
+            ControlType::Custom(index) => self.laws.get(index).is_none_or(|(_, _, headless)| *headless),
+            _ => true,
+        }
+    }
+
+    pub fn get_names(&self) -> Vec<String>{
+        self.laws.iter().map(|(name, _, _)| name.clone()).collect()
+    }
+
+    pub fn build(&self, config: &Config) -> Box<dyn ControlLaw>{
+        match config.get_controller_conf().get_control_option(){
+            ControlType::Custom(index) => {
+                match self.laws.get(*index){
+                    Some((_, factory, _)) => factory(config),
+                    None => Box::new(Cascade::new(config)),
+                }
+            }
+            _ => Box::new(Cascade::new(config))
+        }
+    }
+}
+
+impl Cascade{
+    pub fn new(config: &Config) -> Self{
+        let pid_conf = config.get_pid_conf();
+        Self{pos_pid: Pid::new(pid_conf[0]), vel_pid: Pid::new(pid_conf[1]), trq_pid: Pid::new(pid_conf[2]),
+            dob: Dob::new(*config.get_dob_conf(), *config.get_motor_conf()), config: *config.get_controller_conf()}
+    }
+
+    fn velocity_control(&mut self, measurements: &Measurements, vel: f64, delta: f64) -> f64{
+        let trq = self.vel_pid.generate_control(measurements.velocity, vel, delta, self.config.trq_bound);
+        let trq = self.dob.compensate(trq, self.config.trq_bound);
+        self.trq_pid.generate_control(measurements.torque, trq, delta, self.config.vltg_bound)
+    }
+}
+
+impl ControlLaw for Cascade{
+    fn generate_control(&mut self, measurements: &Measurements, references: &References, delta: f64) -> f64{
+        self.dob.update(delta, measurements.torque, measurements.velocity);
+
+        if let Some(pos) = references.pos{
+            match self.config.control_option{
+                ControlType::Pos => {
+                    self.pos_pid.generate_control(measurements.position, pos, delta, self.config.vltg_bound)
+                }
+                _ => {
+                    let vel = self.pos_pid.generate_control(measurements.position, pos, delta, self.config.vel_bound);
+                    self.velocity_control(measurements, vel, delta)
+                }
+            }
+        } else if let Some(vel) = references.vel{
+            self.velocity_control(measurements, vel, delta)
+        } else if let Some(trq) = references.trq{
+            self.trq_pid.generate_control(measurements.torque, trq, delta, self.config.vltg_bound)
+        } else {
+            0.0
+        }
+    }
+
+    fn get_disturbance(&self) -> Option<f64>{
+        Some(self.dob.get_estimate())
+    }
+}
@@ -0,0 +1,107 @@
+use super::law::References;
+
+// Software position limits in deg of the unwrapped angle, enforced outside calibrations.
+#[derive(Copy, Clone)]
+pub struct ConfigLimits{
+    enabled: bool,
+    min: f64,
+    max: f64,
+}
+
+impl Default for ConfigLimits{
+    fn default() -> Self {
+        Self{enabled: false, min: -360.0, max: 360.0}
+    }
+}
+
+impl ConfigLimits{
+    pub fn set_enabled(&mut self) -> &mut bool{
+        &mut self.enabled
+    }
+
+    pub fn set_min(&mut self) -> &mut f64{
+        &mut self.min
+    }
+
+    pub fn set_max(&mut self) -> &mut f64{
+        &mut self.max
+    }
+
+    // Position references are clamped into the range. Beyond a limit, motion only leads back,
+    // other references are replaced by a zero speed. Returns whether a limit is active.
+    pub fn apply(&self, references: References, position: f64) -> (References, bool){
+        if !self.enabled{
+            return (references, false);
+        }
+        let references = match references.get_pos(){
+            Some(pos) => References::position(pos.clamp(self.min, self.max.max(self.min))),
+            None => references,
+        };
+        let direction = match (references.get_pos(), references.get_vel(), references.get_trq()){
+            (Some(pos), _, _) => pos - position,
+            (_, Some(vel), _) => vel,
+            (_, _, Some(trq)) => trq,
+            _ => 0.0,
+        };
+        if (position > self.max && direction > 0.0) || (position < self.min && direction < 0.0){
+            return (References::velocity(0.0), true);
+        }
+        (references, position > self.max || position < self.min)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn limits(min: f64, max: f64) -> ConfigLimits{
+        ConfigLimits{enabled: true, min, max}
+    }
+
+    #[test]
+    fn clamps_position(){
+        let limits = limits(-90.0, 90.0);
+        let (references, active) = limits.apply(References::position(400.0), 0.0);
+        assert_eq!(references.get_pos(), Some(90.0));
+        assert!(!active);
+        assert_eq!(limits.apply(References::position(-400.0), 0.0).0.get_pos(), Some(-90.0));
+        assert_eq!(limits.apply(References::position(45.0), 0.0).0.get_pos(), Some(45.0));
+    }
+
+    #[test]
+    fn beyond_a_limit(){
+        let limits = limits(-90.0, 90.0);
+        // back towards the range
+        let (references, active) = limits.apply(References::position(400.0), 120.0);
+        assert_eq!(references.get_pos(), Some(90.0));
+        assert!(active);
+        let (references, active) = limits.apply(References::velocity(-100.0), 120.0);
+        assert_eq!(references.get_vel(), Some(-100.0));
+        assert!(active);
+        assert_eq!(limits.apply(References::torque(0.1), -120.0).0.get_trq(), Some(0.1));
+        // further out
+        let (references, active) = limits.apply(References::velocity(100.0), 120.0);
+        assert_eq!((references.get_pos(), references.get_vel()), (None, Some(0.0)));
+        assert!(active);
+        assert_eq!(limits.apply(References::torque(-0.1), -120.0).0.get_vel(), Some(0.0));
+    }
+
+    #[test]
+    fn disabled(){
+        let limits = ConfigLimits{enabled: false, ..limits(-90.0, 90.0)};
+        let (references, active) = limits.apply(References::velocity(100.0), 120.0);
+        assert_eq!(references.get_vel(), Some(100.0));
+        assert!(!active);
+        assert_eq!(limits.apply(References::position(400.0), 0.0).0.get_pos(), Some(400.0));
+    }
+
+    #[test]
+    fn min_above_max(){
+        // the range collapses to min, no panic of the clamp
+        let limits = limits(90.0, -90.0);
+        let (references, active) = limits.apply(References::position(0.0), 90.0);
+        assert_eq!(references.get_pos(), Some(90.0));
+        assert!(active);
+        assert_eq!(limits.apply(References::position(-400.0), 90.0).0.get_pos(), Some(90.0));
+    }
+}
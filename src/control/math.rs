@@ -53,4 +53,39 @@ pub fn rad_to_deg(rad: f64) -> f64{
 
 pub fn rads_to_rpm(vel: f64) -> f64{
     vel*30.0/std::f64::consts::PI
-}
\ No newline at end of file
+}
+
+pub fn rpm_to_rads(vel: f64) -> f64{
+    vel*std::f64::consts::PI/30.0
+}
+
+// SplitMix64 generator, the same seed always gives the same sequence.
+pub struct Rng{
+    state: u64,
+}
+
+impl Rng{
+    pub fn new(seed: u64) -> Self{
+        Self{state: seed}
+    }
+
+    pub fn next_u64(&mut self) -> u64{
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // [0, 1)
+    pub fn uniform(&mut self) -> f64{
+        (self.next_u64() >> 11) as f64/(1u64 << 53) as f64
+    }
+
+    // standard normal, Box-Muller
+    pub fn normal(&mut self) -> f64{
+        let u1 = 1.0 - self.uniform();
+        let u2 = self.uniform();
+        (-2.0*u1.ln()).sqrt()*(2.0*std::f64::consts::PI*u2).cos()
+    }
+}
@@ -0,0 +1,176 @@
+use super::{PlotPnts, TypePid};
+
+#[derive(Copy, Clone, Default)]
+pub struct StepMetrics{
+    rise_time: Option<f64>,
+    overshoot: f64,
+    settling_time: Option<f64>,
+    ss_error: f64,
+    iae: f64,
+    ise: f64,
+    itae: f64,
+    peak_effort: f64,
+    saturation_time: f64,
+}
+
+impl StepMetrics{
+    // 10% to 90% of the step
+    pub fn get_rise_time(&self) -> Option<f64>{
+        self.rise_time
+    }
+
+    // percent of the step
+    pub fn get_overshoot(&self) -> f64{
+        self.overshoot
+    }
+
+    // None when the response leaves the settling band at the end of the run
+    pub fn get_settling_time(&self) -> Option<f64>{
+        self.settling_time
+    }
+
+    // mean error over the last tenth of the run
+    pub fn get_ss_error(&self) -> f64{
+        self.ss_error
+    }
+
+    pub fn get_iae(&self) -> f64{
+        self.iae
+    }
+
+    pub fn get_ise(&self) -> f64{
+        self.ise
+    }
+
+    pub fn get_itae(&self) -> f64{
+        self.itae
+    }
+
+    // peak absolute voltage
+    pub fn get_peak_effort(&self) -> f64{
+        self.peak_effort
+    }
+
+    pub fn get_saturation_time(&self) -> f64{
+        self.saturation_time
+    }
+}
+
+// band is in percent of the step, vltg_bound marks saturation of the output
+pub fn step_metrics(points: &PlotPnts, calib: TypePid, target: f64, band: f64, vltg_bound: f64) -> StepMetrics{
+    let signal = match calib{
+        TypePid::Pos => &points.pos,
+        TypePid::Vel => &points.vel,
+        TypePid::Trq => &points.trq,
+    };
+    let mut metrics = StepMetrics::default();
+    let step = target.abs();
+    if signal.is_empty() || step == 0.0{
+        return metrics;
+    }
+
+    let band = band/100.0*step;
+    let mut rise_start = None;
+    let mut prev_time = 0.0;
+    let mut last_outside = Some(0.0);
+    let mut peak = 0.0_f64;
+    for [time, value] in signal.iter(){
+        let error = target - value;
+        let dt = time - prev_time;
+        prev_time = *time;
+        let progress = value*target.signum()/step;
+
+        if rise_start.is_none() && progress >= 0.1{
+            rise_start = Some(*time);
+        }
+        if let (Some(start), None) = (rise_start, metrics.rise_time){
+            if progress >= 0.9{
+                metrics.rise_time = Some(time - start);
+            }
+        }
+        peak = peak.max(progress - 1.0);
+        if error.abs() > band{
+            last_outside = Some(*time);
+        }
+        metrics.iae += error.abs()*dt;
+        metrics.ise += error*error*dt;
+        metrics.itae += time*error.abs()*dt;
+    }
+    metrics.overshoot = 100.0*peak;
+
+    let end = signal.back().unwrap()[0];
+    if (target - signal.back().unwrap()[1]).abs() <= band{
+        metrics.settling_time = last_outside;
+    }
+    let tail: Vec<f64> = signal.iter().filter(|[time, _]| *time >= 0.9*end).map(|[_, value]| target - value).collect();
+    metrics.ss_error = tail.iter().sum::<f64>()/tail.len() as f64;
+
+    let mut prev_time = 0.0;
+    for [time, voltage] in points.voltage.iter(){
+        metrics.peak_effort = metrics.peak_effort.max(voltage.abs());
+        if voltage.abs() >= vltg_bound{
+            metrics.saturation_time += time - prev_time;
+        }
+        prev_time = *time;
+    }
+    metrics
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // Unit step of a second order system with damping zeta and natural frequency wn, scaled to target.
+    fn second_order(zeta: f64, wn: f64, target: f64) -> PlotPnts{
+        let mut points = PlotPnts::default();
+        let wd = wn*(1.0 - zeta*zeta).sqrt();
+        for step in 1..=100000{
+            let time = step as f64*1e-4;
+            let decay = (-zeta*wn*time).exp();
+            let value = 1.0 - decay*((wd*time).cos() + zeta*wn/wd*(wd*time).sin());
+            points.pos.push_back([time, target*value]);
+            // saturated over the first second
+            points.voltage.push_back([time, if time <= 1.0 {-24.0} else {3.0}]);
+        }
+        points
+    }
+
+    #[test]
+    fn second_order_step(){
+        let (zeta, wn) = (0.5, 10.0);
+        let metrics = step_metrics(&second_order(zeta, wn, 90.0), TypePid::Pos, 90.0, 2.0, 24.0);
+        let overshoot = 100.0*(-std::f64::consts::PI*zeta/(1.0 - zeta*zeta).sqrt()).exp();
+        assert!((metrics.get_overshoot() - overshoot).abs() < 0.01, "overshoot {} against {}", metrics.get_overshoot(), overshoot);
+        // 10% to 90% of an underdamped step, about 1.6/wn at this damping
+        let rise_time = metrics.get_rise_time().unwrap();
+        assert!((rise_time - 0.164).abs() < 0.005, "rise time {}", rise_time);
+        // the response stays in the 2% band once its envelope does, shortly before
+        let envelope = (1.0/(0.02*(1.0 - zeta*zeta).sqrt())).ln()/(zeta*wn);
+        let settling_time = metrics.get_settling_time().unwrap();
+        assert!(settling_time > 0.9*envelope && settling_time <= envelope, "settling time {} envelope {}", settling_time, envelope);
+        assert!(metrics.get_ss_error().abs() < 1e-9);
+        assert_eq!(metrics.get_peak_effort(), 24.0);
+        assert!((metrics.get_saturation_time() - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn negative_step(){
+        let (zeta, wn) = (0.3, 5.0);
+        let negative = step_metrics(&second_order(zeta, wn, -45.0), TypePid::Pos, -45.0, 2.0, 24.0);
+        let positive = step_metrics(&second_order(zeta, wn, 45.0), TypePid::Pos, 45.0, 2.0, 24.0);
+        let overshoot = 100.0*(-std::f64::consts::PI*zeta/(1.0 - zeta*zeta).sqrt()).exp();
+        assert!((negative.get_overshoot() - overshoot).abs() < 0.01);
+        assert_eq!(negative.get_rise_time(), positive.get_rise_time());
+        assert_eq!(negative.get_settling_time(), positive.get_settling_time());
+        assert!((negative.get_iae() - positive.get_iae()).abs() < 1e-9);
+        assert!((negative.get_ss_error() + positive.get_ss_error()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn empty_or_zero_step(){
+        let metrics = step_metrics(&PlotPnts::default(), TypePid::Pos, 90.0, 2.0, 24.0);
+        assert!(metrics.get_rise_time().is_none());
+        let metrics = step_metrics(&second_order(0.5, 10.0, 1.0), TypePid::Pos, 0.0, 2.0, 24.0);
+        assert_eq!(metrics.get_iae(), 0.0);
+    }
+}
@@ -0,0 +1,451 @@
+use std::{io::{ErrorKind, Read, Write}, net::{TcpListener, TcpStream}, sync::{Arc, Mutex}, thread, time::Duration};
+
+use super::{Config, ControlType, protection::DriveState};
+
+// Holding registers, floats are IEEE 754 singles in two registers, high word first. See the README.
+pub const HOLDING_MODE: usize = 0;
+pub const HOLDING_RUN: usize = 1;
+pub const HOLDING_TARGET: usize = 2;
+// kp, ki, kd of the position, speed and torque loops
+pub const HOLDING_GAINS: usize = 4;
+// voltage, speed and torque
+pub const HOLDING_BOUNDS: usize = 22;
+pub const HOLDING_LEN: usize = 28;
+// Input registers
+pub const INPUT_TIME: usize = 0;
+pub const INPUT_POSITION: usize = 2;
+pub const INPUT_VELOCITY: usize = 4;
+pub const INPUT_TORQUE: usize = 6;
+pub const INPUT_VOLTAGE: usize = 8;
+pub const INPUT_STATE: usize = 10;
+pub const INPUT_LEN: usize = 11;
+
+const READ_HOLDING: u8 = 0x03;
+const READ_INPUT: u8 = 0x04;
+const WRITE_SINGLE: u8 = 0x06;
+const WRITE_MULTIPLE: u8 = 0x10;
+const ILLEGAL_FUNCTION: u8 = 0x01;
+const ILLEGAL_ADDRESS: u8 = 0x02;
+const ILLEGAL_VALUE: u8 = 0x03;
+// how often a waiting thread checks the stop flag
+const POLL: Duration = Duration::from_millis(100);
+
+#[derive(Copy, Clone)]
+pub struct ConfigModbus{
+    port: u16,
+}
+
+// Register image shared between the server threads and the UI, which keeps it in sync with the config.
+pub struct ModbusStatus{
+    running: bool,
+    stop_flag: bool,
+    error: Option<String>,
+    // bound port, 0 until listening, the config may ask for any free one with 0
+    port: u16,
+    // control modes, Pos, PosVelTrq and then the custom laws
+    modes: u16,
+    holding: [u16; HOLDING_LEN],
+    input: [u16; INPUT_LEN],
+    // holding registers were written by a client since the last sync
+    written: bool,
+    clients: usize,
+    requests: usize,
+    exceptions: usize,
+}
+
+impl Default for ConfigModbus{
+    fn default() -> Self {
+        Self{port: 5020}
+    }
+}
+
+impl ConfigModbus{
+    pub fn set_port(&mut self) -> &mut u16{
+        &mut self.port
+    }
+}
+
+fn set_float(registers: &mut [u16], address: usize, value: f64){
+    let bits = (value as f32).to_bits();
+    registers[address] = (bits >> 16) as u16;
+    registers[address + 1] = bits as u16;
+}
+
+fn get_float(registers: &[u16], address: usize) -> f64{
+    f32::from_bits((registers[address] as u32) << 16 | registers[address + 1] as u32) as f64
+}
+
+impl ModbusStatus{
+    pub fn get_running(&self) -> bool{
+        self.running
+    }
+
+    pub fn set_stop_flag(&mut self) -> &mut bool{
+        &mut self.stop_flag
+    }
+
+    pub fn get_error(&self) -> &Option<String>{
+        &self.error
+    }
+
+    pub fn get_port(&self) -> u16{
+        self.port
+    }
+
+    pub fn get_clients(&self) -> usize{
+        self.clients
+    }
+
+    pub fn get_requests(&self) -> usize{
+        self.requests
+    }
+
+    pub fn get_exceptions(&self) -> usize{
+        self.exceptions
+    }
+
+    // Registers a client changed are applied, the target directly as the UI slider does. Returns true
+    // when the config changed and has to be sent, otherwise the registers take the values of the config.
+    pub fn sync(&mut self, config: &mut Config, target: &mut f64) -> bool{
+        if !std::mem::take(&mut self.written){
+            self.holding = Self::image(config, *target);
+            return false;
+        }
+        let registers = self.holding;
+        let image = Self::image(config, *target);
+        // floats the client left alone keep the f64 of the config
+        let value = |address: usize| Some(get_float(&registers, address))
+            .filter(|value| value.is_finite() && registers[address..address + 2] != image[address..address + 2]);
+        let mut changed = false;
+        if let Some(value) = value(HOLDING_TARGET){
+            *target = value;
+        }
+        for (pid, pid_conf) in config.set_pid_conf().iter_mut().enumerate(){
+            let address = HOLDING_GAINS + 6*pid;
+            for (offset, gain) in [&mut pid_conf.kp, &mut pid_conf.ki, &mut pid_conf.kd].into_iter().enumerate(){
+                if let Some(value) = value(address + 2*offset){
+                    *gain = value;
+                    changed = true;
+                }
+            }
+        }
+        let controller = config.set_controller_conf();
+        for (offset, bound) in [&mut controller.vltg_bound, &mut controller.vel_bound, &mut controller.trq_bound].into_iter().enumerate(){
+            if let Some(value) = value(HOLDING_BOUNDS + 2*offset){
+                *bound = value;
+                changed = true;
+            }
+        }
+        if registers[HOLDING_MODE] != image[HOLDING_MODE]{
+            controller.control_option = match registers[HOLDING_MODE]{
+                0 => ControlType::Pos,
+                1 => ControlType::PosVelTrq,
+                mode => ControlType::Custom(mode as usize - 2),
+            };
+            changed = true;
+        }
+        // starts and stops reach the controller with the config, as the Start and Stop buttons
+        if registers[HOLDING_RUN] != image[HOLDING_RUN]{
+            let run = registers[HOLDING_RUN] == 1;
+            if run{
+                controller.calib_option = None;
+            }
+            controller.start_flag = run;
+            changed = true;
+        }
+        self.holding = Self::image(config, *target);
+        changed
+    }
+
+    fn image(config: &Config, target: f64) -> [u16; HOLDING_LEN]{
+        let mut holding = [0; HOLDING_LEN];
+        let controller = config.get_controller_conf();
+        holding[HOLDING_MODE] = match controller.control_option{
+            ControlType::Pos => 0,
+            ControlType::PosVelTrq => 1,
+            ControlType::Custom(index) => index as u16 + 2,
+        };
+        holding[HOLDING_RUN] = controller.start_flag as u16;
+        set_float(&mut holding, HOLDING_TARGET, target);
+        for (pid, pid_conf) in config.get_pid_conf().iter().enumerate(){
+            for (offset, gain) in [pid_conf.kp, pid_conf.ki, pid_conf.kd].into_iter().enumerate(){
+                set_float(&mut holding, HOLDING_GAINS + 6*pid + 2*offset, gain);
+            }
+        }
+        for (offset, bound) in [controller.vltg_bound, controller.vel_bound, controller.trq_bound].into_iter().enumerate(){
+            set_float(&mut holding, HOLDING_BOUNDS + 2*offset, bound);
+        }
+        holding
+    }
+
+    // Live values, time in sec, position deg, velocity rpm, torque N*m and voltage V.
+    pub fn set_inputs(&mut self, values: [f64; 5], state: DriveState){
+        for (index, value) in values.into_iter().enumerate(){
+            set_float(&mut self.input, INPUT_TIME + 2*index, value);
+        }
+        self.input[INPUT_STATE] = match state{
+            DriveState::Running => 0,
+            DriveState::Stopping(_) => 1,
+            DriveState::Faulted(_) => 2,
+        };
+    }
+
+    // A write starting or ending at address would cover one register of a float.
+    fn splits_float(address: usize) -> bool{
+        address > HOLDING_TARGET && address < HOLDING_LEN && (address - HOLDING_TARGET)%2 == 1
+    }
+
+    // Bounds clamp the outputs as -bound..bound and can not be negative.
+    fn valid_float(address: usize, value: f64) -> bool{
+        value.is_finite() && (address < HOLDING_BOUNDS || value >= 0.0)
+    }
+
+    fn valid_holding(&self, address: usize, value: u16) -> bool{
+        match address{
+            HOLDING_MODE => value < self.modes,
+            HOLDING_RUN => value <= 1,
+            _ => true,
+        }
+    }
+
+    // Response PDU for a request PDU.
+    fn respond(&mut self, request: &[u8]) -> Vec<u8>{
+        self.requests += 1;
+        let function = request[0];
+        let word = |index: usize| request.get(index..index + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize);
+        let result = match (function, word(1), word(3)){
+            (READ_HOLDING | READ_INPUT, Some(start), Some(count)) => {
+                let registers: &[u16] = if function == READ_HOLDING {&self.holding} else {&self.input};
+                if count == 0 || count > 125{
+                    Err(ILLEGAL_VALUE)
+                } else if start + count > registers.len(){
+                    Err(ILLEGAL_ADDRESS)
+                } else {
+                    let mut response = vec![function, 2*count as u8];
+                    response.extend(registers[start..start + count].iter().flat_map(|register| register.to_be_bytes()));
+                    Ok(response)
+                }
+            }
+            (WRITE_SINGLE, Some(address), Some(value)) => {
+                // past the integers, floats take two registers and are only written whole with WRITE_MULTIPLE
+                if address >= HOLDING_TARGET{
+                    Err(ILLEGAL_ADDRESS)
+                } else if !self.valid_holding(address, value as u16){
+                    Err(ILLEGAL_VALUE)
+                } else {
+                    self.holding[address] = value as u16;
+                    self.written = true;
+                    Ok(request[..5].to_vec())
+                }
+            }
+            (WRITE_MULTIPLE, Some(start), Some(count)) => {
+                let values: Vec<u16> = request.get(6..).unwrap_or_default().chunks_exact(2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]])).collect();
+                if count == 0 || count > 123 || request.get(5) != Some(&(2*count as u8)) || values.len() != count{
+                    Err(ILLEGAL_VALUE)
+                } else if start + count > HOLDING_LEN || Self::splits_float(start) || Self::splits_float(start + count){
+                    Err(ILLEGAL_ADDRESS)
+                } else {
+                    let mut holding = self.holding;
+                    holding[start..start + count].copy_from_slice(&values);
+                    let mut floats = (HOLDING_TARGET..HOLDING_LEN).step_by(2).filter(|address| (start..start + count).contains(address));
+                    if !values.iter().enumerate().all(|(offset, value)| self.valid_holding(start + offset, *value))
+                        || !floats.all(|address| Self::valid_float(address, get_float(&holding, address))){
+                        Err(ILLEGAL_VALUE)
+                    } else {
+                        self.holding = holding;
+                        self.written = true;
+                        Ok(request[..5].to_vec())
+                    }
+                }
+            }
+            (READ_HOLDING | READ_INPUT | WRITE_SINGLE | WRITE_MULTIPLE, _, _) => Err(ILLEGAL_VALUE),
+            _ => Err(ILLEGAL_FUNCTION),
+        };
+        result.unwrap_or_else(|code|{
+            self.exceptions += 1;
+            vec![function | 0x80, code]
+        })
+    }
+}
+
+// Frames are answered as they complete, a connection ends on a malformed header.
+fn serve(mut stream: TcpStream, shared: Arc<Mutex<ModbusStatus>>){
+    if stream.set_read_timeout(Some(POLL)).is_err(){
+        return;
+    }
+    let mut pending: Vec<u8> = vec![];
+    let mut buffer = [0; 512];
+    while !shared.lock().unwrap().stop_flag{
+        match stream.read(&mut buffer){
+            Ok(0) => return,
+            Ok(len) => pending.extend(&buffer[..len]),
+            Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted) => continue,
+            Err(_) => return,
+        }
+        // MBAP header: transaction, protocol 0, length of the unit and the PDU, unit
+        while pending.len() >= 8{
+            let len = u16::from_be_bytes([pending[4], pending[5]]) as usize;
+            if pending[2..4] != [0, 0] || !(2..=254).contains(&len){
+                return;
+            }
+            if pending.len() < 6 + len{
+                break;
+            }
+            let frame: Vec<u8> = pending.drain(..6 + len).collect();
+            let response = shared.lock().unwrap().respond(&frame[7..]);
+            let mut reply = frame[..4].to_vec();
+            reply.extend((response.len() as u16 + 1).to_be_bytes());
+            reply.push(frame[6]);
+            reply.extend(response);
+            if stream.write_all(&reply).is_err(){
+                return;
+            }
+        }
+    }
+}
+
+// Listens on 127.0.0.1:port in its own thread, one more thread per client, until the stop flag is set.
+pub fn run(config: ConfigModbus, modes: u16) -> Arc<Mutex<ModbusStatus>>{
+    let status = Arc::new(Mutex::new(ModbusStatus{running: true, stop_flag: false, error: None, port: 0, modes, holding: [0; HOLDING_LEN],
+        input: [0; INPUT_LEN], written: false, clients: 0, requests: 0, exceptions: 0}));
+    let shared = Arc::clone(&status);
+
+    thread::spawn(move ||{
+        let listener = match TcpListener::bind(("127.0.0.1", config.port)).and_then(|listener| listener.set_nonblocking(true).map(|_| listener)){
+            Ok(listener) => listener,
+            Err(error) => {
+                let mut status = shared.lock().unwrap();
+                status.error = Some(error.to_string());
+                status.running = false;
+                return;
+            }
+        };
+        shared.lock().unwrap().port = listener.local_addr().map(|address| address.port()).unwrap_or(config.port);
+        while !shared.lock().unwrap().stop_flag{
+            match listener.accept(){
+                Ok((stream, _)) => {
+                    // accepted sockets may inherit the non blocking mode
+                    if stream.set_nonblocking(false).is_ok(){
+                        shared.lock().unwrap().clients += 1;
+                        let client = Arc::clone(&shared);
+                        thread::spawn(move || serve(stream, client));
+                    }
+                }
+                Err(_) => thread::sleep(POLL),
+            }
+        }
+        shared.lock().unwrap().running = false;
+    });
+    status
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn status() -> ModbusStatus{
+        ModbusStatus{running: true, stop_flag: false, error: None, port: 0, modes: 3, holding: [0; HOLDING_LEN], input: [0; INPUT_LEN],
+            written: false, clients: 0, requests: 0, exceptions: 0}
+    }
+
+    // WRITE_MULTIPLE request with the floats from address
+    fn write_floats(address: usize, values: &[f64]) -> Vec<u8>{
+        let mut registers = vec![0; 2*values.len()];
+        for (index, value) in values.iter().enumerate(){
+            set_float(&mut registers, 2*index, *value);
+        }
+        let mut request = vec![WRITE_MULTIPLE];
+        request.extend((address as u16).to_be_bytes());
+        request.extend((registers.len() as u16).to_be_bytes());
+        request.push(2*registers.len() as u8);
+        request.extend(registers.iter().flat_map(|register| register.to_be_bytes()));
+        request
+    }
+
+    #[test]
+    fn read_registers(){
+        let mut status = status();
+        status.set_inputs([1.0, 90.0, 0.0, 0.0, 0.0], DriveState::Running);
+        assert_eq!(status.respond(&[READ_INPUT, 0, 2, 0, 2]), vec![READ_INPUT, 4, 0x42, 0xb4, 0, 0]);
+        status.holding[HOLDING_MODE] = 1;
+        assert_eq!(status.respond(&[READ_HOLDING, 0, 0, 0, 1]), vec![READ_HOLDING, 2, 0, 1]);
+        assert_eq!(status.get_exceptions(), 0);
+    }
+
+    #[test]
+    fn exceptions(){
+        let mut status = status();
+        assert_eq!(status.respond(&[0x05, 0, 0, 0, 0]), vec![0x85, ILLEGAL_FUNCTION]);
+        assert_eq!(status.respond(&[READ_HOLDING, 0, 0, 0, 0]), vec![0x83, ILLEGAL_VALUE]);
+        assert_eq!(status.respond(&[READ_INPUT, 0, 10, 0, 2]), vec![0x84, ILLEGAL_ADDRESS]);
+        assert_eq!(status.respond(&[READ_HOLDING, 0]), vec![0x83, ILLEGAL_VALUE]);
+        // mode past the laws, run flag other than 0 and 1
+        assert_eq!(status.respond(&[WRITE_SINGLE, 0, 0, 0, 3]), vec![0x86, ILLEGAL_VALUE]);
+        assert_eq!(status.respond(&[WRITE_SINGLE, 0, 1, 0, 2]), vec![0x86, ILLEGAL_VALUE]);
+        // floats only whole
+        assert_eq!(status.respond(&[WRITE_SINGLE, 0, 4, 0, 0]), vec![0x86, ILLEGAL_ADDRESS]);
+        assert_eq!(status.respond(&write_floats(HOLDING_TARGET, &[1.0])).len(), 5);
+        let mut split = write_floats(HOLDING_GAINS, &[1.0]);
+        split[2] += 1;
+        assert_eq!(status.respond(&split), vec![0x90, ILLEGAL_ADDRESS]);
+        // byte count not matching the registers
+        let mut short = write_floats(HOLDING_GAINS, &[1.0]);
+        short.pop();
+        assert_eq!(status.respond(&short), vec![0x90, ILLEGAL_VALUE]);
+        assert_eq!(status.get_exceptions(), 9);
+        assert_eq!(status.get_requests(), 10);
+    }
+
+    #[test]
+    fn float_values(){
+        let mut status = status();
+        assert_eq!(status.respond(&write_floats(HOLDING_BOUNDS, &[-1.0])), vec![0x90, ILLEGAL_VALUE]);
+        assert_eq!(status.respond(&write_floats(HOLDING_GAINS, &[f64::NAN])), vec![0x90, ILLEGAL_VALUE]);
+        assert_eq!(status.holding, [0; HOLDING_LEN]);
+        // negative gains are valid
+        assert_eq!(status.respond(&write_floats(HOLDING_GAINS, &[-1.0, 2.0])), vec![WRITE_MULTIPLE, 0, HOLDING_GAINS as u8, 0, 4]);
+        assert_eq!(get_float(&status.holding, HOLDING_GAINS + 2), 2.0);
+    }
+
+    #[test]
+    fn sync_changes(){
+        let mut status = status();
+        let mut config = Config::default();
+        let mut target = 0.0;
+        assert!(!status.sync(&mut config, &mut target));
+        // the target is applied without the config
+        status.respond(&write_floats(HOLDING_TARGET, &[45.0]));
+        assert!(!status.sync(&mut config, &mut target));
+        assert_eq!(target, 45.0);
+        // the same run flag is no change
+        status.respond(&[WRITE_SINGLE, 0, 1, 0, 0]);
+        assert!(!status.sync(&mut config, &mut target));
+        status.respond(&write_floats(HOLDING_BOUNDS, &[6.0]));
+        assert!(status.sync(&mut config, &mut target));
+        assert_eq!(config.get_controller_conf().vltg_bound, 6.0);
+        status.respond(&[WRITE_SINGLE, 0, 1, 0, 1]);
+        assert!(status.sync(&mut config, &mut target));
+        assert!(config.get_controller_conf().start_flag);
+    }
+
+    #[test]
+    fn mbap_framing(){
+        // any free port
+        let status = run(ConfigModbus{port: 0}, 2);
+        let port = (0..50).find_map(|_|{
+            thread::sleep(Duration::from_millis(20));
+            Some(status.lock().unwrap().get_port()).filter(|port| *port != 0)
+        }).unwrap();
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        // two frames in one write, transaction ids and the unit are echoed
+        let frame = |transaction: u8, unit: u8| vec![0, transaction, 0, 0, 0, 6, unit, READ_HOLDING, 0, 0, 0, 1];
+        stream.write_all(&[frame(1, 7), frame(2, 9)].concat()).unwrap();
+        let mut reply = [0; 22];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(reply, [0, 1, 0, 0, 0, 5, 7, READ_HOLDING, 2, 0, 0, 0, 2, 0, 0, 0, 5, 9, READ_HOLDING, 2, 0, 0]);
+        // a foreign protocol id ends the connection
+        stream.write_all(&[0, 3, 0, 1, 0, 6, 1, READ_HOLDING, 0, 0, 0, 1]).unwrap();
+        assert_eq!(stream.read(&mut reply).unwrap_or(0), 0);
+        *status.lock().unwrap().set_stop_flag() = true;
+    }
+}
@@ -0,0 +1,246 @@
+use std::{sync::{Arc, Mutex}, thread};
+
+use super::{simulate, Config, TypePid, law::LawRegistry, math::Rng, metrics::StepMetrics, motor::ConfigMotor};
+
+pub const TOLERANCE_PARAMETERS: [&str; 5] = ["j", "b", "k", "r", "l"];
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Distribution{
+    Uniform,
+    Normal,
+}
+
+// Relative spread of one motor parameter: half width of the uniform, 3 sigma of the normal.
+#[derive(Copy, Clone)]
+pub struct ConfigTolerance{
+    distribution: Distribution,
+    tolerance: f64,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConfigMonteCarlo{
+    samples: usize,
+    seed: u64,
+    // ordered as TOLERANCE_PARAMETERS
+    tolerances: [ConfigTolerance; 5],
+}
+
+#[derive(Copy, Clone)]
+pub struct Sample{
+    motor: ConfigMotor,
+    metrics: Option<StepMetrics>,
+    unstable: bool,
+}
+
+pub struct MonteCarloProgress{
+    samples: usize,
+    results: Vec<Sample>,
+    nominal: Vec<[f64; 2]>,
+    // [time, min, max] over the stable samples
+    envelope: Vec<[f64; 3]>,
+    running: bool,
+    stop_flag: bool,
+}
+
+impl Default for ConfigTolerance{
+    fn default() -> Self {
+        Self{distribution: Distribution::Uniform, tolerance: 0.0}
+    }
+}
+
+impl Default for ConfigMonteCarlo{
+    fn default() -> Self {
+        let spread = ConfigTolerance{distribution: Distribution::Uniform, tolerance: 0.1};
+        Self{samples: 100, seed: 1, tolerances: [spread, ConfigTolerance::default(), spread, spread, ConfigTolerance::default()]}
+    }
+}
+
+impl Distribution{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            Distribution::Uniform => "Uniform",
+            Distribution::Normal => "Normal",
+        }
+    }
+}
+
+impl ConfigTolerance{
+    pub fn set_distribution(&mut self) -> &mut Distribution{
+        &mut self.distribution
+    }
+
+    pub fn set_tolerance(&mut self) -> &mut f64{
+        &mut self.tolerance
+    }
+
+    // multiplier of the nominal value, kept positive
+    fn sample(&self, rng: &mut Rng) -> f64{
+        let deviation = match self.distribution{
+            Distribution::Uniform => self.tolerance*(2.0*rng.uniform() - 1.0),
+            Distribution::Normal => self.tolerance/3.0*rng.normal(),
+        };
+        (1.0 + deviation).max(0.01)
+    }
+}
+
+impl ConfigMonteCarlo{
+    pub fn set_samples(&mut self) -> &mut usize{
+        &mut self.samples
+    }
+
+    pub fn set_seed(&mut self) -> &mut u64{
+        &mut self.seed
+    }
+
+    pub fn set_tolerances(&mut self) -> &mut [ConfigTolerance; 5]{
+        &mut self.tolerances
+    }
+
+    // Sample index draws from its own seed, so a run can be reproduced one sample at a time.
+    pub fn sample_motor(&self, motor: &ConfigMotor, index: usize) -> ConfigMotor{
+        let mut rng = Rng::new(self.seed.wrapping_add(index as u64));
+        let mut motor = *motor;
+        *motor.set_j() *= self.tolerances[0].sample(&mut rng);
+        *motor.set_b() *= self.tolerances[1].sample(&mut rng);
+        *motor.set_k() *= self.tolerances[2].sample(&mut rng);
+        *motor.set_r() *= self.tolerances[3].sample(&mut rng);
+        *motor.set_l() *= self.tolerances[4].sample(&mut rng);
+        motor
+    }
+}
+
+impl Sample{
+    pub fn get_motor(&self) -> &ConfigMotor{
+        &self.motor
+    }
+
+    pub fn get_metrics(&self) -> Option<StepMetrics>{
+        self.metrics
+    }
+
+    pub fn get_unstable(&self) -> bool{
+        self.unstable
+    }
+}
+
+impl MonteCarloProgress{
+    pub fn get_done(&self) -> usize{
+        self.results.len()
+    }
+
+    pub fn get_samples(&self) -> usize{
+        self.samples
+    }
+
+    pub fn get_results(&self) -> &Vec<Sample>{
+        &self.results
+    }
+
+    pub fn get_nominal(&self) -> &Vec<[f64; 2]>{
+        &self.nominal
+    }
+
+    pub fn lower_as_vec(&self) -> Vec<[f64; 2]>{
+        self.envelope.iter().map(|[time, min, _]| [*time, *min]).collect()
+    }
+
+    pub fn upper_as_vec(&self) -> Vec<[f64; 2]>{
+        self.envelope.iter().map(|[time, _, max]| [*time, *max]).collect()
+    }
+
+    pub fn get_running(&self) -> bool{
+        self.running
+    }
+
+    pub fn set_stop_flag(&mut self) -> &mut bool{
+        &mut self.stop_flag
+    }
+
+    // overshoot in percent of the stable samples
+    pub fn get_overshoots(&self) -> Vec<f64>{
+        self.results.iter().filter(|sample| !sample.unstable).filter_map(|sample| sample.metrics).map(|metrics| metrics.get_overshoot()).collect()
+    }
+
+    // settling time of the stable samples that settled
+    pub fn get_settling_times(&self) -> Vec<f64>{
+        self.results.iter().filter(|sample| !sample.unstable).filter_map(|sample| sample.metrics?.get_settling_time()).collect()
+    }
+
+    fn add_to_envelope(&mut self, signal: &[[f64; 2]]){
+        if self.envelope.is_empty(){
+            self.envelope = signal.iter().map(|[time, value]| [*time, *value, *value]).collect();
+            return;
+        }
+        self.envelope.truncate(signal.len());
+        for (bounds, [_, value]) in self.envelope.iter_mut().zip(signal.iter()){
+            bounds[1] = bounds[1].min(*value);
+            bounds[2] = bounds[2].max(*value);
+        }
+    }
+}
+
+// A run that did not settle and whose error does not shrink over the last half of the run.
+fn is_unstable(signal: &[[f64; 2]], target: f64, metrics: &Option<StepMetrics>) -> bool{
+    if signal.iter().any(|[_, value]| !value.is_finite()){
+        return true;
+    }
+    if metrics.is_some_and(|metrics| metrics.get_settling_time().is_some()) || signal.len() < 4{
+        return false;
+    }
+    let quarter = signal.len()/4;
+    let max_error = |points: &[[f64; 2]]| points.iter().map(|[_, value]| (target - value).abs()).fold(0.0, f64::max);
+    max_error(&signal[3*quarter..]) >= 0.9*max_error(&signal[2*quarter..3*quarter])
+}
+
+// (bin width, [bin center, count]) over the range of the values
+pub fn histogram(values: &[f64], bins: usize) -> (f64, Vec<[f64; 2]>){
+    let min = values.iter().copied().fold(f64::MAX, f64::min);
+    let max = values.iter().copied().fold(f64::MIN, f64::max);
+    if values.is_empty() || bins == 0{
+        return (1.0, vec![]);
+    }
+    let width = if max > min {(max - min)/bins as f64} else {1.0};
+    let mut counts = vec![0.0; bins];
+    for value in values{
+        counts[(((value - min)/width) as usize).min(bins - 1)] += 1.0;
+    }
+    (width, counts.iter().enumerate().map(|(i, count)| [min + (i as f64 + 0.5)*width, *count]).collect())
+}
+
+// Runs the calibration of the config for the nominal motor and then for every sampled motor, in its own thread.
+pub fn run(config: Config, montecarlo: ConfigMonteCarlo, registry: Arc<LawRegistry>) -> Arc<Mutex<MonteCarloProgress>>{
+    let progress = Arc::new(Mutex::new(MonteCarloProgress{samples: montecarlo.samples, results: vec![], nominal: vec![],
+        envelope: vec![], running: true, stop_flag: false}));
+    let shared = Arc::clone(&progress);
+
+    thread::spawn(move ||{
+        let calib = config.get_controller_conf().get_calib_option().unwrap_or(TypePid::Pos);
+        let mut config = config;
+        *config.set_controller_conf().set_calib_option() = Some(calib);
+        let target = config.get_controller_conf().get_calib_target().unwrap_or(0.0);
+
+        let nominal = simulate(config, Arc::clone(&registry)).clone_loop_as_vec(calib);
+        shared.lock().unwrap().nominal = nominal;
+
+        for index in 0..montecarlo.samples{
+            if shared.lock().unwrap().stop_flag{
+                break;
+            }
+            let motor = montecarlo.sample_motor(config.get_motor_conf(), index);
+            let mut sample_config = config;
+            *sample_config.set_motor_conf() = motor;
+            let points = simulate(sample_config, Arc::clone(&registry));
+            let signal = points.clone_loop_as_vec(calib);
+            let metrics = points.get_metrics();
+            let unstable = is_unstable(&signal, target, &metrics);
+
+            let mut progress = shared.lock().unwrap();
+            if !unstable{
+                progress.add_to_envelope(&signal);
+            }
+            progress.results.push(Sample{motor, metrics, unstable});
+        }
+        shared.lock().unwrap().running = false;
+    });
+    progress
+}
@@ -2,30 +2,91 @@ use nalgebra::{matrix,vector, Matrix2, Vector2};
 
 use super::math::{Integrator, Derivative, rad_to_deg, rads_to_rpm};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct ConfigMotor {
     j: f64,
     b: f64,
     l: f64,
     r: f64,
-    k: f64
+    k: f64,
+    tl: f64,
+    end_stops: ConfigEndStops
 }
 
+// Stops on the unwrapped angle in deg, the contact is a spring-damper in N*m/rad and N*m*s/rad.
+#[derive(Copy, Clone, PartialEq)]
+pub struct ConfigEndStops{
+    enabled: bool,
+    negative: f64,
+    positive: f64,
+    stiffness: f64,
+    damping: f64,
+}
+
+// discretizations kept, the split periods of the timing emulation alternate two steps
+const CACHE_LEN: usize = 2;
+
+// (a, b, e) of x[k+1] = a*x[k] + b*voltage + e*tl
+type Discretization = (Matrix2<f64>, Vector2<f64>, Vector2<f64>);
+
 pub struct Motor{
-    a_matrix: Matrix2<f64>,
-    b_vector: Vector2<f64>,
-    i_matrix: Matrix2<f64>,
+    // (delta, discretization) of the latest steps, newest first, cleared with the config
+    cache: Vec<(f64, Discretization)>,
     ss_vector: Vector2<f64>,
     position: Integrator,
     velocity: f64,
     acceleration: Derivative,
     torque: f64,
+    open_circuit: bool,
+    locked: bool,
     config: ConfigMotor
 }
 
 impl Default for ConfigMotor{
     fn default() -> Self {
-        Self{j:0.00065, b:0.000024 , l:0.00073, r:0.7, k:0.057}
+        Self{j:0.00065, b:0.000024 , l:0.00073, r:0.7, k:0.057, tl: 0.0, end_stops: ConfigEndStops::default()}
+    }
+}
+
+impl Default for ConfigEndStops{
+    fn default() -> Self {
+        Self{enabled: false, negative: -800.0, positive: 800.0, stiffness: 50.0, damping: 0.1}
+    }
+}
+
+impl ConfigEndStops{
+    pub fn set_enabled(&mut self) -> &mut bool{
+        &mut self.enabled
+    }
+
+    pub fn set_negative(&mut self) -> &mut f64{
+        &mut self.negative
+    }
+
+    pub fn set_positive(&mut self) -> &mut f64{
+        &mut self.positive
+    }
+
+    pub fn set_stiffness(&mut self) -> &mut f64{
+        &mut self.stiffness
+    }
+
+    pub fn set_damping(&mut self) -> &mut f64{
+        &mut self.damping
+    }
+
+    // Load torque of the contact at position rad and velocity rad/s, a stop only pushes the rotor back.
+    pub fn get_torque(&self, position: f64, velocity: f64) -> f64{
+        if !self.enabled{
+            return 0.0;
+        }
+        if position > self.positive.to_radians(){
+            (self.stiffness*(position - self.positive.to_radians()) + self.damping*velocity).max(0.0)
+        } else if position < self.negative.to_radians(){
+            (self.stiffness*(position - self.negative.to_radians()) + self.damping*velocity).min(0.0)
+        } else {
+            0.0
+        }
     }
 }
 
@@ -49,25 +110,110 @@ impl ConfigMotor{
     pub fn set_l(&mut self) ->&mut f64{
         &mut self.l
     }
+
+    pub fn set_tl(&mut self) ->&mut f64{
+        &mut self.tl
+    }
+
+    pub fn get_j(&self) -> f64{
+        self.j
+    }
+
+    pub fn get_b(&self) -> f64{
+        self.b
+    }
+
+    pub fn get_k(&self) -> f64{
+        self.k
+    }
+
+    pub fn get_r(&self) -> f64{
+        self.r
+    }
+
+    pub fn get_l(&self) -> f64{
+        self.l
+    }
+
+    pub fn get_tl(&self) -> f64{
+        self.tl
+    }
+
+    pub fn set_end_stops(&mut self) -> &mut ConfigEndStops{
+        &mut self.end_stops
+    }
+
+    pub fn get_end_stops(&self) -> &ConfigEndStops{
+        &self.end_stops
+    }
+
+    // Continuous model x' = a*x + b*voltage + e*tl, x = [velocity rad/s, current A]
+    pub fn get_state_space(&self) -> (Matrix2<f64>, Vector2<f64>, Vector2<f64>){
+        (matrix![-self.b/self.j, self.k/self.j; -self.k/self.l, -self.r/self.l],
+            vector![0.0, 1.0/self.l],
+            vector![-1.0/self.j, 0.0])
+    }
+
+    // Zero-order hold discretization of the state space model
+    pub fn discretize(&self, delta: f64) -> (Matrix2<f64>, Vector2<f64>, Vector2<f64>){
+        let (a_matrix, b_vector, e_vector) = self.get_state_space();
+        let a_d_matrix = (delta*a_matrix).exp();
+        let a_inv_matrix = a_matrix.try_inverse().unwrap();
+        let i_matrix = Matrix2::identity();
+        (a_d_matrix, a_inv_matrix*(a_d_matrix-i_matrix)*b_vector, a_inv_matrix*(a_d_matrix-i_matrix)*e_vector)
+    }
 }
 
 impl Motor {
     pub fn new(config: ConfigMotor) -> Self{
-        let a_matrix = matrix![-config.b/config.j, config.k/config.j; -config.k/config.l, -config.r/config.l];
-        let b_vector = vector![0.0, 1.0/config.l];
-        let i_matrix = matrix![1.0, 0.0; 0.0, 1.0];
         let ss_vector = vector![0.0, 0.0];
         let position = Integrator::default();
         let velocity = ss_vector[0];
         let acceleration = Derivative::default();
         let torque = config.k*ss_vector[1];
-        Self {a_matrix, b_vector, i_matrix, ss_vector, position, velocity, acceleration, torque, config}
+        Self {cache: Vec::with_capacity(CACHE_LEN), ss_vector, position, velocity, acceleration, torque, open_circuit: false, locked: false, config}
+    }
+
+    // Zero-order hold matrices for a step of delta, the matrix exponential only for a new step.
+    fn discretization(&mut self, delta: f64) -> Discretization{
+        match self.cache.iter().position(|(cached, _)| *cached == delta){
+            Some(index) => {
+                let entry = self.cache.remove(index);
+                self.cache.insert(0, entry);
+            }
+            None => {
+                self.cache.truncate(CACHE_LEN - 1);
+                self.cache.insert(0, (delta, self.config.discretize(delta)));
+            }
+        }
+        self.cache[0].1
     }
 
     pub fn update_state(&mut self, delta: f64, voltage: f64){
-        let a_d_matrix = (delta*self.a_matrix).exp();
-        let b_d_vector = self.a_matrix.try_inverse().unwrap()*(a_d_matrix-self.i_matrix)*self.b_vector;
-        self.ss_vector = a_d_matrix*self.ss_vector+b_d_vector*voltage;
+        // end stop contact adds to the load over the period
+        let tl = self.config.tl + self.config.end_stops.get_torque(self.position.get_state(), self.ss_vector[0]);
+        self.ss_vector = match (self.open_circuit, self.locked){
+            (false, false) => {
+                let (a_d_matrix, b_d_vector, e_d_vector) = self.discretization(delta);
+                a_d_matrix*self.ss_vector+b_d_vector*voltage+e_d_vector*tl
+            }
+            // no current, the rotor coasts against friction and load
+            (true, false) => {
+                let decay = (-self.config.b/self.config.j*delta).exp();
+                let velocity = if self.config.b > 0.0 {
+                    self.ss_vector[0]*decay - (1.0 - decay)*tl/self.config.b
+                } else {
+                    self.ss_vector[0] - delta*tl/self.config.j
+                };
+                vector![velocity, 0.0]
+            }
+            // rotor held, only the winding
+            (false, true) => {
+                let decay = (-self.config.r/self.config.l*delta).exp();
+                vector![0.0, self.ss_vector[1]*decay + (1.0 - decay)*voltage/self.config.r]
+            }
+            (true, true) => vector![0.0, 0.0],
+        };
         self.position.integrate(delta, self.ss_vector[0]); 
         self.velocity = self.ss_vector[0];
         self.acceleration.derivate(delta, self.ss_vector[0]);
@@ -76,20 +222,24 @@ impl Motor {
 
     pub fn reset(&mut self, config: ConfigMotor){
         self.config = config;
-        self.a_matrix = matrix![-config.b/config.j, config.k/config.j; -config.k/config.l, -config.r/config.l];
-        self.b_vector = vector![0.0, 1.0/config.l];
-        self.i_matrix = matrix![1.0, 0.0; 0.0, 1.0];
+        self.cache.clear();
         self.ss_vector = vector![0.0, 0.0];
         self.position = Integrator::default();
         self.velocity = self.ss_vector[0];
         self.acceleration = Derivative::default();
         self.torque = config.k*self.ss_vector[1];
-
+        self.open_circuit = false;
+        self.locked = false;
     }
 
     pub fn get_position(&self) -> f64{
         rad_to_deg(self.position.get_state())
     }
+
+    // deg, not wrapped to a revolution
+    pub fn get_unwrapped_position(&self) -> f64{
+        self.position.get_state().to_degrees()
+    }
     
     pub fn get_velocity(&self) -> f64{
         rads_to_rpm(self.velocity)
@@ -103,4 +253,50 @@ impl Motor {
         self.torque
     }
 
+    pub fn get_config(&self) -> &ConfigMotor{
+        &self.config
+    }
+
+    // motor leads disconnected
+    pub fn set_open_circuit(&mut self) -> &mut bool{
+        &mut self.open_circuit
+    }
+
+    // rotor jammed
+    pub fn set_locked(&mut self) -> &mut bool{
+        &mut self.locked
+    }
+
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn steady_speed(){
+        let config = ConfigMotor::default();
+        let mut motor = Motor::new(config);
+        for _ in 0..5000{
+            motor.update_state(0.001, 12.0);
+        }
+        // k*i = b*w and 12 = r*i + k*w
+        let speed = 12.0*config.k/(config.r*config.b + config.k*config.k);
+        assert!((motor.get_velocity() - rads_to_rpm(speed)).abs() < 1e-6*rads_to_rpm(speed));
+    }
+
+    #[test]
+    fn split_steps(){
+        // the hold is exact for a constant voltage, so split steps land on the whole ones
+        let mut whole = Motor::new(ConfigMotor::default());
+        let mut split = Motor::new(ConfigMotor::default());
+        for step in 0..1000{
+            let voltage = if step < 500 {12.0} else {-6.0};
+            whole.update_state(0.001, voltage);
+            split.update_state(0.0003, voltage);
+            split.update_state(0.0007, voltage);
+        }
+        assert!((whole.get_velocity() - split.get_velocity()).abs() < 1e-9*whole.get_velocity().abs().max(1.0));
+        assert!((whole.get_torque() - split.get_torque()).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,76 @@
+use super::{math::rpm_to_rads, motor::ConfigMotor};
+
+#[derive(Copy, Clone)]
+pub struct ConfigDob{
+    enabled: bool,
+    bandwidth: f64,
+}
+
+// Disturbance observer on the nominal mechanical model j*dw/dt + b*w = t - tl.
+// The q-filter is first order, the derivative of w is folded into the filter
+// so the estimate does not need a differentiated speed signal.
+pub struct Dob{
+    config: ConfigDob,
+    motor: ConfigMotor,
+    filter: f64,
+    estimate: f64,
+}
+
+impl Default for ConfigDob{
+    fn default() -> Self {
+        Self{enabled: false, bandwidth: 200.0}
+    }
+}
+
+impl ConfigDob{
+    pub fn set_enabled(&mut self) -> &mut bool{
+        &mut self.enabled
+    }
+
+    pub fn get_enabled(&self) -> bool{
+        self.enabled
+    }
+
+    pub fn set_bandwidth(&mut self) -> &mut f64{
+        &mut self.bandwidth
+    }
+
+    pub fn get_bandwidth(&self) -> f64{
+        self.bandwidth
+    }
+}
+
+impl Dob{
+    pub fn new(config: ConfigDob, motor: ConfigMotor) -> Self{
+        Self{config, motor, filter: 0.0, estimate: 0.0}
+    }
+
+    pub fn reset(&mut self, config: ConfigDob, motor: ConfigMotor){
+        self.config = config;
+        self.motor = motor;
+        self.filter = 0.0;
+        self.estimate = 0.0;
+    }
+
+    // torque in N*m, velocity in rpm
+    pub fn update(&mut self, delta: f64, torque: f64, velocity: f64){
+        let vel = rpm_to_rads(velocity);
+        let gain = self.config.bandwidth*self.motor.get_j();
+        let input = torque - self.motor.get_b()*vel + gain*vel;
+        let alpha = 1.0 - (-self.config.bandwidth*delta).exp();
+        self.filter += alpha*(input - self.filter);
+        self.estimate = self.filter - gain*vel;
+    }
+
+    pub fn compensate(&self, trq: f64, bound: f64) -> f64{
+        if self.config.enabled{
+            (trq + self.estimate).clamp(-bound, bound)
+        } else {
+            trq
+        }
+    }
+
+    pub fn get_estimate(&self) -> f64{
+        self.estimate
+    }
+}
@@ -0,0 +1,336 @@
+use std::{sync::{Arc, Mutex}, thread};
+
+use super::{simulate, Config, ConfigPid, ControlType, TypePid, law::LawRegistry, metrics::StepMetrics};
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Cost{
+    Itae,
+    Ise,
+    OvershootSettling,
+}
+
+pub const COSTS: [Cost; 3] = [Cost::Itae, Cost::Ise, Cost::OvershootSettling];
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum OptimTarget{
+    Loop(TypePid),
+    Cascade,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConfigOptim{
+    target: OptimTarget,
+    cost: Cost,
+    max_iter: usize,
+    // [loop][kp, ki, kd] = (lower, upper), a gain with equal bounds is kept fixed
+    bounds: [[(f64, f64); 3]; 3],
+}
+
+#[derive(Clone)]
+pub struct OptimProgress{
+    iteration: usize,
+    max_iter: usize,
+    best_cost: f64,
+    best_gains: [ConfigPid; 3],
+    running: bool,
+    stop_flag: bool,
+}
+
+struct Problem{
+    config: Config,
+    optim: ConfigOptim,
+    registry: Arc<LawRegistry>,
+    // (loop, gain) of every free parameter
+    params: Vec<(usize, usize)>,
+}
+
+impl Default for ConfigOptim{
+    fn default() -> Self {
+        Self{target: OptimTarget::Loop(TypePid::Pos), cost: Cost::Itae, max_iter: 100,
+            bounds: [[(0.0, 200.0), (0.0, 100.0), (0.0, 10.0)],
+                [(0.0, 0.1), (0.0, 10.0), (0.0, 0.001)],
+                [(0.0, 50.0), (0.0, 20000.0), (0.0, 0.1)]]}
+    }
+}
+
+impl Cost{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            Cost::Itae => "ITAE",
+            Cost::Ise => "ISE",
+            Cost::OvershootSettling => "Settling time + overshoot",
+        }
+    }
+}
+
+impl ConfigOptim{
+    pub fn set_target(&mut self) -> &mut OptimTarget{
+        &mut self.target
+    }
+
+    pub fn set_cost(&mut self) -> &mut Cost{
+        &mut self.cost
+    }
+
+    pub fn get_cost(&self) -> Cost{
+        self.cost
+    }
+
+    pub fn set_max_iter(&mut self) -> &mut usize{
+        &mut self.max_iter
+    }
+
+    pub fn set_bounds(&mut self) -> &mut [[(f64, f64); 3]; 3]{
+        &mut self.bounds
+    }
+}
+
+impl OptimProgress{
+    pub fn get_iteration(&self) -> usize{
+        self.iteration
+    }
+
+    pub fn get_max_iter(&self) -> usize{
+        self.max_iter
+    }
+
+    pub fn get_best_cost(&self) -> f64{
+        self.best_cost
+    }
+
+    pub fn get_best_gains(&self) -> &[ConfigPid; 3]{
+        &self.best_gains
+    }
+
+    pub fn get_running(&self) -> bool{
+        self.running
+    }
+
+    pub fn set_stop_flag(&mut self) -> &mut bool{
+        &mut self.stop_flag
+    }
+}
+
+impl Problem{
+    fn new(config: Config, optim: ConfigOptim, registry: Arc<LawRegistry>) -> Self{
+        let loops = match optim.target{
+            OptimTarget::Loop(pid) => vec![pid as usize],
+            OptimTarget::Cascade => match config.get_controller_conf().get_control_option(){
+                ControlType::Pos => vec![0],
+                _ => vec![0, 1, 2],
+            },
+        };
+        let mut params = vec![];
+        for pid in loops{
+            for gain in 0..3{
+                let (lower, upper) = optim.bounds[pid][gain];
+                if upper > lower{
+                    params.push((pid, gain));
+                }
+            }
+        }
+        Self{config, optim, registry, params}
+    }
+
+    fn get_gain(pid: &mut ConfigPid, gain: usize) -> &mut f64{
+        match gain{
+            0 => pid.set_kp(),
+            1 => pid.set_ki(),
+            _ => pid.set_kd(),
+        }
+    }
+
+    // x is normalized to [0, 1] over the bounds of every free parameter
+    fn apply(&self, x: &[f64]) -> Config{
+        let mut config = self.config;
+        for (value, (pid, gain)) in x.iter().zip(self.params.iter()){
+            let (lower, upper) = self.optim.bounds[*pid][*gain];
+            *Problem::get_gain(&mut config.set_pid_conf()[*pid], *gain) = lower + value.clamp(0.0, 1.0)*(upper - lower);
+        }
+        config
+    }
+
+    fn initial_point(&self) -> Vec<f64>{
+        self.params.iter().map(|(pid, gain)| {
+            let (lower, upper) = self.optim.bounds[*pid][*gain];
+            let mut pid_conf = self.config.get_pid_conf()[*pid];
+            ((*Problem::get_gain(&mut pid_conf, *gain) - lower)/(upper - lower)).clamp(0.0, 1.0)
+        }).collect()
+    }
+
+    fn evaluate(&self, x: &[f64]) -> f64{
+        let mut config = self.apply(x);
+        let calib = match self.optim.target{
+            OptimTarget::Loop(pid) => pid,
+            OptimTarget::Cascade => TypePid::Pos,
+        };
+        *config.set_controller_conf().set_calib_option() = Some(calib);
+        let points = simulate(config, Arc::clone(&self.registry));
+        let cost = match points.get_metrics(){
+            Some(metrics) => step_cost(&metrics, self.optim.cost, config.get_controller_conf().get_duration()),
+            None => f64::MAX,
+        };
+        if cost.is_finite() {cost} else {f64::MAX}
+    }
+}
+
+fn step_cost(metrics: &StepMetrics, cost: Cost, duration: f64) -> f64{
+    match cost{
+        Cost::Itae => metrics.get_itae(),
+        Cost::Ise => metrics.get_ise(),
+        Cost::OvershootSettling => metrics.get_settling_time().unwrap_or(2.0*duration) + duration*metrics.get_overshoot()/100.0,
+    }
+}
+
+// Bounded Nelder-Mead over the unit box from x0, report gets the iteration, the best point and its cost
+// after every sort and stops the search by returning false. Returns the best point and its cost.
+fn nelder_mead(x0: Vec<f64>, max_iter: usize, evaluate: impl Fn(&[f64]) -> f64, mut report: impl FnMut(usize, &[f64], f64) -> bool) -> (Vec<f64>, f64){
+    let n = x0.len();
+    let mut simplex = vec![x0.clone()];
+    for i in 0..n{
+        let mut x = x0.clone();
+        x[i] = if x[i] + 0.1 <= 1.0 {x[i] + 0.1} else {x[i] - 0.1};
+        simplex.push(x);
+    }
+    let mut costs: Vec<f64> = simplex.iter().map(|x| evaluate(x)).collect();
+
+    for iteration in 0..max_iter{
+        let mut order: Vec<usize> = (0..=n).collect();
+        order.sort_by(|a, b| costs[*a].total_cmp(&costs[*b]));
+        simplex = order.iter().map(|i| simplex[*i].clone()).collect();
+        costs = order.iter().map(|i| costs[*i]).collect();
+
+        if !report(iteration + 1, &simplex[0], costs[0]){
+            break;
+        }
+        if costs[n] - costs[0] <= 1e-9*costs[0].abs(){
+            break;
+        }
+
+        let centroid: Vec<f64> = (0..n).map(|j| simplex[..n].iter().map(|x| x[j]).sum::<f64>()/n as f64).collect();
+        let point = |coef: f64| -> Vec<f64>{
+            centroid.iter().zip(simplex[n].iter()).map(|(c, w)| (c + coef*(w - c)).clamp(0.0, 1.0)).collect()
+        };
+
+        let reflected = point(-1.0);
+        let reflected_cost = evaluate(&reflected);
+        if reflected_cost < costs[0]{
+            let expanded = point(-2.0);
+            let expanded_cost = evaluate(&expanded);
+            if expanded_cost < reflected_cost{
+                simplex[n] = expanded;
+                costs[n] = expanded_cost;
+            } else {
+                simplex[n] = reflected;
+                costs[n] = reflected_cost;
+            }
+        } else if reflected_cost < costs[n - 1]{
+            simplex[n] = reflected;
+            costs[n] = reflected_cost;
+        } else {
+            let contracted = point(0.5);
+            let contracted_cost = evaluate(&contracted);
+            if contracted_cost < costs[n]{
+                simplex[n] = contracted;
+                costs[n] = contracted_cost;
+            } else {
+                for i in 1..=n{
+                    simplex[i] = simplex[0].iter().zip(simplex[i].iter()).map(|(b, x)| b + 0.5*(x - b)).collect();
+                    costs[i] = evaluate(&simplex[i]);
+                }
+            }
+        }
+    }
+
+    let best = (0..=n).min_by(|a, b| costs[*a].total_cmp(&costs[*b])).unwrap();
+    (simplex.swap_remove(best), costs[best])
+}
+
+// Searches the gains in normalized coordinates in its own thread.
+pub fn optimize(config: Config, optim: ConfigOptim, registry: Arc<LawRegistry>) -> Arc<Mutex<OptimProgress>>{
+    let progress = Arc::new(Mutex::new(OptimProgress{iteration: 0, max_iter: optim.max_iter, best_cost: f64::MAX,
+        best_gains: *config.get_pid_conf(), running: true, stop_flag: false}));
+    let shared = Arc::clone(&progress);
+
+    thread::spawn(move ||{
+        let problem = Problem::new(config, optim, registry);
+        if problem.params.is_empty(){
+            shared.lock().unwrap().running = false;
+            return;
+        }
+
+        let (best, cost) = nelder_mead(problem.initial_point(), optim.max_iter, |x| problem.evaluate(x), |iteration, best, cost|{
+            let mut progress = shared.lock().unwrap();
+            progress.iteration = iteration;
+            progress.best_cost = cost;
+            progress.best_gains = *problem.apply(best).get_pid_conf();
+            !progress.stop_flag
+        });
+
+        let mut progress = shared.lock().unwrap();
+        progress.best_cost = cost;
+        progress.best_gains = *problem.apply(&best).get_pid_conf();
+        progress.running = false;
+    });
+
+    progress
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn distance(x: &[f64], y: &[f64]) -> f64{
+        x.iter().zip(y.iter()).map(|(a, b)| (a - b).powi(2)).sum::<f64>().sqrt()
+    }
+
+    #[test]
+    fn quadratic_bowl(){
+        let minimum = [0.3, 0.7, 0.45];
+        let bowl = |x: &[f64]| x.iter().zip(minimum.iter()).enumerate().map(|(i, (a, b))| (i + 1) as f64*(a - b).powi(2)).sum::<f64>();
+        let (best, cost) = nelder_mead(vec![0.5; 3], 500, bowl, |_, _, _| true);
+        assert!(distance(&best, &minimum) < 1e-3, "best {:?}", best);
+        assert!(cost < 1e-6);
+    }
+
+    #[test]
+    fn rosenbrock(){
+        // (1, 1) of the Rosenbrock valley maps to (0.75, 0.75)
+        let valley = |x: &[f64]|{
+            let (a, b) = (4.0*x[0] - 2.0, 4.0*x[1] - 2.0);
+            (1.0 - a).powi(2) + 100.0*(b - a*a).powi(2)
+        };
+        let (best, _) = nelder_mead(vec![0.2, 0.8], 2000, valley, |_, _, _| true);
+        assert!(distance(&best, &[0.75, 0.75]) < 1e-3, "best {:?}", best);
+    }
+
+    #[test]
+    fn minimum_outside_the_box(){
+        let (best, _) = nelder_mead(vec![0.5, 0.5], 500, |x| (x[0] - 1.5).powi(2) + (x[1] + 0.2).powi(2), |_, _, _| true);
+        assert!(distance(&best, &[1.0, 0.0]) < 1e-6, "best {:?}", best);
+    }
+
+    #[test]
+    fn stops_on_report(){
+        let mut iterations = 0;
+        nelder_mead(vec![0.5, 0.5], 500, |x| x[0] + x[1], |iteration, _, _|{
+            iterations = iteration;
+            iteration < 3
+        });
+        assert_eq!(iterations, 3);
+    }
+
+    #[test]
+    fn normalized_gains(){
+        let mut optim = ConfigOptim::default();
+        // ki of the angle loop fixed
+        optim.set_bounds()[0][1] = (1.0, 1.0);
+        let problem = Problem::new(Config::default(), optim, Arc::new(LawRegistry::default()));
+        assert_eq!(problem.params, vec![(0, 0), (0, 2)]);
+        // default kp 40 of 0..200 and kd 1.5 of 0..10
+        let x = problem.initial_point();
+        assert!(distance(&x, &[0.2, 0.15]) < 1e-12);
+        let pid = problem.apply(&[0.5, 2.0]).get_pid_conf()[0];
+        assert_eq!((pid.get_kp(), pid.get_ki(), pid.get_kd()), (100.0, 1.0, 10.0));
+    }
+}
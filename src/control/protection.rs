@@ -0,0 +1,369 @@
+use std::sync::{Arc, Mutex};
+
+use super::{law::{Measurements, References}, ControlType, Motor};
+
+// below this speed a stopping drive counts as stopped, rpm
+const STANDSTILL: f64 = 10.0;
+const HISTORY_LEN: usize = 100;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Trip{
+    Overcurrent,
+    Overspeed,
+    Stall,
+    FollowingError,
+    OverTemperature,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Reaction{
+    // power stage off, the rotor runs down freely
+    Coast,
+    // windings shorted
+    Brake,
+    // speed reference ramped to zero, needs the speed loop
+    QuickStop,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum DriveState{
+    Running,
+    Stopping(Trip),
+    Faulted(Trip),
+}
+
+// A limit of 0 disables its check. The winding temperature follows a first order thermal model.
+#[derive(Copy, Clone)]
+pub struct ConfigProtection{
+    enabled: bool,
+    reaction: Reaction,
+    // rpm/s
+    quick_stop_decel: f64,
+    // sec, a drive not at standstill by then is faulted anyway
+    stop_time: f64,
+    // A
+    overcurrent: f64,
+    // rpm
+    overspeed: f64,
+    // current above stall_current with speed below stall_speed for stall_time
+    stall_current: f64,
+    stall_speed: f64,
+    stall_time: f64,
+    // deg, for following_time
+    following_error: f64,
+    following_time: f64,
+    // deg C
+    max_temperature: f64,
+    ambient: f64,
+    // K/W, J/K
+    thermal_resistance: f64,
+    thermal_capacity: f64,
+}
+
+#[derive(Copy, Clone)]
+pub struct TripRecord{
+    time: f64,
+    trip: Trip,
+    value: f64,
+}
+
+// Shared between the controller thread and the UI.
+pub struct ProtectionStatus{
+    state: DriveState,
+    temperature: f64,
+    history: Vec<TripRecord>,
+    reset_request: bool,
+    // a software position limit acts on the references
+    limit_active: bool,
+}
+
+pub struct Supervisor{
+    config: ConfigProtection,
+    control_option: ControlType,
+    status: Arc<Mutex<ProtectionStatus>>,
+    state: DriveState,
+    temperature: f64,
+    stall_timer: f64,
+    following_timer: f64,
+    stop_timer: f64,
+    // quick stop speed reference, rpm
+    ramp: f64,
+}
+
+impl Default for ConfigProtection{
+    fn default() -> Self {
+        Self{enabled: false, reaction: Reaction::QuickStop, quick_stop_decel: 20000.0, stop_time: 2.0, overcurrent: 25.0, overspeed: 5000.0,
+            stall_current: 15.0, stall_speed: 10.0, stall_time: 0.5, following_error: 0.0, following_time: 0.2,
+            max_temperature: 120.0, ambient: 25.0, thermal_resistance: 3.0, thermal_capacity: 20.0}
+    }
+}
+
+impl Default for ProtectionStatus{
+    fn default() -> Self {
+        Self{state: DriveState::Running, temperature: ConfigProtection::default().ambient, history: vec![], reset_request: false, limit_active: false}
+    }
+}
+
+impl Trip{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            Trip::Overcurrent => "Overcurrent, A",
+            Trip::Overspeed => "Overspeed, rpm",
+            Trip::Stall => "Stall, A",
+            Trip::FollowingError => "Following error, deg",
+            Trip::OverTemperature => "Over-temperature, C",
+        }
+    }
+}
+
+impl Reaction{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            Reaction::Coast => "Coast",
+            Reaction::Brake => "Brake",
+            Reaction::QuickStop => "Quick stop",
+        }
+    }
+}
+
+impl DriveState{
+    pub fn get_name(&self) -> String{
+        match self{
+            DriveState::Running => "Running".to_string(),
+            DriveState::Stopping(trip) => format!("Stopping on {}", trip.get_name()),
+            DriveState::Faulted(trip) => format!("Faulted on {}", trip.get_name()),
+        }
+    }
+}
+
+impl ConfigProtection{
+    pub fn set_enabled(&mut self) -> &mut bool{
+        &mut self.enabled
+    }
+
+    pub fn set_reaction(&mut self) -> &mut Reaction{
+        &mut self.reaction
+    }
+
+    pub fn set_quick_stop_decel(&mut self) -> &mut f64{
+        &mut self.quick_stop_decel
+    }
+
+    pub fn set_stop_time(&mut self) -> &mut f64{
+        &mut self.stop_time
+    }
+
+    pub fn set_overcurrent(&mut self) -> &mut f64{
+        &mut self.overcurrent
+    }
+
+    pub fn set_overspeed(&mut self) -> &mut f64{
+        &mut self.overspeed
+    }
+
+    pub fn set_stall_current(&mut self) -> &mut f64{
+        &mut self.stall_current
+    }
+
+    pub fn set_stall_speed(&mut self) -> &mut f64{
+        &mut self.stall_speed
+    }
+
+    pub fn set_stall_time(&mut self) -> &mut f64{
+        &mut self.stall_time
+    }
+
+    pub fn set_following_error(&mut self) -> &mut f64{
+        &mut self.following_error
+    }
+
+    pub fn set_following_time(&mut self) -> &mut f64{
+        &mut self.following_time
+    }
+
+    pub fn set_max_temperature(&mut self) -> &mut f64{
+        &mut self.max_temperature
+    }
+
+    pub fn set_ambient(&mut self) -> &mut f64{
+        &mut self.ambient
+    }
+
+    pub fn set_thermal_resistance(&mut self) -> &mut f64{
+        &mut self.thermal_resistance
+    }
+
+    pub fn set_thermal_capacity(&mut self) -> &mut f64{
+        &mut self.thermal_capacity
+    }
+}
+
+impl TripRecord{
+    pub fn get_time(&self) -> f64{
+        self.time
+    }
+
+    pub fn get_trip(&self) -> Trip{
+        self.trip
+    }
+
+    // measured value that tripped
+    pub fn get_value(&self) -> f64{
+        self.value
+    }
+}
+
+impl ProtectionStatus{
+    pub fn get_state(&self) -> DriveState{
+        self.state
+    }
+
+    // winding, deg C
+    pub fn get_temperature(&self) -> f64{
+        self.temperature
+    }
+
+    pub fn get_history(&self) -> &Vec<TripRecord>{
+        &self.history
+    }
+
+    pub fn clear_history(&mut self){
+        self.history.clear();
+    }
+
+    pub fn set_reset_request(&mut self) -> &mut bool{
+        &mut self.reset_request
+    }
+
+    pub fn set_limit_active(&mut self) -> &mut bool{
+        &mut self.limit_active
+    }
+
+    pub fn get_limit_active(&self) -> bool{
+        self.limit_active
+    }
+}
+
+impl Supervisor{
+    pub fn new(config: ConfigProtection, control_option: ControlType, status: Arc<Mutex<ProtectionStatus>>) -> Self{
+        {
+            let mut status = status.lock().unwrap();
+            status.state = DriveState::Running;
+            status.temperature = config.ambient;
+            status.reset_request = false;
+        }
+        Self{config, control_option, status, state: DriveState::Running, temperature: config.ambient,
+            stall_timer: 0.0, following_timer: 0.0, stop_timer: 0.0, ramp: 0.0}
+    }
+
+    pub fn get_state(&self) -> DriveState{
+        self.state
+    }
+
+    fn quick_stop_available(&self) -> bool{
+        self.config.reaction == Reaction::QuickStop && self.control_option == ControlType::PosVelTrq
+    }
+
+    // Checks the limits on the sensed values, the temperature uses the motor current.
+    pub fn update(&mut self, time: f64, delta: f64, measurements: &Measurements, references: &References, motor: &Motor){
+        let motor_conf = motor.get_config();
+        let winding_current = motor.get_torque()/motor_conf.get_k();
+        let heating = winding_current*winding_current*motor_conf.get_r();
+        let cooling = (self.temperature - self.config.ambient)/self.config.thermal_resistance;
+        self.temperature += delta*(heating - cooling)/self.config.thermal_capacity;
+
+        let current = measurements.get_torque()/motor_conf.get_k();
+        let velocity = measurements.get_velocity();
+        let reset = std::mem::take(&mut self.status.lock().unwrap().reset_request);
+
+        if self.config.enabled{
+            match self.state{
+                DriveState::Running => {
+                    if let Some((trip, value)) = self.check(delta, current, velocity, measurements, references){
+                        self.state = DriveState::Stopping(trip);
+                        self.ramp = velocity;
+                        self.stop_timer = 0.0;
+                        let mut status = self.status.lock().unwrap();
+                        if status.history.len() >= HISTORY_LEN{
+                            status.history.remove(0);
+                        }
+                        status.history.push(TripRecord{time, trip, value});
+                    }
+                }
+                DriveState::Stopping(trip) => {
+                    // a driving load or a frozen speed sensor never reaches standstill
+                    self.stop_timer += delta;
+                    let timed_out = self.config.stop_time > 0.0 && self.stop_timer > self.config.stop_time;
+                    if velocity.abs() < STANDSTILL || timed_out{
+                        self.state = DriveState::Faulted(trip);
+                    }
+                }
+                DriveState::Faulted(_) => {}
+            }
+        }
+        if reset || !self.config.enabled{
+            self.state = DriveState::Running;
+            self.stall_timer = 0.0;
+            self.following_timer = 0.0;
+        }
+
+        let mut status = self.status.lock().unwrap();
+        status.state = self.state;
+        status.temperature = self.temperature;
+    }
+
+    fn check(&mut self, delta: f64, current: f64, velocity: f64, measurements: &Measurements, references: &References) -> Option<(Trip, f64)>{
+        let config = self.config;
+        if config.overcurrent > 0.0 && current.abs() > config.overcurrent{
+            return Some((Trip::Overcurrent, current));
+        }
+        if config.overspeed > 0.0 && velocity.abs() > config.overspeed{
+            return Some((Trip::Overspeed, velocity));
+        }
+
+        self.stall_timer = if current.abs() > config.stall_current && velocity.abs() < config.stall_speed {self.stall_timer + delta} else {0.0};
+        if config.stall_current > 0.0 && self.stall_timer > config.stall_time{
+            return Some((Trip::Stall, current));
+        }
+
+        let following = references.get_pos().map_or(0.0, |pos| pos - measurements.get_position());
+        self.following_timer = if following.abs() > config.following_error {self.following_timer + delta} else {0.0};
+        if config.following_error > 0.0 && self.following_timer > config.following_time{
+            return Some((Trip::FollowingError, following));
+        }
+
+        if config.max_temperature > 0.0 && self.temperature > config.max_temperature{
+            return Some((Trip::OverTemperature, self.temperature));
+        }
+        None
+    }
+
+    // References handed to the law, the quick stop ramp replaces them while stopping.
+    pub fn get_references(&mut self, references: References, delta: f64) -> References{
+        match self.state{
+            DriveState::Stopping(_) if self.quick_stop_available() => {
+                let step = self.config.quick_stop_decel*delta;
+                self.ramp -= self.ramp.clamp(-step, step);
+                References::velocity(self.ramp)
+            }
+            _ => references,
+        }
+    }
+
+    // Voltage that reaches the motor, the power stage is switched off after a trip.
+    pub fn actuate(&self, motor: &mut Motor, voltage: f64) -> f64{
+        match self.state{
+            DriveState::Running => voltage,
+            DriveState::Stopping(_) if self.quick_stop_available() => voltage,
+            DriveState::Stopping(_) if self.config.reaction == Reaction::Coast => {
+                *motor.set_open_circuit() = true;
+                0.0
+            }
+            DriveState::Stopping(_) => 0.0,
+            DriveState::Faulted(_) => {
+                *motor.set_open_circuit() = true;
+                0.0
+            }
+        }
+    }
+}
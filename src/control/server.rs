@@ -0,0 +1,249 @@
+use std::{net::{SocketAddr, UdpSocket}, sync::{Arc, Mutex}, thread, time::Duration};
+
+use super::{time_mod::Time, Config, Motor};
+
+// Packets are little endian, see the README.
+pub const PACKET_STEP: u8 = 0x01;
+pub const PACKET_RESET: u8 = 0x02;
+pub const PACKET_STATE: u8 = 0x81;
+// kind, sequence, voltage V, step sec
+pub const COMMAND_LEN: usize = 1 + 4 + 8 + 8;
+// kind, sequence, time sec, position deg, velocity rpm, current A, unwrapped position deg
+pub const STATE_LEN: usize = 1 + 4 + 5*8;
+// how often a waiting server checks the stop flag
+const POLL: Duration = Duration::from_millis(100);
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ServerMode{
+    // the plant advances by the step of every command
+    LockStep,
+    // the plant runs on the wall clock at the controller frequency, commands set the voltage
+    RealTime,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConfigServer{
+    port: u16,
+    mode: ServerMode,
+}
+
+// Shared between the server thread and the UI.
+pub struct ServerStatus{
+    running: bool,
+    stop_flag: bool,
+    error: Option<String>,
+    client: Option<SocketAddr>,
+    commands: usize,
+    invalid: usize,
+    time: f64,
+    voltage: f64,
+    position: f64,
+    velocity: f64,
+    current: f64,
+}
+
+impl Default for ConfigServer{
+    fn default() -> Self {
+        Self{port: 5005, mode: ServerMode::LockStep}
+    }
+}
+
+impl ServerMode{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            ServerMode::LockStep => "Lock-step",
+            ServerMode::RealTime => "Real time",
+        }
+    }
+}
+
+impl ConfigServer{
+    pub fn set_port(&mut self) -> &mut u16{
+        &mut self.port
+    }
+
+    pub fn set_mode(&mut self) -> &mut ServerMode{
+        &mut self.mode
+    }
+}
+
+impl ServerStatus{
+    pub fn get_running(&self) -> bool{
+        self.running
+    }
+
+    pub fn set_stop_flag(&mut self) -> &mut bool{
+        &mut self.stop_flag
+    }
+
+    pub fn get_error(&self) -> &Option<String>{
+        &self.error
+    }
+
+    pub fn get_client(&self) -> Option<SocketAddr>{
+        self.client
+    }
+
+    pub fn get_commands(&self) -> usize{
+        self.commands
+    }
+
+    pub fn get_invalid(&self) -> usize{
+        self.invalid
+    }
+
+    pub fn get_time(&self) -> f64{
+        self.time
+    }
+
+    pub fn get_voltage(&self) -> f64{
+        self.voltage
+    }
+
+    pub fn get_position(&self) -> f64{
+        self.position
+    }
+
+    pub fn get_velocity(&self) -> f64{
+        self.velocity
+    }
+
+    pub fn get_current(&self) -> f64{
+        self.current
+    }
+}
+
+enum Command{
+    Step{sequence: u32, voltage: f64, step: f64},
+    Reset{sequence: u32},
+}
+
+fn parse(packet: &[u8]) -> Option<Command>{
+    if packet.len() != COMMAND_LEN{
+        return None;
+    }
+    let sequence = u32::from_le_bytes(packet[1..5].try_into().unwrap());
+    let voltage = f64::from_le_bytes(packet[5..13].try_into().unwrap());
+    let step = f64::from_le_bytes(packet[13..21].try_into().unwrap());
+    match packet[0]{
+        PACKET_STEP if voltage.is_finite() && step.is_finite() && step >= 0.0 => Some(Command::Step{sequence, voltage, step}),
+        PACKET_RESET => Some(Command::Reset{sequence}),
+        _ => None,
+    }
+}
+
+fn state_packet(sequence: u32, time: f64, motor: &Motor) -> [u8; STATE_LEN]{
+    let mut packet = [0; STATE_LEN];
+    packet[0] = PACKET_STATE;
+    packet[1..5].copy_from_slice(&sequence.to_le_bytes());
+    let current = motor.get_torque()/motor.get_config().get_k();
+    // the unwrapped angle comes last, for clients tracking several turns
+    for (index, value) in [time, motor.get_position(), motor.get_velocity(), current, motor.get_unwrapped_position()].iter().enumerate(){
+        packet[5 + 8*index..13 + 8*index].copy_from_slice(&value.to_le_bytes());
+    }
+    packet
+}
+
+struct Plant{
+    motor: Motor,
+    config: Config,
+    status: Arc<Mutex<ServerStatus>>,
+    voltage: f64,
+    time: f64,
+}
+
+impl Plant{
+    // Applies a command and answers it, returns false for an invalid packet.
+    fn handle(&mut self, socket: &UdpSocket, packet: &[u8], client: SocketAddr, lock_step: bool) -> bool{
+        let sequence = match parse(packet){
+            Some(Command::Step{sequence, voltage, step}) => {
+                self.voltage = voltage;
+                if lock_step{
+                    // a zero step advances by the controller period
+                    let step = if step > 0.0 {step} else {1.0/self.config.get_controller_conf().get_frequency()};
+                    self.motor.update_state(step, voltage);
+                    self.time += step;
+                }
+                sequence
+            }
+            Some(Command::Reset{sequence}) => {
+                self.motor.reset(*self.config.get_motor_conf());
+                self.voltage = 0.0;
+                self.time = 0.0;
+                sequence
+            }
+            None => return false,
+        };
+        // a client that went away is not an error of the plant
+        let _ = socket.send_to(&state_packet(sequence, self.time, &self.motor), client);
+        let mut status = self.status.lock().unwrap();
+        status.client = Some(client);
+        status.commands += 1;
+        true
+    }
+
+    fn publish(&self){
+        let current = self.motor.get_torque()/self.motor.get_config().get_k();
+        let mut status = self.status.lock().unwrap();
+        status.time = self.time;
+        status.voltage = self.voltage;
+        status.position = self.motor.get_position();
+        status.velocity = self.motor.get_velocity();
+        status.current = current;
+    }
+}
+
+// Serves the motor of the config on 127.0.0.1:port in its own thread until the stop flag is set.
+pub fn run(config: Config, server: ConfigServer) -> Arc<Mutex<ServerStatus>>{
+    let status = Arc::new(Mutex::new(ServerStatus{running: true, stop_flag: false, error: None, client: None, commands: 0, invalid: 0,
+        time: 0.0, voltage: 0.0, position: 0.0, velocity: 0.0, current: 0.0}));
+    let shared = Arc::clone(&status);
+
+    thread::spawn(move ||{
+        let socket = match UdpSocket::bind(("127.0.0.1", server.port)){
+            Ok(socket) => socket,
+            Err(error) => {
+                let mut status = shared.lock().unwrap();
+                status.error = Some(error.to_string());
+                status.running = false;
+                return;
+            }
+        };
+        let mut plant = Plant{motor: Motor::new(*config.get_motor_conf()), config, status: Arc::clone(&shared), voltage: 0.0, time: 0.0};
+        plant.publish();
+        let mut buffer = [0; 64];
+
+        match server.mode{
+            ServerMode::LockStep => {
+                socket.set_read_timeout(Some(POLL)).unwrap();
+                while !shared.lock().unwrap().stop_flag{
+                    // timeouts only wake the loop up
+                    if let Ok((len, client)) = socket.recv_from(&mut buffer){
+                        if !plant.handle(&socket, &buffer[..len], client, true){
+                            shared.lock().unwrap().invalid += 1;
+                        }
+                        plant.publish();
+                    }
+                }
+            }
+            ServerMode::RealTime => {
+                socket.set_nonblocking(true).unwrap();
+                let mut time = Time::new(config.get_controller_conf().get_frequency());
+                while !shared.lock().unwrap().stop_flag{
+                    time.update_state_sleeping();
+                    while let Ok((len, client)) = socket.recv_from(&mut buffer){
+                        if !plant.handle(&socket, &buffer[..len], client, false){
+                            shared.lock().unwrap().invalid += 1;
+                        }
+                    }
+                    let delta = time.get_delta();
+                    plant.motor.update_state(delta, plant.voltage);
+                    plant.time += delta;
+                    plant.publish();
+                }
+            }
+        }
+        shared.lock().unwrap().running = false;
+    });
+    status
+}
@@ -0,0 +1,421 @@
+use std::{sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}}, thread};
+
+use super::{simulate, Config, TypePid, law::LawRegistry, metrics::StepMetrics};
+
+const MOTOR_PARAMETERS: [&str; 6] = ["j", "b", "k", "r", "l", "tl"];
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum SweepParameter{
+    // loop, gain as kp, ki, kd
+    Gain(TypePid, usize),
+    // index of MOTOR_PARAMETERS
+    Motor(usize),
+    VltgBound,
+    TrqBound,
+    VelBound,
+    Frequency,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum SweepMetric{
+    Overshoot,
+    RiseTime,
+    SettlingTime,
+    SsError,
+    Iae,
+    Ise,
+    Itae,
+    PeakEffort,
+}
+
+pub const SWEEP_METRICS: [SweepMetric; 8] = [SweepMetric::Overshoot, SweepMetric::RiseTime, SweepMetric::SettlingTime, SweepMetric::SsError,
+    SweepMetric::Iae, SweepMetric::Ise, SweepMetric::Itae, SweepMetric::PeakEffort];
+
+// Values are spaced linearly from start to end, both included.
+#[derive(Copy, Clone)]
+pub struct ConfigAxis{
+    parameter: SweepParameter,
+    start: f64,
+    end: f64,
+    points: usize,
+}
+
+#[derive(Copy, Clone)]
+pub struct ConfigSweep{
+    axes: [ConfigAxis; 2],
+    two_dimensional: bool,
+    threads: usize,
+}
+
+#[derive(Copy, Clone)]
+pub struct SweepCase{
+    values: [f64; 2],
+    metrics: Option<StepMetrics>,
+    // why the case was not simulated
+    error: Option<&'static str>,
+}
+
+pub struct SweepProgress{
+    sweep: ConfigSweep,
+    // ordered by the second axis, then the first one
+    cases: Vec<Option<SweepCase>>,
+    done: usize,
+    workers: usize,
+    stop_flag: bool,
+}
+
+impl Default for ConfigAxis{
+    fn default() -> Self {
+        Self{parameter: SweepParameter::Gain(TypePid::Pos, 0), start: 10.0, end: 100.0, points: 10}
+    }
+}
+
+impl Default for ConfigSweep{
+    fn default() -> Self {
+        Self{axes: [ConfigAxis::default(), ConfigAxis{parameter: SweepParameter::Gain(TypePid::Pos, 2), start: 0.0, end: 3.0, points: 10}],
+            two_dimensional: false, threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(4)}
+    }
+}
+
+impl SweepParameter{
+    pub fn all() -> Vec<SweepParameter>{
+        let mut parameters = vec![];
+        for pid in [TypePid::Pos, TypePid::Vel, TypePid::Trq]{
+            for gain in 0..3{
+                parameters.push(SweepParameter::Gain(pid, gain));
+            }
+        }
+        for index in 0..MOTOR_PARAMETERS.len(){
+            parameters.push(SweepParameter::Motor(index));
+        }
+        parameters.extend([SweepParameter::VltgBound, SweepParameter::TrqBound, SweepParameter::VelBound, SweepParameter::Frequency]);
+        parameters
+    }
+
+    pub fn get_name(&self) -> String{
+        match self{
+            SweepParameter::Gain(pid, gain) => {
+                let pid = match pid{
+                    TypePid::Pos => "Angle",
+                    TypePid::Vel => "Speed",
+                    TypePid::Trq => "Torque",
+                };
+                format!("{} {}", pid, ["Kp", "Ki", "Kd"][*gain])
+            }
+            SweepParameter::Motor(index) => format!("Motor {}", MOTOR_PARAMETERS[*index]),
+            SweepParameter::VltgBound => "Voltage bound".to_string(),
+            SweepParameter::TrqBound => "Torque bound".to_string(),
+            SweepParameter::VelBound => "Velocity bound".to_string(),
+            SweepParameter::Frequency => "Frequency".to_string(),
+        }
+    }
+
+    pub fn apply(&self, config: &mut Config, value: f64){
+        match self{
+            SweepParameter::Gain(pid, gain) => {
+                let pid_conf = &mut config.set_pid_conf()[*pid as usize];
+                match gain{
+                    0 => *pid_conf.set_kp() = value,
+                    1 => *pid_conf.set_ki() = value,
+                    _ => *pid_conf.set_kd() = value,
+                }
+            }
+            SweepParameter::Motor(index) => {
+                let motor = config.set_motor_conf();
+                match index{
+                    0 => *motor.set_j() = value,
+                    1 => *motor.set_b() = value,
+                    2 => *motor.set_k() = value,
+                    3 => *motor.set_r() = value,
+                    4 => *motor.set_l() = value,
+                    _ => *motor.set_tl() = value,
+                }
+            }
+            SweepParameter::VltgBound => *config.set_controller_conf().set_vltg_bound() = value,
+            SweepParameter::TrqBound => *config.set_controller_conf().set_trq_bound() = value,
+            SweepParameter::VelBound => *config.set_controller_conf().set_vel_bound() = value,
+            SweepParameter::Frequency => *config.set_controller_conf().set_frequency() = value,
+        }
+    }
+}
+
+impl SweepMetric{
+    pub fn get_name(&self) -> &'static str{
+        match self{
+            SweepMetric::Overshoot => "Overshoot, %",
+            SweepMetric::RiseTime => "Rise time, sec",
+            SweepMetric::SettlingTime => "Settling time, sec",
+            SweepMetric::SsError => "Steady state error",
+            SweepMetric::Iae => "IAE",
+            SweepMetric::Ise => "ISE",
+            SweepMetric::Itae => "ITAE",
+            SweepMetric::PeakEffort => "Peak voltage, V",
+        }
+    }
+
+    pub fn get_value(&self, metrics: &StepMetrics) -> Option<f64>{
+        match self{
+            SweepMetric::Overshoot => Some(metrics.get_overshoot()),
+            SweepMetric::RiseTime => metrics.get_rise_time(),
+            SweepMetric::SettlingTime => metrics.get_settling_time(),
+            SweepMetric::SsError => Some(metrics.get_ss_error()),
+            SweepMetric::Iae => Some(metrics.get_iae()),
+            SweepMetric::Ise => Some(metrics.get_ise()),
+            SweepMetric::Itae => Some(metrics.get_itae()),
+            SweepMetric::PeakEffort => Some(metrics.get_peak_effort()),
+        }
+    }
+}
+
+impl ConfigAxis{
+    pub fn set_parameter(&mut self) -> &mut SweepParameter{
+        &mut self.parameter
+    }
+
+    pub fn get_parameter(&self) -> SweepParameter{
+        self.parameter
+    }
+
+    pub fn set_start(&mut self) -> &mut f64{
+        &mut self.start
+    }
+
+    pub fn set_end(&mut self) -> &mut f64{
+        &mut self.end
+    }
+
+    pub fn set_points(&mut self) -> &mut usize{
+        &mut self.points
+    }
+
+    pub fn get_points(&self) -> usize{
+        self.points.max(1)
+    }
+
+    pub fn get_value(&self, index: usize) -> f64{
+        if self.get_points() == 1 {self.start} else {self.start + (self.end - self.start)*index as f64/(self.points - 1) as f64}
+    }
+
+    // distance between two neighbouring values
+    pub fn get_step(&self) -> f64{
+        if self.get_points() == 1 {1.0} else {(self.end - self.start)/(self.points - 1) as f64}
+    }
+}
+
+impl ConfigSweep{
+    pub fn set_axes(&mut self) -> &mut [ConfigAxis; 2]{
+        &mut self.axes
+    }
+
+    pub fn get_axes(&self) -> &[ConfigAxis; 2]{
+        &self.axes
+    }
+
+    pub fn set_two_dimensional(&mut self) -> &mut bool{
+        &mut self.two_dimensional
+    }
+
+    pub fn get_two_dimensional(&self) -> bool{
+        self.two_dimensional
+    }
+
+    pub fn set_threads(&mut self) -> &mut usize{
+        &mut self.threads
+    }
+
+    pub fn get_cases(&self) -> usize{
+        self.axes[0].get_points()*if self.two_dimensional {self.axes[1].get_points()} else {1}
+    }
+
+    // axis values of a case index
+    pub fn get_values(&self, index: usize) -> [f64; 2]{
+        let first = self.axes[0].get_points();
+        let second = if self.two_dimensional {self.axes[1].get_value(index/first)} else {f64::NAN};
+        [self.axes[0].get_value(index%first), second]
+    }
+}
+
+impl SweepCase{
+    pub fn get_values(&self) -> [f64; 2]{
+        self.values
+    }
+
+    pub fn get_metrics(&self) -> Option<StepMetrics>{
+        self.metrics
+    }
+
+    pub fn get_metric(&self, metric: SweepMetric) -> Option<f64>{
+        self.metrics.and_then(|metrics| metric.get_value(&metrics))
+    }
+
+    pub fn get_error(&self) -> Option<&'static str>{
+        self.error
+    }
+}
+
+impl SweepProgress{
+    pub fn get_sweep(&self) -> &ConfigSweep{
+        &self.sweep
+    }
+
+    pub fn get_cases(&self) -> &Vec<Option<SweepCase>>{
+        &self.cases
+    }
+
+    pub fn get_done(&self) -> usize{
+        self.done
+    }
+
+    pub fn get_running(&self) -> bool{
+        self.workers > 0
+    }
+
+    pub fn set_stop_flag(&mut self) -> &mut bool{
+        &mut self.stop_flag
+    }
+
+    // One row per finished case, empty fields for undefined metrics.
+    pub fn to_csv(&self) -> String{
+        let mut header: Vec<String> = vec![self.sweep.axes[0].parameter.get_name()];
+        if self.sweep.two_dimensional{
+            header.push(self.sweep.axes[1].parameter.get_name());
+        }
+        header.extend(SWEEP_METRICS.iter().map(|metric| metric.get_name().to_string()));
+        header.push("Invalid".to_string());
+
+        // names contain commas
+        let header: Vec<String> = header.iter().map(|name| format!("\"{}\"", name)).collect();
+        let mut csv = header.join(",") + "\n";
+        for case in self.cases.iter().flatten(){
+            let mut row = vec![case.values[0].to_string()];
+            if self.sweep.two_dimensional{
+                row.push(case.values[1].to_string());
+            }
+            row.extend(SWEEP_METRICS.iter().map(|metric| case.get_metric(*metric).map_or(String::new(), |value| value.to_string())));
+            row.push(case.error.map_or(String::new(), |error| format!("\"{}\"", error)));
+            csv += &(row.join(",") + "\n");
+        }
+        csv
+    }
+}
+
+// Parameters the model and the controller can not run with, as a zero inductance or a negative bound.
+fn check(config: &Config) -> Result<(), &'static str>{
+    let motor = config.get_motor_conf();
+    let controller = config.get_controller_conf();
+    let values = [motor.get_j(), motor.get_b(), motor.get_k(), motor.get_r(), motor.get_l(), motor.get_tl(),
+        controller.vltg_bound, controller.vel_bound, controller.trq_bound, controller.frequency];
+    if !values.iter().all(|value| value.is_finite()){
+        return Err("not a finite value");
+    }
+    if motor.get_j() <= 0.0 || motor.get_l() <= 0.0{
+        return Err("j and l must be positive");
+    }
+    if motor.get_b() < 0.0 || motor.get_r() < 0.0 || motor.get_b()*motor.get_r() + motor.get_k().powi(2) <= 0.0{
+        return Err("b and r must not be negative, with b*r + k^2 positive");
+    }
+    if controller.vltg_bound < 0.0 || controller.vel_bound < 0.0 || controller.trq_bound < 0.0{
+        return Err("bounds must not be negative");
+    }
+    if controller.frequency <= 0.0{
+        return Err("frequency must be positive");
+    }
+    Ok(())
+}
+
+// Every case is the calibration of the config with the swept values applied,
+// cases are taken from a shared counter by sweep.threads worker threads.
+pub fn run(config: Config, sweep: ConfigSweep, registry: Arc<LawRegistry>) -> Arc<Mutex<SweepProgress>>{
+    let total = sweep.get_cases();
+    let workers = sweep.threads.clamp(1, total);
+    let progress = Arc::new(Mutex::new(SweepProgress{sweep, cases: vec![None; total], done: 0, workers, stop_flag: false}));
+    let next = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..workers{
+        let shared = Arc::clone(&progress);
+        let next = Arc::clone(&next);
+        let registry = Arc::clone(&registry);
+        thread::spawn(move ||{
+            loop{
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= total || shared.lock().unwrap().stop_flag{
+                    break;
+                }
+                let values = sweep.get_values(index);
+                let mut config = config;
+                sweep.axes[0].parameter.apply(&mut config, values[0]);
+                if sweep.two_dimensional{
+                    sweep.axes[1].parameter.apply(&mut config, values[1]);
+                }
+                let error = check(&config).err();
+                let metrics = if error.is_none() {simulate(config, Arc::clone(&registry)).get_metrics()} else {None};
+
+                let mut progress = shared.lock().unwrap();
+                progress.cases[index] = Some(SweepCase{values, metrics, error});
+                progress.done += 1;
+            }
+            shared.lock().unwrap().workers -= 1;
+        });
+    }
+    progress
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn grid(){
+        let mut sweep = ConfigSweep{axes: [ConfigAxis{parameter: SweepParameter::VltgBound, start: 1.0, end: 3.0, points: 3},
+            ConfigAxis{parameter: SweepParameter::Frequency, start: 100.0, end: 200.0, points: 2}], ..Default::default()};
+        assert_eq!(sweep.get_cases(), 3);
+        assert!(sweep.get_values(2)[1].is_nan());
+        sweep.two_dimensional = true;
+        assert_eq!(sweep.get_cases(), 6);
+        assert_eq!(sweep.get_values(4), [2.0, 200.0]);
+        assert_eq!(sweep.axes[0].get_step(), 1.0);
+        let single = ConfigAxis{points: 0, ..sweep.axes[0]};
+        assert_eq!((single.get_points(), single.get_value(0)), (1, 1.0));
+        assert_eq!(SweepParameter::all().len(), 19);
+    }
+
+    #[test]
+    fn invalid_parameters(){
+        assert!(check(&Config::default()).is_ok());
+        let cases = [(SweepParameter::Motor(4), 0.0, "j and l must be positive"),
+            (SweepParameter::Motor(3), -1.0, "b and r must not be negative, with b*r + k^2 positive"),
+            (SweepParameter::TrqBound, -1.0, "bounds must not be negative"),
+            (SweepParameter::Frequency, 0.0, "frequency must be positive"),
+            (SweepParameter::Gain(TypePid::Pos, 0), f64::NAN, "not a finite value")];
+        for (parameter, value, error) in cases{
+            let mut config = Config::default();
+            parameter.apply(&mut config, value);
+            // gains are not checked, the controller runs with any finite value
+            let expected = if let SweepParameter::Gain(..) = parameter {Ok(())} else {Err(error)};
+            assert_eq!(check(&config), expected, "{}", parameter.get_name());
+        }
+    }
+
+    #[test]
+    fn runs_every_case(){
+        let mut config = Config::default();
+        *config.set_controller_conf().set_duration() = 0.2;
+        let mut sweep = ConfigSweep{threads: 2, ..Default::default()};
+        sweep.axes[0] = ConfigAxis{parameter: SweepParameter::VltgBound, start: -12.0, end: 12.0, points: 3};
+        let progress = run(config, sweep, Arc::new(LawRegistry::default()));
+        while progress.lock().unwrap().get_running(){
+            thread::sleep(Duration::from_millis(10));
+        }
+        let progress = progress.lock().unwrap();
+        assert_eq!(progress.get_done(), 3);
+        let cases: Vec<SweepCase> = progress.get_cases().iter().map(|case| case.unwrap()).collect();
+        assert_eq!(cases[0].get_error(), Some("bounds must not be negative"));
+        assert!(cases[0].get_metrics().is_none());
+        assert!(cases[1..].iter().all(|case| case.get_error().is_none() && case.get_metrics().is_some()));
+        assert_eq!(cases[2].get_values()[0], 12.0);
+        let csv = progress.to_csv();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.lines().nth(1).unwrap().starts_with("-12,") && csv.lines().nth(1).unwrap().ends_with(",\"bounds must not be negative\""));
+    }
+}
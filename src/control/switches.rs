@@ -0,0 +1,82 @@
+// Positions in deg of the unwrapped motor angle.
+#[derive(Copy, Clone)]
+pub struct ConfigSwitches{
+    // active at and below
+    negative_limit: f64,
+    // active at and above
+    positive_limit: f64,
+    // home cam, active at and above
+    home: f64,
+    // one index pulse per revolution at this angle
+    index: f64,
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct SwitchInputs{
+    negative_limit: bool,
+    positive_limit: bool,
+    home: bool,
+    // true for the period the index angle was passed in
+    index: bool,
+}
+
+pub struct Switches{
+    config: ConfigSwitches,
+    prev_position: Option<f64>,
+}
+
+impl Default for ConfigSwitches{
+    fn default() -> Self {
+        Self{negative_limit: -720.0, positive_limit: 720.0, home: 90.0, index: 0.0}
+    }
+}
+
+impl ConfigSwitches{
+    pub fn set_negative_limit(&mut self) -> &mut f64{
+        &mut self.negative_limit
+    }
+
+    pub fn set_positive_limit(&mut self) -> &mut f64{
+        &mut self.positive_limit
+    }
+
+    pub fn set_home(&mut self) -> &mut f64{
+        &mut self.home
+    }
+
+    pub fn set_index(&mut self) -> &mut f64{
+        &mut self.index
+    }
+}
+
+impl SwitchInputs{
+    pub fn get_negative_limit(&self) -> bool{
+        self.negative_limit
+    }
+
+    pub fn get_positive_limit(&self) -> bool{
+        self.positive_limit
+    }
+
+    pub fn get_home(&self) -> bool{
+        self.home
+    }
+
+    pub fn get_index(&self) -> bool{
+        self.index
+    }
+}
+
+impl Switches{
+    pub fn new(config: ConfigSwitches) -> Self{
+        Self{config, prev_position: None}
+    }
+
+    pub fn update(&mut self, position: f64) -> SwitchInputs{
+        let revolution = |position: f64| ((position - self.config.index)/360.0).floor();
+        let index = self.prev_position.is_some_and(|prev| revolution(prev) != revolution(position));
+        self.prev_position = Some(position);
+        SwitchInputs{negative_limit: position <= self.config.negative_limit, positive_limit: position >= self.config.positive_limit,
+            home: position >= self.config.home, index}
+    }
+}
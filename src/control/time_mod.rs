@@ -1,11 +1,17 @@
-use std::{time::{Instant, Duration}};
+use std::{thread, time::{Instant, Duration}};
+
+// sec before the end of a period a sleeping wait starts to spin, covers the oversleep of the scheduler
+const SPIN: f64 = 0.0005;
 
 pub struct Time{
     prev_state: Option<Duration>, 
     state: Duration,
     zero_time: Duration,
     instant:Instant,
-    time_period: f64
+    time_period: f64,
+    // deviation of the next period, part of the period
+    jitter: f64,
+    headless: bool
 
 }
 
@@ -14,18 +20,58 @@ impl Time {
         let instant = Instant::now();
         let zero_time = instant.elapsed();
         let state = zero_time;
-        Self {zero_time, prev_state: None, state, instant, time_period: 1./frquency}
+        Self {zero_time, prev_state: None, state, instant, time_period: 1./frquency, jitter: 0.0, headless: false}
+    }
+
+    // Virtual clock advancing exactly one period per update, used for simulations without the UI.
+    pub fn new_headless(frquency: f64) -> Self{
+        Self {headless: true, ..Time::new(frquency)}
     }
 
     pub fn update_state(&mut self){
         self.prev_state = Some(self.state);
+        let period = self.time_period*(1.0 + self.jitter);
+        if self.headless{
+            self.state += Duration::from_secs_f64(period);
+            return;
+        }
         self.state = self.instant.elapsed();
-        while (self.state-self.prev_state.unwrap()).as_secs_f64() < self.time_period{
+        while (self.state-self.prev_state.unwrap()).as_secs_f64() < period{
             self.state = self.instant.elapsed();
         }
         self.state = self.instant.elapsed();
     }
 
+    // As update_state, sleeps through the period and only spins its end, for threads that need not be exact.
+    pub fn update_state_sleeping(&mut self){
+        if !self.headless{
+            let period = self.time_period*(1.0 + self.jitter);
+            let remaining = period - (self.instant.elapsed() - self.state).as_secs_f64() - SPIN;
+            if remaining > 0.0{
+                thread::sleep(Duration::from_secs_f64(remaining));
+            }
+        }
+        self.update_state();
+    }
+
+    // The next period starts now, the time from start goes on without the pause.
+    pub fn restart_period(&mut self){
+        if self.headless{
+            return;
+        }
+        let now = self.instant.elapsed();
+        self.zero_time += now - self.state;
+        self.state = now;
+    }
+
+    pub fn set_jitter(&mut self) -> &mut f64{
+        &mut self.jitter
+    }
+
+    pub fn get_period(&self) -> f64{
+        self.time_period
+    }
+
     pub fn get_delta(&self) -> f64{
         (self.state-self.prev_state.unwrap()).as_secs_f64()
     }
@@ -0,0 +1,212 @@
+use std::collections::VecDeque;
+
+use super::{law::Measurements, math::Rng, montecarlo::Distribution};
+
+// periods kept for the statistics
+const STATS_LEN: usize = 10000;
+// a period longer than the nominal one by this part of it is an overrun
+const OVERRUN_MARGIN: f64 = 0.5;
+
+// Embedded timing of the controller, delays are counted in control periods.
+#[derive(Copy, Clone)]
+pub struct ConfigTiming{
+    enabled: bool,
+    // from sampling to the new voltage at the motor, may be fractional
+    computation_delay: f64,
+    // age of the sensor readings
+    sensor_delay: usize,
+    // relative to the period, half width of the uniform or 3 sigma of the normal
+    jitter: f64,
+    distribution: Distribution,
+    seed: u64,
+}
+
+// Achieved timing of the controller thread, shared with the UI. Times in sec, step times are wall clock
+// from the end of the wait to the end of the step, preemption of the thread included.
+pub struct TimingStats{
+    nominal: f64,
+    steps: usize,
+    overruns: usize,
+    max_period: f64,
+    max_step_time: f64,
+    // the last STATS_LEN steps
+    periods: VecDeque<f64>,
+    step_times: VecDeque<f64>,
+}
+
+pub struct TimingEmulator{
+    config: ConfigTiming,
+    rng: Rng,
+    // newest first
+    inputs: VecDeque<f64>,
+    measurements: VecDeque<Measurements>,
+    // voltage the power stage applied after the split of the previous period
+    held: f64,
+}
+
+impl Default for ConfigTiming{
+    fn default() -> Self {
+        Self{enabled: false, computation_delay: 0.5, sensor_delay: 0, jitter: 0.05, distribution: Distribution::Uniform, seed: 1}
+    }
+}
+
+impl Default for TimingStats{
+    fn default() -> Self {
+        Self{nominal: 0.0, steps: 0, overruns: 0, max_period: 0.0, max_step_time: 0.0, periods: VecDeque::new(), step_times: VecDeque::new()}
+    }
+}
+
+impl ConfigTiming{
+    pub fn set_enabled(&mut self) -> &mut bool{
+        &mut self.enabled
+    }
+
+    pub fn get_enabled(&self) -> bool{
+        self.enabled
+    }
+
+    pub fn set_computation_delay(&mut self) -> &mut f64{
+        &mut self.computation_delay
+    }
+
+    pub fn set_sensor_delay(&mut self) -> &mut usize{
+        &mut self.sensor_delay
+    }
+
+    pub fn set_jitter(&mut self) -> &mut f64{
+        &mut self.jitter
+    }
+
+    pub fn set_distribution(&mut self) -> &mut Distribution{
+        &mut self.distribution
+    }
+
+    pub fn set_seed(&mut self) -> &mut u64{
+        &mut self.seed
+    }
+}
+
+impl TimingStats{
+    pub fn reset(&mut self, nominal: f64){
+        *self = TimingStats{nominal, ..Default::default()};
+    }
+
+    // Period since the previous step and wall time of this one.
+    pub fn record(&mut self, period: f64, step_time: f64){
+        self.steps += 1;
+        if period > self.nominal*(1.0 + OVERRUN_MARGIN){
+            self.overruns += 1;
+        }
+        self.max_period = self.max_period.max(period);
+        self.max_step_time = self.max_step_time.max(step_time);
+        self.periods.push_back(period);
+        self.step_times.push_back(step_time);
+        if self.periods.len() > STATS_LEN{
+            self.periods.pop_front();
+            self.step_times.pop_front();
+        }
+    }
+
+    pub fn get_nominal(&self) -> f64{
+        self.nominal
+    }
+
+    pub fn get_steps(&self) -> usize{
+        self.steps
+    }
+
+    pub fn get_overruns(&self) -> usize{
+        self.overruns
+    }
+
+    pub fn get_max_period(&self) -> f64{
+        self.max_period
+    }
+
+    pub fn get_max_step_time(&self) -> f64{
+        self.max_step_time
+    }
+
+    pub fn get_periods(&self) -> Vec<f64>{
+        self.periods.iter().copied().collect()
+    }
+
+    // over the kept steps
+    pub fn get_mean_period(&self) -> f64{
+        self.periods.iter().sum::<f64>()/self.periods.len().max(1) as f64
+    }
+
+    pub fn get_mean_step_time(&self) -> f64{
+        self.step_times.iter().sum::<f64>()/self.step_times.len().max(1) as f64
+    }
+
+    // standard deviation of the period
+    pub fn get_jitter(&self) -> f64{
+        let mean = self.get_mean_period();
+        (self.periods.iter().map(|period| (period - mean).powi(2)).sum::<f64>()/self.periods.len().max(1) as f64).sqrt()
+    }
+
+    // hz
+    pub fn get_rate(&self) -> f64{
+        let mean = self.get_mean_period();
+        if mean > 0.0 {1.0/mean} else {0.0}
+    }
+}
+
+impl TimingEmulator{
+    pub fn new(config: ConfigTiming) -> Self{
+        Self{config, rng: Rng::new(config.seed), inputs: VecDeque::new(), measurements: VecDeque::new(), held: 0.0}
+    }
+
+    pub fn get_enabled(&self) -> bool{
+        self.config.enabled
+    }
+
+    // Deviation of the next sampling instant from the nominal one, part of the period.
+    pub fn next_jitter(&mut self) -> f64{
+        if !self.config.enabled{
+            return 0.0;
+        }
+        let deviation = match self.config.distribution{
+            Distribution::Uniform => self.config.jitter*(2.0*self.rng.uniform() - 1.0),
+            Distribution::Normal => self.config.jitter/3.0*self.rng.normal(),
+        };
+        // sampling instants stay ordered
+        deviation.max(-0.9)
+    }
+
+    // Readings sensor_delay periods old, the first ones until the buffer is filled.
+    pub fn measure(&mut self, measurements: Measurements) -> Measurements{
+        if !self.config.enabled{
+            return measurements;
+        }
+        self.measurements.push_front(measurements);
+        self.measurements.truncate(self.config.sensor_delay + 1);
+        *self.measurements.back().unwrap()
+    }
+
+    // Part of the period the previous voltage is still applied, the whole delay is split into periods and this part.
+    pub fn get_split(&self) -> f64{
+        if self.config.enabled {self.config.computation_delay.max(0.0).fract()} else {0.0}
+    }
+
+    // Commanded voltage that reaches the power stage after the split.
+    pub fn actuate(&mut self, input: f64) -> f64{
+        if !self.config.enabled{
+            return input;
+        }
+        let periods = self.config.computation_delay.max(0.0) as usize;
+        self.inputs.push_front(input);
+        self.inputs.truncate(periods + 1);
+        self.inputs.get(periods).copied().unwrap_or(0.0)
+    }
+
+    // Voltage at the motor before the split, set to the applied one at the end of every period.
+    pub fn get_held(&self) -> f64{
+        self.held
+    }
+
+    pub fn set_held(&mut self) -> &mut f64{
+        &mut self.held
+    }
+}
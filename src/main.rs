@@ -1,19 +1,36 @@
+pub mod analysis;
 pub mod control;
 pub mod ui;
-use std::{thread, time::{Duration}, sync::{mpsc::{self, Sender, Receiver}}};
-use control::{Controller, Config};
+use std::{thread, time::{Duration}, sync::{Arc, Mutex, mpsc::{self, Sender, Receiver}}};
+use control::{Controller, Config, law::LawRegistry, external::{ExternalLaw, ExternalStatus, LiveStatus}};
 
 use crate::ui::Motorsim;
 
 fn main() {
     let (tx, rx): (Sender<Config>, Receiver<Config>) = mpsc::channel();
 
-    let motorsim = Motorsim::new(tx.clone());
+    let mut registry = LawRegistry::default();
+    let external: LiveStatus = Arc::new(Mutex::new(Arc::new(Mutex::new(ExternalStatus::default()))));
+    let live = Arc::clone(&external);
+    registry.register_live("External", move |config|{
+        let law = ExternalLaw::new(config);
+        *live.lock().unwrap() = law.get_status();
+        Box::new(law)
+    });
+    // Custom control laws implementing control::law::ControlLaw are registered here, e.g.
+    // registry.register("Sliding mode", |config| Box::new(SlidingMode::new(config)));
+    let registry = Arc::new(registry);
+
+    let motorsim = Motorsim::new(tx.clone(), Arc::clone(&registry), external);
     let plotpoints = motorsim.get_plotpoints();
     let target = motorsim.get_target();
+    let protection = motorsim.get_protection();
+    let drive_objects = motorsim.get_drive_objects();
+    let timing_stats = motorsim.get_timing_stats();
 
     let thread = thread::spawn(move || {
-        let mut controller = Controller::new(rx.recv().unwrap(), plotpoints, target);
+        let mut controller = Controller::new(rx.recv().unwrap(), plotpoints, target, protection, drive_objects, timing_stats, registry);
+        let mut running = false;
 
         loop{
             match rx.try_recv(){
@@ -25,8 +42,13 @@ fn main() {
 
             if !controller.get_controller_conf().get_end_flag(){
                 if *(controller.get_controller_conf().get_start_flag()){
+                    if !running{
+                        controller.start_run();
+                        running = true;
+                    }
                     controller.calculate_point();
                 } else {
+                    running = false;
                     thread::sleep(Duration::from_millis(100));
                 }
             } else {
@@ -1,3 +1,27 @@
+mod autotune;
+mod bode;
+mod cache;
+mod cia402;
+mod codegen;
+mod external;
+mod faults;
+mod fixed;
+mod ident;
+mod limits;
+mod measurement;
+mod metrics;
+mod modbus;
+mod montecarlo;
+mod nyquist;
+mod optimizer;
+mod pole_zero;
+mod protection;
+mod server;
+mod sweep;
+mod timing;
+mod timing_stats;
+mod wizard;
+
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::mpsc::Sender;
@@ -5,23 +29,122 @@ use std::sync::mpsc::Sender;
 use eframe::egui::plot::Legend;
 use eframe::egui::plot::PlotUi;
 use eframe::egui::{self,Ui};
-use egui::plot::{Line, Plot, PlotPoints};
+use egui::plot::{Line, LineStyle, MarkerShape, Plot, PlotPoints, Points, VLine};
+use crate::analysis::freq::Margins;
+use self::cache::AnalysisCache;
+use crate::analysis::spectral::MeasuredResponse;
+use crate::analysis::locus::Gain;
+use crate::analysis::ident::Identification;
 use crate::control::Config;
 use crate::control::ConfigController;
 use crate::control::ControlType;
 use crate::control::PlotPnts;
 use crate::control::TypePid;
+use crate::control::law::LawRegistry;
+use crate::control::autotune::{RelayResult, TuningRule};
+use crate::control::analytic::ConfigAnalytic;
+use crate::control::optimizer::{ConfigOptim, OptimProgress};
+use crate::control::montecarlo::{ConfigMonteCarlo, MonteCarloProgress};
+use crate::control::sweep::{ConfigSweep, SweepMetric, SweepProgress};
 use crate::control::motor::ConfigMotor;
+use crate::control::observer::ConfigDob;
+use crate::control::protection::{DriveState, ProtectionStatus};
+use crate::control::timing::TimingStats;
+use crate::control::fixed::Comparison;
+use crate::control::server::{ConfigServer, ServerStatus};
+use crate::control::external::LiveStatus;
+use crate::control::modbus::{ConfigModbus, ModbusStatus};
+use crate::control::cia402::DriveObjects;
+
+// hover text of the batch runs for a law that only runs live
+const LIVE_ONLY: &str = "The selected control law talks to an outside process and only runs live";
 
 pub struct Motorsim{
     config: Config,
     target: Arc<Mutex<f64>>,
     plotpoints: Arc<Mutex<PlotPnts>>,
-    transmitter: Sender<Config>
+    protection: Arc<Mutex<ProtectionStatus>>,
+    drive_objects: Arc<Mutex<DriveObjects>>,
+    timing_stats: Arc<Mutex<TimingStats>>,
+    transmitter: Sender<Config>,
+    law_names: Vec<String>,
+    registry: Arc<LawRegistry>,
+    tuning_rule: TuningRule,
+    relay_results: [Option<RelayResult>; 3],
+    optim: ConfigOptim,
+    optim_progress: Option<Arc<Mutex<OptimProgress>>>,
+    show_optimizer: bool,
+    analytic: ConfigAnalytic,
+    show_wizard: bool,
+    analysis_loop: TypePid,
+    analysis: AnalysisCache,
+    show_bode: bool,
+    measuring: bool,
+    measured: Option<(TypePid, MeasuredResponse)>,
+    show_pz: bool,
+    locus_pid: TypePid,
+    locus_gain: Gain,
+    locus_max: f64,
+    show_nyquist: bool,
+    nichols: bool,
+    show_ident: bool,
+    ident_path: String,
+    identification: Option<Result<Identification, String>>,
+    montecarlo: ConfigMonteCarlo,
+    montecarlo_progress: Option<Arc<Mutex<MonteCarloProgress>>>,
+    show_montecarlo: bool,
+    sweep: ConfigSweep,
+    sweep_progress: Option<Arc<Mutex<SweepProgress>>>,
+    show_sweep: bool,
+    sweep_metric: SweepMetric,
+    // (column, ascending)
+    sweep_sort: (usize, bool),
+    sweep_path: String,
+    sweep_status: String,
+    show_faults: bool,
+    show_protection: bool,
+    show_cia402: bool,
+    show_limits: bool,
+    show_timing: bool,
+    show_timing_stats: bool,
+    show_fixed: bool,
+    fixed_comparison: Option<Result<Comparison, String>>,
+    show_codegen: bool,
+    // file name without the extension, also the C prefix
+    codegen_name: String,
+    codegen_status: String,
+    show_server: bool,
+    server: ConfigServer,
+    server_status: Option<Arc<Mutex<ServerStatus>>>,
+    show_external: bool,
+    external: LiveStatus,
+    show_modbus: bool,
+    modbus: ConfigModbus,
+    modbus_status: Option<Arc<Mutex<ModbusStatus>>>
 }
 
 impl eframe::App for Motorsim {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame){
+        self.optimizer_window(ctx);
+        self.wizard_window(ctx);
+        self.bode_window(ctx);
+        self.pole_zero_window(ctx);
+        self.nyquist_window(ctx);
+        self.ident_window(ctx);
+        self.montecarlo_window(ctx);
+        self.sweep_window(ctx);
+        self.faults_window(ctx);
+        self.protection_window(ctx);
+        self.cia402_window(ctx);
+        self.limits_window(ctx);
+        self.timing_window(ctx);
+        self.timing_stats_window(ctx);
+        self.fixed_window(ctx);
+        self.codegen_window(ctx);
+        self.server_window(ctx);
+        self.external_window(ctx);
+        self.modbus_window(ctx);
+        self.modbus_sync(ctx);
 
         egui::CentralPanel::default().show(&ctx, |ui| {
             let width = ui.available_width();
@@ -34,6 +157,9 @@ impl eframe::App for Motorsim {
                         left.horizontal(|left| {
                             left.selectable_value(self.config.set_controller_conf().set_control_option(), ControlType::Pos, "Pos");
                             left.selectable_value(self.config.set_controller_conf().set_control_option(), ControlType::PosVelTrq, "PosVelTrq");
+                            for (i, name) in self.law_names.iter().enumerate(){
+                                left.selectable_value(self.config.set_controller_conf().set_control_option(), ControlType::Custom(i), name);
+                            }
                         });
                     });
                     if Motorsim::pid_ui(&mut self.config, ["Angle controller", "Speed controller", "Torque controller"] , left){
@@ -41,8 +167,34 @@ impl eframe::App for Motorsim {
                         self.transmitter.send(self.config).unwrap();
                     }
 
+                    Motorsim::autotune_ui(&mut self.config, &mut self.tuning_rule, &mut self.relay_results, left);
+                    left.group(|left|{
+                        left.label("Tools :");
+                        left.horizontal_wrapped(|left| {
+                            left.toggle_value(&mut self.show_optimizer, "Optimizer");
+                            left.toggle_value(&mut self.show_wizard, "Tuning wizard");
+                            left.toggle_value(&mut self.show_bode, "Bode");
+                            left.toggle_value(&mut self.show_pz, "Pole-zero map");
+                            left.toggle_value(&mut self.show_nyquist, "Nyquist / Nichols");
+                            left.toggle_value(&mut self.show_ident, "Identification");
+                            left.toggle_value(&mut self.show_montecarlo, "Monte Carlo");
+                            left.toggle_value(&mut self.show_sweep, "Parameter sweep");
+                            left.toggle_value(&mut self.show_faults, "Faults");
+                            left.toggle_value(&mut self.show_protection, "Protection");
+                            left.toggle_value(&mut self.show_cia402, "CiA 402");
+                            left.toggle_value(&mut self.show_limits, "Travel limits");
+                            left.toggle_value(&mut self.show_timing, "Timing");
+                            left.toggle_value(&mut self.show_timing_stats, "Timing stats");
+                            left.toggle_value(&mut self.show_fixed, "Fixed point");
+                            left.toggle_value(&mut self.show_codegen, "Code export");
+                            left.toggle_value(&mut self.show_server, "Plant server");
+                            left.toggle_value(&mut self.show_external, "External controller");
+                            left.toggle_value(&mut self.show_modbus, "Modbus TCP");
+                        });
+                    });
                     Motorsim::motor_params_ui(self.config.set_motor_conf(), left);
                     Motorsim::bounds_ui(self.config.set_controller_conf(), left);
+                    Motorsim::dob_ui(self.config.set_dob_conf(), left);
                     left.add(egui::Slider::new(&mut *(self.target.lock().unwrap()), 0.0..=360.0).text("Pos target"));
 
                     left.group(|left|{
@@ -64,8 +216,23 @@ impl eframe::App for Motorsim {
                             left.label("Frequency, hz :");
                             left.add(egui::DragValue::new(self.config.set_controller_conf().set_frequency()).speed(0.05));
 
+                            left.label("Settling band, % :");
+                            left.add(egui::DragValue::new(self.config.set_controller_conf().set_settling_band()).speed(0.05).clamp_range(0.01..=100.0));
+                        });
+                        let status = self.protection.lock().unwrap();
+                        let state = status.get_state();
+                        let color = if state == DriveState::Running {left.visuals().text_color()} else {egui::Color32::RED};
+                        left.horizontal(|left|{
+                            left.colored_label(color, format!("Drive : {}", state.get_name()));
+                            if status.get_limit_active(){
+                                left.colored_label(egui::Color32::YELLOW, "Software limit");
+                            }
                         });
                     });
+
+                    if let Some(metrics) = self.plotpoints.lock().unwrap().get_metrics(){
+                        Motorsim::metrics_ui(&metrics, left);
+                    }
                 });
                 }
                 let right = &mut uis[1];
@@ -90,14 +257,69 @@ impl eframe::App for Motorsim {
 }
 
 impl Motorsim {
-    pub fn new(tx: Sender<Config>) -> Self{
+    pub fn new(tx: Sender<Config>, registry: Arc<LawRegistry>, external: LiveStatus) -> Self{
         let config = Config::default();
         tx.send(config).unwrap();
         Self {
             config: config,
             target: Arc::new(Mutex::new(180.0)),
             plotpoints: Arc::new(Mutex::new(PlotPnts::default())),
-            transmitter: tx
+            protection: Arc::new(Mutex::new(ProtectionStatus::default())),
+            drive_objects: Arc::new(Mutex::new(DriveObjects::default())),
+            timing_stats: Arc::new(Mutex::new(TimingStats::default())),
+            transmitter: tx,
+            law_names: registry.get_names(),
+            registry,
+            tuning_rule: TuningRule::ZieglerNichols,
+            relay_results: [None; 3],
+            optim: ConfigOptim::default(),
+            optim_progress: None,
+            show_optimizer: false,
+            analytic: ConfigAnalytic::default(),
+            show_wizard: false,
+            analysis_loop: TypePid::Pos,
+            analysis: AnalysisCache::default(),
+            show_bode: false,
+            measuring: false,
+            measured: None,
+            show_pz: false,
+            locus_pid: TypePid::Pos,
+            locus_gain: Gain::Kp,
+            locus_max: 200.0,
+            show_nyquist: false,
+            nichols: false,
+            show_ident: false,
+            ident_path: String::new(),
+            identification: None,
+            montecarlo: ConfigMonteCarlo::default(),
+            montecarlo_progress: None,
+            show_montecarlo: false,
+            sweep: ConfigSweep::default(),
+            sweep_progress: None,
+            show_sweep: false,
+            sweep_metric: SweepMetric::Itae,
+            sweep_sort: (0, true),
+            sweep_path: "sweep.csv".to_string(),
+            sweep_status: String::new(),
+            show_faults: false,
+            show_protection: false,
+            show_cia402: false,
+            show_limits: false,
+            show_timing: false,
+            show_timing_stats: false,
+            show_fixed: false,
+            fixed_comparison: None,
+            show_codegen: false,
+            codegen_name: "motor_ctrl".to_string(),
+            codegen_status: String::new(),
+            show_server: false,
+            server: ConfigServer::default(),
+            server_status: None,
+            show_external: false,
+            external,
+            show_modbus: false,
+            modbus: ConfigModbus::default(),
+            modbus_status: None
         }
     }
 
@@ -132,34 +354,63 @@ impl Motorsim {
         let vel_line = Line::new(PlotPoints::from(points.clone_vel_as_vec())).name("Speed, rpm");
         let trq_line = Line::new(PlotPoints::from(points.clone_trq_as_vec())).name("Torque, N*m");
         let vltg_line = Line::new(PlotPoints::from(points.clone_voltage_as_vec())).name("Voltage, V");
+        let dist_line = Line::new(PlotPoints::from(points.clone_dist_as_vec())).name("Disturbance est., N*m");
+        // empty without sensor faults
+        let sensed_style = |values: Vec<[f64; 2]>, name: &str| (!values.is_empty()).then(|| Line::new(PlotPoints::from(values)).style(LineStyle::dashed_loose()).name(name));
+        let sensed_pos = sensed_style(points.clone_sensed_pos_as_vec(), "Sensed angle, deg");
+        let sensed_vel = sensed_style(points.clone_sensed_vel_as_vec(), "Sensed speed, rpm");
+        let sensed_trq = sensed_style(points.clone_sensed_trq_as_vec(), "Sensed torque, N*m");
 
-        match config.get_controller_conf().get_calib_option(){
-            Some(pid_type) =>{
-                
-                for pid in config.get_pid_conf(){
-                    if pid.get_option() == *pid_type{
-                        match pid.get_option(){
-                            TypePid::Pos =>{
-                                pos_target = Line::new(PlotPoints::from(vec![[0.0, 180.0],[config.get_controller_conf().get_duration(), 180.0]]));
-                            },
-                            TypePid::Vel =>{
-                                let value = config.get_controller_conf().get_vel_bound()/2.0;
-                                vel_target = Line::new(PlotPoints::from(vec![[0.0, value],[config.get_controller_conf().get_duration(), value]]));
-                            },
-                            TypePid::Trq =>{
-                                let value = config.get_controller_conf().get_trq_bound()/2.0;
-                                trq_target = Line::new(PlotPoints::from(vec![[0.0, value],[config.get_controller_conf().get_duration(), value]]));
-                            }
-                        }
-                    }
-                }
+        if let (Some(pid_type), Some(value)) = (config.get_controller_conf().get_calib_option(), config.get_controller_conf().get_calib_target()){
+            let target = if config.get_excitation_conf().get_enabled(){
+                Line::new(PlotPoints::from(points.clone_reference_as_vec()))
+            } else {
+                Line::new(PlotPoints::from(vec![[0.0, value],[config.get_controller_conf().get_duration(), value]]))
+            };
+            match pid_type{
+                TypePid::Pos => pos_target = target,
+                TypePid::Vel => vel_target = target,
+                TypePid::Trq => trq_target = target,
             }
-            None => { }
         }
-        pos_plot.show(ui, |plot_ui: &mut PlotUi| {plot_ui.line(pos_line); plot_ui.line(pos_target)});
-        vel_plot.show(ui, |plot_ui: &mut PlotUi| {plot_ui.line(vel_line); plot_ui.line(vel_target)});
-        trq_plot.show(ui, |plot_ui: &mut PlotUi| {plot_ui.line(trq_line); plot_ui.line(trq_target)});
-        voltage_plot.show(ui, |plot_ui: &mut PlotUi| {plot_ui.line(vltg_line);});
+        // start and end of the scheduled faults
+        let fault_markers = |plot_ui: &mut PlotUi| {
+            for fault in config.get_faults().iter().filter(|fault| fault.get_enabled()){
+                for time in [fault.get_start(), fault.get_start() + fault.get_duration()]{
+                    plot_ui.vline(VLine::new(time).color(egui::Color32::RED).style(LineStyle::dashed_dense()).name(fault.get_option().get_name()));
+                }
+            }
+        };
+        let homes = points.get_homes().clone();
+        pos_plot.show(ui, |plot_ui: &mut PlotUi| {
+            plot_ui.line(pos_line);
+            if let Some(sensed) = sensed_pos{
+                plot_ui.line(sensed);
+            }
+            plot_ui.line(pos_target);
+            fault_markers(plot_ui);
+            if !homes.is_empty(){
+                plot_ui.points(Points::new(PlotPoints::from(homes)).shape(MarkerShape::Diamond).radius(5.0).color(egui::Color32::GREEN).name("Home"));
+            }
+        });
+        vel_plot.show(ui, |plot_ui: &mut PlotUi| {
+            plot_ui.line(vel_line);
+            if let Some(sensed) = sensed_vel{
+                plot_ui.line(sensed);
+            }
+            plot_ui.line(vel_target);
+            fault_markers(plot_ui);
+        });
+        trq_plot.show(ui, |plot_ui: &mut PlotUi| {
+            plot_ui.line(trq_line);
+            if let Some(sensed) = sensed_trq{
+                plot_ui.line(sensed);
+            }
+            plot_ui.line(trq_target);
+            plot_ui.line(dist_line);
+            fault_markers(plot_ui);
+        });
+        voltage_plot.show(ui, |plot_ui: &mut PlotUi| {plot_ui.line(vltg_line); fault_markers(plot_ui)});
     }
 
     fn pid_ui(config: &mut Config, label:[&str;3],  ui: &mut Ui) -> bool{
@@ -193,6 +444,7 @@ impl Motorsim {
                         }
                     }
                     ControlType::PosVelTrq =>{ }
+                    ControlType::Custom(_) =>{ }
                 }
                 if ui.add(egui::Button::new("Calibrate")).clicked() {
                     calib_option = Some(pid.get_option());
@@ -203,10 +455,35 @@ impl Motorsim {
         }
         if send_flag{
             *(config.set_controller_conf().set_calib_option()) = calib_option;
+            *(config.set_excitation_conf().set_enabled()) = false;
         }
     send_flag
     }
 
+    fn analysis_loop_ui(analysis_loop: &mut TypePid, available: [bool; 3], ui: &mut Ui){
+        ui.horizontal(|ui|{
+            for (option, label) in [(TypePid::Pos, "Angle loop"), (TypePid::Vel, "Speed loop"), (TypePid::Trq, "Torque loop")]{
+                ui.add_enabled_ui(available[option as usize], |ui| ui.selectable_value(analysis_loop, option, label));
+            }
+        });
+        if !available[*analysis_loop as usize]{
+            *analysis_loop = TypePid::Pos;
+        }
+    }
+
+    fn margins_ui(margins: &Margins, ui: &mut Ui){
+        let format = |value: Option<f64>, unit: &str| match value{
+            Some(value) => format!("{:.3} {}", value, unit),
+            None => "-".to_string(),
+        };
+        ui.horizontal(|ui|{
+            ui.label(format!("Gain margin : {} at {}", format(margins.get_gain_margin(), "dB"), format(margins.get_phase_crossover(), "hz")));
+            ui.label(format!("Phase margin : {} at {}", format(margins.get_phase_margin(), "deg"), format(margins.get_gain_crossover(), "hz")));
+            ui.label(format!("Bandwidth : {}", format(margins.get_bandwidth(), "hz")));
+            ui.label(format!("Ms : {} at {}", format(margins.get_sensitivity_peak(), ""), format(margins.get_sensitivity_freq(), "hz")));
+        });
+    }
+
     fn motor_params_ui(motor_conf: &mut ConfigMotor, ui: &mut Ui){
         ui.vertical(|ui|{
             ui.label("Motor parameters");
@@ -222,6 +499,8 @@ impl Motorsim {
                     ui.add(egui::DragValue::new(motor_conf.set_r()).speed(0.05).max_decimals(6));
                     ui.label("l :");
                     ui.add(egui::DragValue::new(motor_conf.set_l()).speed(0.05).max_decimals(6));
+                    ui.label("tl :");
+                    ui.add(egui::DragValue::new(motor_conf.set_tl()).speed(0.001).max_decimals(6));
                 });
             });
         });
@@ -233,16 +512,29 @@ impl Motorsim {
             ui.group(|ui|{
                 ui.horizontal(|ui| {
                     ui.label("Vltg bound, V :");
-                    ui.add(egui::DragValue::new(controller_conf.set_vltg_bound()).speed(0.05).max_decimals(6));
+                    ui.add(egui::DragValue::new(controller_conf.set_vltg_bound()).speed(0.05).clamp_range(0.0..=f64::MAX).max_decimals(6));
                     ui.label("Vel bound, rpm :");
-                    ui.add(egui::DragValue::new(controller_conf.set_vel_bound()).speed(0.05).max_decimals(6));
+                    ui.add(egui::DragValue::new(controller_conf.set_vel_bound()).speed(0.05).clamp_range(0.0..=f64::MAX).max_decimals(6));
                     ui.label("Trq bound, N*m :");
-                    ui.add(egui::DragValue::new(controller_conf.set_trq_bound()).speed(0.05).max_decimals(6));
+                    ui.add(egui::DragValue::new(controller_conf.set_trq_bound()).speed(0.05).clamp_range(0.0..=f64::MAX).max_decimals(6));
                 });
             });  
         });
     }
 
+    fn dob_ui(dob_conf: &mut ConfigDob, ui: &mut Ui){
+        ui.vertical(|ui|{
+            ui.label("Disturbance observer");
+            ui.group(|ui|{
+                ui.horizontal(|ui| {
+                    ui.checkbox(dob_conf.set_enabled(), "Compensation");
+                    ui.label("Q-filter bandwidth, rad/s :");
+                    ui.add(egui::DragValue::new(dob_conf.set_bandwidth()).speed(1.0).clamp_range(1.0..=100000.0));
+                });
+            });
+        });
+    }
+
     pub fn get_plotpoints(&self) -> Arc<Mutex<PlotPnts>>{
         Arc::clone(&self.plotpoints)
     }
@@ -251,4 +543,16 @@ impl Motorsim {
         Arc::clone(&self.target)
     }
 
+    pub fn get_protection(&self) -> Arc<Mutex<ProtectionStatus>>{
+        Arc::clone(&self.protection)
+    }
+
+    pub fn get_drive_objects(&self) -> Arc<Mutex<DriveObjects>>{
+        Arc::clone(&self.drive_objects)
+    }
+
+    pub fn get_timing_stats(&self) -> Arc<Mutex<TimingStats>>{
+        Arc::clone(&self.timing_stats)
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,36 @@
+use eframe::egui::{self,Ui};
+use crate::control::Config;
+use crate::control::autotune::{self, RelayResult, TuningRule, TUNING_RULES};
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn autotune_ui(config: &mut Config, rule: &mut TuningRule, results: &mut [Option<RelayResult>; 3], ui: &mut Ui){
+        ui.vertical(|ui|{
+            ui.label("Relay autotune");
+            ui.group(|ui|{
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("tuning_rule").selected_text(rule.get_name()).show_ui(ui, |ui|{
+                        for option in TUNING_RULES{
+                            ui.selectable_value(rule, option, option.get_name());
+                        }
+                    });
+                    if ui.add(egui::Button::new("Autotune")).clicked() {
+                        *results = autotune::relay_autotune(config, *rule);
+                    }
+                    if ui.add(egui::Button::new("Apply")).clicked() {
+                        for result in results.iter().flatten(){
+                            config.set_pid_conf()[result.get_option() as usize] = result.get_pid(*rule);
+                        }
+                    }
+                });
+                for (i, label) in ["Angle", "Speed", "Torque"].iter().enumerate(){
+                    if let Some(result) = results[i]{
+                        let pid = result.get_pid(*rule);
+                        ui.label(format!("{} : Ku = {:.4}, Pu = {:.5} s -> Kp = {:.4}, Ki = {:.4}, Kd = {:.6}",
+                            label, result.get_ku(), result.get_pu(), pid.get_kp(), pid.get_ki(), pid.get_kd()));
+                    }
+                }
+            });
+        });
+    }
+}
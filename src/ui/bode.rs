@@ -0,0 +1,56 @@
+use eframe::egui;
+use egui::plot::{HLine, Legend, Line, Plot, PlotPoints, Points};
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn bode_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_bode;
+        egui::Window::new("Bode").open(&mut open).default_width(800.0).show(ctx, |ui|{
+            let available = self.analysis.get_available(&self.config);
+            Motorsim::analysis_loop_ui(&mut self.analysis_loop, available, ui);
+            let Some(response) = self.analysis.get_response(&self.config, self.analysis_loop, 500) else {
+                return;
+            };
+            let (open_fr, closed_fr) = (response.get_open(), response.get_closed());
+            Motorsim::margins_ui(response.get_margins(), ui);
+            self.measurement_ui(ui);
+            let measured = match &self.measured{
+                Some((option, measured)) if *option == self.analysis_loop => Some(measured),
+                _ => None,
+            };
+
+            let height = ui.available_height().max(500.0)/2.5;
+            let log_formatter = |x: f64, _range: &std::ops::RangeInclusive<f64>| format!("{:.3}", 10f64.powf(x));
+            Plot::new("Bode magnitude").height(height).legend(Legend::default()).link_axis("bode", true, false)
+                .x_axis_formatter(log_formatter).label_formatter(|name, value| format!("{}\n{:.3} hz, {:.2} dB", name, 10f64.powf(value.x), value.y))
+                .show(ui, |plot_ui|{
+                    plot_ui.line(Line::new(PlotPoints::from(open_fr.mag_as_vec())).name("Open loop, dB"));
+                    plot_ui.line(Line::new(PlotPoints::from(closed_fr.mag_as_vec())).name("Closed loop, dB"));
+                    if let Some(measured) = measured{
+                        plot_ui.points(Points::new(PlotPoints::from(measured.get_open().mag_as_vec())).name("Measured open loop, dB"));
+                        plot_ui.points(Points::new(PlotPoints::from(measured.get_closed().mag_as_vec())).name("Measured closed loop, dB"));
+                    }
+                    plot_ui.hline(HLine::new(0.0));
+                });
+            Plot::new("Bode phase").height(height).legend(Legend::default()).link_axis("bode", true, false)
+                .x_axis_formatter(log_formatter).label_formatter(|name, value| format!("{}\n{:.3} hz, {:.1} deg", name, 10f64.powf(value.x), value.y))
+                .show(ui, |plot_ui|{
+                    plot_ui.line(Line::new(PlotPoints::from(open_fr.phase_as_vec())).name("Open loop, deg"));
+                    plot_ui.line(Line::new(PlotPoints::from(closed_fr.phase_as_vec())).name("Closed loop, deg"));
+                    if let Some(measured) = measured{
+                        plot_ui.points(Points::new(PlotPoints::from(measured.get_open().phase_as_vec())).name("Measured open loop, deg"));
+                        plot_ui.points(Points::new(PlotPoints::from(measured.get_closed().phase_as_vec())).name("Measured closed loop, deg"));
+                    }
+                    plot_ui.hline(HLine::new(-180.0));
+                });
+            if let Some(measured) = measured{
+                Plot::new("Coherence").height(height/2.0).legend(Legend::default()).link_axis("bode", true, false)
+                    .x_axis_formatter(log_formatter).include_y(0.0).include_y(1.0)
+                    .show(ui, |plot_ui|{
+                        plot_ui.line(Line::new(PlotPoints::from(measured.coherence_as_vec())).name("Coherence"));
+                    });
+            }
+        });
+        self.show_bode = open;
+    }
+}
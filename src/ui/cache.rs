@@ -0,0 +1,151 @@
+use std::rc::Rc;
+
+use nalgebra::Complex;
+
+use crate::analysis::freq::{self, FreqResponse, LoopTf, Margins};
+use crate::analysis::locus::{self, Gain, PoleZero};
+use crate::control::{Config, ConfigPid, ControlType, TypePid};
+use crate::control::motor::ConfigMotor;
+
+// points of the closed loop responses, log spaced
+const CLOSED_POINTS: usize = 500;
+const LOCUS_POINTS: usize = 200;
+
+// (loop, swept pid, gain, max)
+type LocusKey = (TypePid, TypePid, Gain, f64);
+
+pub struct LoopResponse{
+    open: FreqResponse,
+    closed: FreqResponse,
+    margins: Margins,
+}
+
+// Closed loop poles while one gain goes from 0 to max.
+pub struct Locus{
+    locus: Vec<(f64, Vec<Complex<f64>>)>,
+    critical: Option<f64>,
+}
+
+// Analysis of the cascade for the analysis windows, recomputed only when the
+// control type, frequency, motor or gains change instead of every frame.
+#[derive(Default)]
+pub struct AnalysisCache{
+    key: Option<(ControlType, f64, ConfigMotor, [ConfigPid; 3])>,
+    loops: [Option<LoopTf>; 3],
+    // (loop, open loop points)
+    responses: Vec<((TypePid, usize), Rc<LoopResponse>)>,
+    locus: Option<(LocusKey, Rc<Locus>)>,
+}
+
+impl LoopResponse{
+    pub fn get_open(&self) -> &FreqResponse{
+        &self.open
+    }
+
+    pub fn get_closed(&self) -> &FreqResponse{
+        &self.closed
+    }
+
+    pub fn get_margins(&self) -> &Margins{
+        &self.margins
+    }
+}
+
+impl Locus{
+    pub fn get_locus(&self) -> &Vec<(f64, Vec<Complex<f64>>)>{
+        &self.locus
+    }
+
+    pub fn get_critical(&self) -> Option<f64>{
+        self.critical
+    }
+}
+
+impl AnalysisCache{
+    fn refresh(&mut self, config: &Config){
+        let controller = config.get_controller_conf();
+        let key = Some((*controller.get_control_option(), controller.get_frequency(), *config.get_motor_conf(), *config.get_pid_conf()));
+        if self.key != key{
+            self.key = key;
+            self.loops = freq::loop_tfs(config);
+            self.responses.clear();
+            self.locus = None;
+        }
+    }
+
+    // loops of the cascade that exist with the control type
+    pub fn get_available(&mut self, config: &Config) -> [bool; 3]{
+        self.refresh(config);
+        [self.loops[0].is_some(), self.loops[1].is_some(), self.loops[2].is_some()]
+    }
+
+    pub fn get_response(&mut self, config: &Config, analysis_loop: TypePid, points: usize) -> Option<Rc<LoopResponse>>{
+        self.refresh(config);
+        if let Some((_, response)) = self.responses.iter().find(|(key, _)| *key == (analysis_loop, points)){
+            return Some(Rc::clone(response));
+        }
+        let loop_tf = self.loops[analysis_loop as usize].as_ref()?;
+        let frequency = config.get_controller_conf().get_frequency();
+        let open = FreqResponse::new(loop_tf.get_open(), frequency, 0.1, points);
+        let closed = FreqResponse::new(loop_tf.get_closed(), frequency, 0.1, CLOSED_POINTS);
+        let margins = Margins::new(&open, &closed);
+        let response = Rc::new(LoopResponse{open, closed, margins});
+        self.responses.push(((analysis_loop, points), Rc::clone(&response)));
+        Some(response)
+    }
+
+    pub fn get_pole_zero(&mut self, config: &Config, analysis_loop: TypePid) -> Option<PoleZero>{
+        self.refresh(config);
+        let closed = self.loops[analysis_loop as usize].as_ref()?.get_closed();
+        Some((closed.poles(), closed.zeros()))
+    }
+
+    pub fn get_locus(&mut self, config: &Config, analysis_loop: TypePid, pid: TypePid, gain: Gain, max: f64) -> Rc<Locus>{
+        self.refresh(config);
+        let key = (analysis_loop, pid, gain, max);
+        match &self.locus{
+            Some((cached, locus)) if *cached == key => Rc::clone(locus),
+            _ => {
+                let values = locus::root_locus(config, analysis_loop, pid, gain, max, LOCUS_POINTS);
+                let locus = Rc::new(Locus{critical: locus::critical_gain(&values), locus: values});
+                self.locus = Some((key, Rc::clone(&locus)));
+                locus
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn recomputes_on_change(){
+        let mut cache = AnalysisCache::default();
+        let mut config = Config::default();
+        let first = cache.get_response(&config, TypePid::Vel, 100).unwrap();
+        assert_eq!(first.get_open().get_freq().len(), 100);
+        assert_eq!(first.get_closed().get_freq().len(), CLOSED_POINTS);
+
+        // settings outside the loops keep the results
+        *config.set_controller_conf().set_duration() = 1.0;
+        *config.set_controller_conf().set_calib_option() = Some(TypePid::Vel);
+        assert!(Rc::ptr_eq(&first, &cache.get_response(&config, TypePid::Vel, 100).unwrap()));
+        assert!(!Rc::ptr_eq(&first, &cache.get_response(&config, TypePid::Vel, 200).unwrap()));
+
+        *config.set_pid_conf()[TypePid::Vel as usize].set_kp() *= 2.0;
+        let second = cache.get_response(&config, TypePid::Vel, 100).unwrap();
+        assert!(!Rc::ptr_eq(&first, &second));
+        assert!(second.get_margins().get_gain_crossover() != first.get_margins().get_gain_crossover());
+
+        let locus = cache.get_locus(&config, TypePid::Vel, TypePid::Vel, Gain::Kp, 1.0);
+        assert_eq!(locus.get_locus().len(), LOCUS_POINTS);
+        assert!(Rc::ptr_eq(&locus, &cache.get_locus(&config, TypePid::Vel, TypePid::Vel, Gain::Kp, 1.0)));
+        assert!(!Rc::ptr_eq(&locus, &cache.get_locus(&config, TypePid::Vel, TypePid::Vel, Gain::Ki, 1.0)));
+        assert_eq!(cache.get_pole_zero(&config, TypePid::Vel).unwrap().0, locus::pole_zero(&config, TypePid::Vel).unwrap().0);
+
+        *config.set_controller_conf().set_control_option() = ControlType::Pos;
+        assert_eq!(cache.get_available(&config), [true, false, false]);
+        assert!(cache.get_response(&config, TypePid::Vel, 100).is_none());
+    }
+}
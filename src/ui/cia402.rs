@@ -0,0 +1,124 @@
+use eframe::egui::{self,Ui};
+use crate::control::cia402::{self, PowerState, HOMING_METHODS, MODES, MODE_HOMING};
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn cia402_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_cia402;
+        egui::Window::new("CiA 402 drive").open(&mut open).show(ctx, |ui|{
+            if ui.checkbox(self.config.set_controller_conf().set_cia402(), "Drive profile instead of Pos target").changed(){
+                self.transmitter.send(self.config).unwrap();
+            }
+            let mut objects = self.drive_objects.lock().unwrap();
+            let statusword = objects.get_statusword();
+            // the set-point and homing start bit is released once the drive acknowledges it
+            if objects.get_controlword() & cia402::CW_NEW_SETPOINT != 0 && statusword & (cia402::SW_MODE_12 | cia402::SW_MODE_13) != 0{
+                *objects.set_controlword() &= !cia402::CW_NEW_SETPOINT;
+            }
+
+            ui.horizontal_wrapped(|ui|{
+                let controlword = objects.get_controlword() & !0x8f;
+                for (name, command) in [("Shutdown", cia402::SHUTDOWN), ("Switch on", cia402::SWITCH_ON), ("Enable operation", cia402::ENABLE_OPERATION),
+                    ("Disable voltage", cia402::DISABLE_VOLTAGE), ("Quick stop", cia402::QUICK_STOP)]{
+                    if ui.add(egui::Button::new(name)).clicked(){
+                        *objects.set_controlword() = controlword | command;
+                    }
+                }
+                if ui.add(egui::Button::new("Fault reset")).clicked(){
+                    *objects.set_controlword() |= cia402::CW_FAULT_RESET;
+                } else if PowerState::from_statusword(statusword) != PowerState::Fault{
+                    *objects.set_controlword() &= !cia402::CW_FAULT_RESET;
+                }
+            });
+            ui.horizontal(|ui|{
+                ui.label("Controlword :");
+                ui.add(egui::DragValue::new(objects.set_controlword()).hexadecimal(4, false, true));
+                let mut halt = objects.get_controlword() & cia402::CW_HALT != 0;
+                if ui.checkbox(&mut halt, "Halt").changed(){
+                    *objects.set_controlword() ^= cia402::CW_HALT;
+                }
+                let mut relative = objects.get_controlword() & cia402::CW_RELATIVE != 0;
+                if ui.checkbox(&mut relative, "Relative").changed(){
+                    *objects.set_controlword() ^= cia402::CW_RELATIVE;
+                }
+            });
+
+            egui::Grid::new("cia402_objects").show(ui, |ui|{
+                ui.label("Mode of operation");
+                let mode = objects.set_modes_of_operation();
+                egui::ComboBox::from_id_source("cia402_mode").selected_text(cia402::get_mode_name(*mode)).show_ui(ui, |ui|{
+                    for value in MODES{
+                        ui.selectable_value(mode, value, cia402::get_mode_name(value));
+                    }
+                });
+                ui.end_row();
+                // accelerations are magnitudes, min 0
+                let row = |ui: &mut Ui, label: &str, value: &mut f64, speed: f64, min: f64|{
+                    ui.label(label);
+                    ui.add(egui::DragValue::new(value).speed(speed).clamp_range(min..=f64::MAX));
+                    ui.end_row();
+                };
+                row(ui, "Target position, deg", objects.set_target_position(), 1.0, f64::MIN);
+                row(ui, "Target velocity, rpm", objects.set_target_velocity(), 1.0, f64::MIN);
+                row(ui, "Profile velocity, rpm", objects.set_profile_velocity(), 1.0, f64::MIN);
+                row(ui, "Profile acceleration, rpm/s", objects.set_profile_acceleration(), 10.0, 0.0);
+                row(ui, "Profile deceleration, rpm/s", objects.set_profile_deceleration(), 10.0, 0.0);
+                row(ui, "Quick stop deceleration, rpm/s", objects.set_quick_stop_deceleration(), 10.0, 0.0);
+                row(ui, "Home offset, deg", objects.set_home_offset(), 1.0, f64::MIN);
+                ui.label("Homing method");
+                let method = objects.set_homing_method();
+                egui::ComboBox::from_id_source("cia402_homing").selected_text(format!("{} {}", method, cia402::get_homing_method_name(*method))).show_ui(ui, |ui|{
+                    for value in HOMING_METHODS{
+                        ui.selectable_value(method, value, format!("{} {}", value, cia402::get_homing_method_name(value)));
+                    }
+                });
+                ui.end_row();
+                row(ui, "Homing switch speed, rpm", objects.set_homing_speed_switch(), 1.0, f64::MIN);
+                row(ui, "Homing zero speed, rpm", objects.set_homing_speed_zero(), 1.0, f64::MIN);
+                row(ui, "Homing acceleration, rpm/s", objects.set_homing_acceleration(), 10.0, 0.0);
+                row(ui, "Hard stop current, A", objects.set_homing_current_threshold(), 0.01, f64::MIN);
+            });
+            let start = if objects.get_modes_of_operation_display() == MODE_HOMING {"Start homing"} else {"New set-point"};
+            if ui.add(egui::Button::new(start)).clicked(){
+                *objects.set_controlword() |= cia402::CW_NEW_SETPOINT;
+            }
+
+            ui.separator();
+            let state = PowerState::from_statusword(statusword);
+            let color = if state == PowerState::Fault {egui::Color32::RED} else {ui.visuals().text_color()};
+            ui.colored_label(color, format!("Statusword : {:#06x}, {}", statusword, state.get_name()));
+            ui.horizontal(|ui|{
+                for (name, bit) in [("Target reached", cia402::SW_TARGET_REACHED), ("Internal limit", cia402::SW_INTERNAL_LIMIT), ("Bit 12", cia402::SW_MODE_12),
+                    ("Bit 13", cia402::SW_MODE_13)]{
+                    let mut set = statusword & bit != 0;
+                    ui.add_enabled(false, egui::Checkbox::new(&mut set, name));
+                }
+            });
+            ui.label(format!("Mode display : {}", cia402::get_mode_name(objects.get_modes_of_operation_display())));
+            ui.label(format!("Position actual : {:.3} deg, velocity actual : {:.3} rpm", objects.get_position_actual(), objects.get_velocity_actual()));
+            ui.horizontal(|ui|{
+                let inputs = objects.get_digital_inputs();
+                for (name, bit) in [("Negative limit", cia402::DI_NEGATIVE_LIMIT), ("Positive limit", cia402::DI_POSITIVE_LIMIT), ("Home switch", cia402::DI_HOME_SWITCH)]{
+                    let mut set = inputs & bit != 0;
+                    ui.add_enabled(false, egui::Checkbox::new(&mut set, name));
+                }
+            });
+            drop(objects);
+
+            ui.separator();
+            ui.label("Switches on the unwrapped angle, deg, applied on the next Start");
+            let switches = self.config.set_switches_conf();
+            ui.horizontal(|ui|{
+                ui.label("Negative limit :");
+                ui.add(egui::DragValue::new(switches.set_negative_limit()).speed(1.0));
+                ui.label("Positive limit :");
+                ui.add(egui::DragValue::new(switches.set_positive_limit()).speed(1.0));
+                ui.label("Home :");
+                ui.add(egui::DragValue::new(switches.set_home()).speed(1.0));
+                ui.label("Index :");
+                ui.add(egui::DragValue::new(switches.set_index()).speed(1.0).clamp_range(0.0..=360.0));
+            });
+        });
+        self.show_cia402 = open;
+    }
+}
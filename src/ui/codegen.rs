@@ -0,0 +1,40 @@
+use eframe::egui;
+use crate::control::codegen;
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn codegen_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_codegen;
+        egui::Window::new("Code export").open(&mut open).show(ctx, |ui|{
+            ui.label("The cascade with the current gains, bounds and frequency, single precision");
+            ui.horizontal(|ui|{
+                ui.label("Name :");
+                ui.text_edit_singleline(&mut self.codegen_name);
+            });
+            ui.horizontal(|ui|{
+                let name = self.codegen_name.clone();
+                if ui.add(egui::Button::new("Export C")).clicked(){
+                    let file = std::path::Path::new(&name).file_name().map_or(String::new(), |file| file.to_string_lossy().to_string());
+                    self.codegen_status = match codegen::to_c(&self.config, &file){
+                        Ok((header, source)) => match std::fs::write(format!("{}.h", name), header).and_then(|_| std::fs::write(format!("{}.c", name), source)){
+                            Ok(()) => format!("Saved to {}.h and {}.c", name, name),
+                            Err(error) => error.to_string(),
+                        },
+                        Err(error) => error,
+                    };
+                }
+                if ui.add(egui::Button::new("Export no_std Rust")).clicked(){
+                    self.codegen_status = match codegen::to_rust(&self.config){
+                        Ok(source) => match std::fs::write(format!("{}.rs", name), source){
+                            Ok(()) => format!("Saved to {}.rs", name),
+                            Err(error) => error.to_string(),
+                        },
+                        Err(error) => error,
+                    };
+                }
+            });
+            ui.label(&self.codegen_status);
+        });
+        self.show_codegen = open;
+    }
+}
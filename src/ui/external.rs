@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use eframe::egui;
+use egui::plot::{Bar, BarChart, Legend, Plot};
+use crate::control::montecarlo;
+use crate::control::external::TimeoutAction;
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn external_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_external;
+        egui::Window::new("External controller").open(&mut open).show(ctx, |ui|{
+            ui.label("The External control law exchanges every period with a controller process over UDP on 127.0.0.1, the protocol is in the README");
+            let external = self.config.set_external_conf();
+            egui::Grid::new("external_grid").show(ui, |ui|{
+                ui.label("Port");
+                ui.add(egui::DragValue::new(external.set_port()));
+                ui.end_row();
+                ui.label("Timeout, ms");
+                ui.add(egui::DragValue::new(external.set_timeout()).speed(0.01).clamp_range(0.0..=1000.0));
+                ui.end_row();
+                ui.label("On timeout");
+                let action = external.set_on_timeout();
+                egui::ComboBox::from_id_source("external_timeout").selected_text(action.get_name()).show_ui(ui, |ui|{
+                    for option in [TimeoutAction::Hold, TimeoutAction::Zero]{
+                        ui.selectable_value(action, option, option.get_name());
+                    }
+                });
+                ui.end_row();
+            });
+            if ui.add(egui::Button::new("Apply")).clicked(){
+                self.transmitter.send(self.config).unwrap();
+            }
+
+            let status = Arc::clone(&self.external.lock().unwrap());
+            let status = status.lock().unwrap();
+            if let Some(error) = status.get_error(){
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            let us = |value: f64| format!("{:.1} us", value*1e6);
+            egui::Grid::new("external_status").show(ui, |ui|{
+                ui.label("Periods / timeouts");
+                ui.label(format!("{} / {}", status.get_steps(), status.get_timeouts()));
+                ui.end_row();
+                ui.label("Latency, mean / max");
+                ui.label(format!("{} / {}", us(status.get_mean_latency()), us(status.get_max_latency())));
+                ui.end_row();
+            });
+            let latencies: Vec<f64> = status.get_latencies().iter().map(|latency| latency*1e6).collect();
+            drop(status);
+            let (width, bins) = montecarlo::histogram(&latencies, 40);
+            let chart = BarChart::new(bins.iter().map(|[center, count]| Bar::new(*center, *count).width(width)).collect()).name("Latency, us");
+            Plot::new("External latency").height(200.0).legend(Legend::default()).show(ui, |plot_ui|{
+                plot_ui.bar_chart(chart);
+            });
+        });
+        self.show_external = open;
+    }
+}
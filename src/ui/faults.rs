@@ -0,0 +1,45 @@
+use eframe::egui;
+use crate::control::fault::FAULT_TYPES;
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn faults_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_faults;
+        egui::Window::new("Fault injection").open(&mut open).show(ctx, |ui|{
+            ui.label("Times are counted from Start, faults are applied on the next Start");
+            ui.checkbox(self.config.set_headless_faults(), "Also in optimizer, sweep, Monte Carlo and fixed point comparison runs");
+            egui::Grid::new("faults_grid").show(ui, |ui|{
+                ui.label("");
+                ui.label("Fault");
+                ui.label("Start, sec");
+                ui.label("Duration, sec");
+                ui.label("Value");
+                ui.end_row();
+                for (i, fault) in self.config.set_faults().iter_mut().enumerate(){
+                    ui.checkbox(fault.set_enabled(), "");
+                    let option = fault.set_option();
+                    egui::ComboBox::from_id_source(("fault_type", i)).selected_text(option.get_name()).show_ui(ui, |ui|{
+                        for value in FAULT_TYPES{
+                            ui.selectable_value(option, value, value.get_name());
+                        }
+                    });
+                    ui.add(egui::DragValue::new(fault.set_start()).speed(0.01).clamp_range(0.0..=f64::MAX));
+                    ui.add(egui::DragValue::new(fault.set_duration()).speed(0.01).clamp_range(0.0..=f64::MAX));
+                    match fault.get_option().get_value_label(){
+                        Some(label) => {
+                            ui.horizontal(|ui|{
+                                ui.add(egui::DragValue::new(fault.set_value()).speed(0.01));
+                                ui.label(label);
+                            });
+                        }
+                        None => {
+                            ui.label("-");
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        self.show_faults = open;
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use eframe::egui;
+use egui::plot::{Legend, Line, Plot, PlotPoints};
+use crate::control::fixed::{self, Overflow, Rounding};
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn fixed_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_fixed;
+        egui::Window::new("Fixed point").open(&mut open).show(ctx, |ui|{
+            ui.label("Cascade of the PID loops without the observer, signals in Q(word-1) of their full scale");
+            let fixed = self.config.set_fixed_conf();
+            ui.checkbox(fixed.set_enabled(), "Replaces the control law");
+            egui::Grid::new("fixed_grid").show(ui, |ui|{
+                ui.label("Word, bits");
+                ui.add(egui::DragValue::new(fixed.set_word()).clamp_range(2..=32));
+                ui.end_row();
+                ui.label("Accumulator, bits");
+                ui.add(egui::DragValue::new(fixed.set_accumulator()).clamp_range(2..=32));
+                ui.end_row();
+                ui.label("Rounding");
+                let rounding = fixed.set_rounding();
+                egui::ComboBox::from_id_source("fixed_rounding").selected_text(rounding.get_name()).show_ui(ui, |ui|{
+                    for option in [Rounding::Truncate, Rounding::Nearest]{
+                        ui.selectable_value(rounding, option, option.get_name());
+                    }
+                });
+                ui.end_row();
+                ui.label("Overflow");
+                let overflow = fixed.set_overflow();
+                egui::ComboBox::from_id_source("fixed_overflow").selected_text(overflow.get_name()).show_ui(ui, |ui|{
+                    for option in [Overflow::Saturate, Overflow::Wrap]{
+                        ui.selectable_value(overflow, option, option.get_name());
+                    }
+                });
+                ui.end_row();
+                for (scale, name) in fixed.set_scales().iter_mut().zip(["Angle full scale, deg", "Speed full scale, rpm", "Torque full scale, N*m", "Voltage full scale, V"]){
+                    ui.label(name);
+                    ui.add(egui::DragValue::new(scale).speed(0.1).clamp_range(0.001..=f64::MAX));
+                    ui.end_row();
+                }
+            });
+            ui.horizontal(|ui|{
+                if ui.add(egui::Button::new("Apply")).clicked(){
+                    self.transmitter.send(self.config).unwrap();
+                }
+                // the calibration of the selected loop with both controllers, the observer off in both
+                if ui.add(egui::Button::new("Compare with f64")).clicked(){
+                    self.fixed_comparison = Some(fixed::compare(self.config, Arc::clone(&self.registry)));
+                }
+            });
+            if self.config.get_fixed_conf().get_enabled(){
+                ui.label(format!("Overflows of the running controller : {}", self.plotpoints.lock().unwrap().get_overflows()));
+            }
+
+            if let Some(Err(error)) = &self.fixed_comparison{
+                ui.label(error);
+            }
+            if let Some(Ok(comparison)) = &self.fixed_comparison{
+                let [value, voltage] = comparison.get_errors();
+                egui::Grid::new("fixed_comparison").show(ui, |ui|{
+                    ui.label("");
+                    ui.label("rms");
+                    ui.label("max");
+                    ui.end_row();
+                    ui.label("Controlled value difference");
+                    ui.label(format!("{:.5}", value.0));
+                    ui.label(format!("{:.5}", value.1));
+                    ui.end_row();
+                    ui.label("Voltage difference, V");
+                    ui.label(format!("{:.5}", voltage.0));
+                    ui.label(format!("{:.5}", voltage.1));
+                    ui.end_row();
+                });
+                ui.label(format!("Overflows : {}", comparison.get_overflows()));
+                for (name, metrics) in [("f64", comparison.get_float_metrics()), ("Fixed point", comparison.get_fixed_metrics())]{
+                    if let Some(metrics) = metrics{
+                        ui.label(format!("{} : ITAE {:.4}, overshoot {:.2} %, steady state error {:.4}", name, metrics.get_itae(), metrics.get_overshoot(), metrics.get_ss_error()));
+                    }
+                }
+                Plot::new("Fixed comparison").height(200.0).legend(Legend::default()).show(ui, |plot_ui|{
+                    plot_ui.line(Line::new(PlotPoints::from(comparison.get_float().clone())).name("f64"));
+                    plot_ui.line(Line::new(PlotPoints::from(comparison.get_fixed().clone())).name("Fixed point"));
+                });
+                Plot::new("Fixed difference").height(150.0).legend(Legend::default()).show(ui, |plot_ui|{
+                    plot_ui.line(Line::new(PlotPoints::from(comparison.get_difference())).name("Fixed minus f64"));
+                });
+            }
+        });
+        self.show_fixed = open;
+    }
+}
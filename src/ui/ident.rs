@@ -0,0 +1,75 @@
+use eframe::egui;
+use egui::plot::{Legend, Line, Plot, PlotPoints};
+use crate::analysis::ident::{self, Recording, PARAMETERS};
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn ident_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_ident;
+        egui::Window::new("Identification").open(&mut open).default_width(700.0).show(ctx, |ui|{
+            ui.label("CSV columns : time s, voltage V, position deg, velocity rpm, current A");
+            ui.horizontal(|ui|{
+                ui.label("File :");
+                ui.text_edit_singleline(&mut self.ident_path);
+                if ui.add(egui::Button::new("Identify")).clicked(){
+                    self.identification = Some(std::fs::read_to_string(&self.ident_path).map_err(|error| error.to_string())
+                        .and_then(|text| Recording::from_csv(&text)).and_then(|recording| ident::identify(&recording)));
+                }
+            });
+
+            let identification = match &self.identification{
+                Some(Ok(identification)) => identification,
+                Some(Err(error)) => {
+                    ui.colored_label(egui::Color32::RED, error);
+                    return;
+                }
+                None => return,
+            };
+
+            let motor_conf = self.config.get_motor_conf();
+            let current = [motor_conf.get_j(), motor_conf.get_b(), motor_conf.get_k(), motor_conf.get_r(), motor_conf.get_l(), motor_conf.get_tl()];
+            egui::Grid::new("ident_grid").striped(true).show(ui, |ui|{
+                ui.label("Parameter");
+                ui.label("Estimate");
+                ui.label("95% interval");
+                ui.label("Simulator");
+                ui.end_row();
+                for ((name, estimate), value) in PARAMETERS.iter().zip(identification.get_estimates().iter()).zip(current.iter()){
+                    ui.label(*name);
+                    ui.label(format!("{:.6e}", estimate.get_value()));
+                    ui.label(format!("± {:.2e}", estimate.get_ci95()));
+                    ui.label(format!("{:.6e}", value));
+                    ui.end_row();
+                }
+            });
+
+            if !identification.is_physical(){
+                ui.colored_label(egui::Color32::RED, "Estimates are not physical, the recording needs more excitation");
+                return;
+            }
+            ui.horizontal(|ui|{
+                ui.label(format!("Replay fit : velocity {:.1} %, current {:.1} %",
+                    100.0*(1.0 - identification.get_vel_nrmse()), 100.0*(1.0 - identification.get_current_nrmse())));
+                if ui.add(egui::Button::new("Apply")).clicked(){
+                    let end_stops = *self.config.get_motor_conf().get_end_stops();
+                    *self.config.set_motor_conf() = identification.get_motor();
+                    *self.config.set_motor_conf().set_end_stops() = end_stops;
+                    self.transmitter.send(self.config).unwrap();
+                }
+            });
+
+            let height = ui.available_height().max(400.0)/2.5;
+            let (recorded, replay) = identification.velocity_as_vec();
+            Plot::new("Identification velocity").height(height).legend(Legend::default()).link_axis("ident", true, false).show(ui, |plot_ui|{
+                plot_ui.line(Line::new(PlotPoints::from(recorded)).name("Recorded velocity, rpm"));
+                plot_ui.line(Line::new(PlotPoints::from(replay)).name("Replayed velocity, rpm"));
+            });
+            let (recorded, replay) = identification.current_as_vec();
+            Plot::new("Identification current").height(height).legend(Legend::default()).link_axis("ident", true, false).show(ui, |plot_ui|{
+                plot_ui.line(Line::new(PlotPoints::from(recorded)).name("Recorded current, A"));
+                plot_ui.line(Line::new(PlotPoints::from(replay)).name("Replayed current, A"));
+            });
+        });
+        self.show_ident = open;
+    }
+}
@@ -0,0 +1,39 @@
+use eframe::egui::{self,Ui};
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn limits_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_limits;
+        egui::Window::new("Travel limits").open(&mut open).show(ctx, |ui|{
+            ui.label("Mechanical end stops on the unwrapped angle");
+            let end_stops = self.config.set_motor_conf().set_end_stops();
+            ui.checkbox(end_stops.set_enabled(), "End stops");
+            egui::Grid::new("end_stops").show(ui, |ui|{
+                let row = |ui: &mut Ui, label: &str, value: &mut f64, speed: f64|{
+                    ui.label(label);
+                    ui.add(egui::DragValue::new(value).speed(speed));
+                    ui.end_row();
+                };
+                row(ui, "Negative stop, deg", end_stops.set_negative(), 1.0);
+                row(ui, "Positive stop, deg", end_stops.set_positive(), 1.0);
+                row(ui, "Stiffness, N*m/rad", end_stops.set_stiffness(), 0.1);
+                row(ui, "Damping, N*m*s/rad", end_stops.set_damping(), 0.001);
+            });
+
+            ui.separator();
+            ui.label("Software limits on the unwrapped angle, not applied in calibrations and homing");
+            let limits = self.config.set_limits_conf();
+            ui.horizontal(|ui|{
+                ui.checkbox(limits.set_enabled(), "Software limits");
+                ui.label("Min, deg :");
+                ui.add(egui::DragValue::new(limits.set_min()).speed(1.0));
+                ui.label("Max, deg :");
+                ui.add(egui::DragValue::new(limits.set_max()).speed(1.0));
+            });
+            if ui.add(egui::Button::new("Apply")).clicked(){
+                self.transmitter.send(self.config).unwrap();
+            }
+        });
+        self.show_limits = open;
+    }
+}
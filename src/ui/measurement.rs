@@ -0,0 +1,58 @@
+use eframe::egui::{self,Ui};
+use crate::analysis::spectral;
+use crate::control::excitation::ExcitationType;
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn measurement_ui(&mut self, ui: &mut Ui){
+        ui.horizontal(|ui|{
+            let excitation = self.config.set_excitation_conf();
+            let option = excitation.set_option();
+            egui::ComboBox::from_id_source("excitation_type").selected_text(option.get_name()).show_ui(ui, |ui|{
+                for value in [ExcitationType::Chirp, ExcitationType::SteppedSine]{
+                    ui.selectable_value(option, value, value.get_name());
+                }
+            });
+            ui.label("From, hz :");
+            ui.add(egui::DragValue::new(excitation.set_f_start()).speed(0.1).clamp_range(0.01..=100000.0));
+            ui.label("To, hz :");
+            ui.add(egui::DragValue::new(excitation.set_f_end()).speed(0.1).clamp_range(0.01..=100000.0));
+            ui.label("Amplitude, part of step :");
+            ui.add(egui::DragValue::new(excitation.set_amplitude()).speed(0.001).clamp_range(0.0..=10.0));
+            ui.label("Steps :");
+            ui.add(egui::DragValue::new(excitation.set_steps()).clamp_range(1..=1000));
+
+            if ui.add_enabled(!self.measuring, egui::Button::new("Measure")).clicked(){
+                *(self.config.set_excitation_conf().set_enabled()) = true;
+                *(self.config.set_controller_conf().set_calib_option()) = Some(self.analysis_loop);
+                *(self.config.set_controller_conf().set_start_flag()) = true;
+                self.plotpoints.lock().unwrap().reset();
+                self.transmitter.send(self.config).unwrap();
+                self.measured = None;
+                self.measuring = true;
+            }
+            if self.measuring && ui.button("Cancel").clicked(){
+                self.measuring = false;
+            }
+        });
+
+        // Start, Stop or another calibration ended the measurement run
+        let controller = self.config.get_controller_conf();
+        if !*controller.get_start_flag() || *controller.get_calib_option() != Some(self.analysis_loop){
+            self.measuring = false;
+        }
+        if self.measuring{
+            let points = self.plotpoints.lock().unwrap();
+            if points.get_metrics().is_some(){
+                let output = points.clone_loop_as_vec(self.analysis_loop);
+                let excitation = self.config.get_excitation_conf();
+                self.measured = spectral::estimate_response(&points.clone_reference_as_vec(), &output,
+                    self.config.get_controller_conf().get_frequency(), excitation.get_f_start(), excitation.get_f_end())
+                    .map(|measured| (self.analysis_loop, measured));
+                self.measuring = false;
+            } else {
+                ui.label("Measuring ...");
+            }
+        }
+    }
+}
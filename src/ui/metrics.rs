@@ -0,0 +1,31 @@
+use eframe::egui::{self,Ui};
+use crate::control::metrics::StepMetrics;
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn metrics_ui(metrics: &StepMetrics, ui: &mut Ui){
+        let format_time = |time: Option<f64>| match time{
+            Some(time) => format!("{:.4} s", time),
+            None => "-".to_string(),
+        };
+        ui.vertical(|ui|{
+            ui.label("Step metrics");
+            ui.group(|ui|{
+                egui::Grid::new("step_metrics").show(ui, |ui|{
+                    ui.label(format!("Rise time : {}", format_time(metrics.get_rise_time())));
+                    ui.label(format!("Overshoot : {:.2} %", metrics.get_overshoot()));
+                    ui.label(format!("Settling time : {}", format_time(metrics.get_settling_time())));
+                    ui.end_row();
+                    ui.label(format!("Steady-state error : {:.5}", metrics.get_ss_error()));
+                    ui.label(format!("Peak voltage : {:.3} V", metrics.get_peak_effort()));
+                    ui.label(format!("Time in saturation : {:.4} s", metrics.get_saturation_time()));
+                    ui.end_row();
+                    ui.label(format!("IAE : {:.5}", metrics.get_iae()));
+                    ui.label(format!("ISE : {:.5}", metrics.get_ise()));
+                    ui.label(format!("ITAE : {:.5}", metrics.get_itae()));
+                    ui.end_row();
+                });
+            });
+        });
+    }
+}
@@ -0,0 +1,55 @@
+use eframe::egui;
+use crate::control::modbus;
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn modbus_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_modbus;
+        egui::Window::new("Modbus TCP").open(&mut open).show(ctx, |ui|{
+            ui.label("Serves the drive registers on 127.0.0.1, the register map is in the README");
+            let running = self.modbus_status.as_ref().is_some_and(|status| status.lock().unwrap().get_running());
+            ui.horizontal(|ui|{
+                ui.label("Port :");
+                ui.add_enabled(!running, egui::DragValue::new(self.modbus.set_port()));
+                if ui.add_enabled(!running, egui::Button::new("Start")).clicked(){
+                    self.modbus_status = Some(modbus::run(self.modbus, self.law_names.len() as u16 + 2));
+                }
+                if ui.add_enabled(running, egui::Button::new("Stop")).clicked(){
+                    if let Some(status) = &self.modbus_status{
+                        *status.lock().unwrap().set_stop_flag() = true;
+                    }
+                }
+            });
+            let Some(status) = &self.modbus_status else {
+                return;
+            };
+            let status = status.lock().unwrap();
+            if let Some(error) = status.get_error(){
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            ui.label(format!("Listening on : {}, connections : {}, requests : {}, exceptions : {}", status.get_port(), status.get_clients(),
+                status.get_requests(), status.get_exceptions()));
+        });
+        self.show_modbus = open;
+    }
+
+    // Live values go to the input registers, client writes to the holding registers are applied as from the UI.
+    pub fn modbus_sync(&mut self, ctx: &egui::Context){
+        let Some(status) = &self.modbus_status else {
+            return;
+        };
+        let mut status = status.lock().unwrap();
+        if !status.get_running(){
+            return;
+        }
+        let points = self.plotpoints.lock().unwrap();
+        let (pos, vel, trq, voltage) = (points.last_pos(), points.last_vel(), points.last_trq(), points.last_voltage());
+        drop(points);
+        status.set_inputs([pos[0], pos[1], vel[1], trq[1], voltage[1]], self.protection.lock().unwrap().get_state());
+        if status.sync(&mut self.config, &mut self.target.lock().unwrap()){
+            self.transmitter.send(self.config).unwrap();
+        }
+        // clients expect fresh values without the window being touched
+        ctx.request_repaint_after(std::time::Duration::from_millis(50));
+    }
+}
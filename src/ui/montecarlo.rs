@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use eframe::egui;
+use egui::plot::{Bar, BarChart, Legend, Line, LineStyle, Plot, PlotPoints};
+use crate::control::ControlType;
+use crate::control::montecarlo::{self, Distribution, TOLERANCE_PARAMETERS};
+use super::{Motorsim, LIVE_ONLY};
+
+impl Motorsim{
+    pub fn montecarlo_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_montecarlo;
+        egui::Window::new("Monte Carlo").open(&mut open).default_width(700.0).show(ctx, |ui|{
+            let available = match self.config.get_controller_conf().get_control_option(){
+                ControlType::Pos => [true, false, false],
+                _ => [true, true, true],
+            };
+            Motorsim::analysis_loop_ui(&mut self.analysis_loop, available, ui);
+
+            egui::Grid::new("montecarlo_tolerances").show(ui, |ui|{
+                ui.label("Parameter");
+                ui.label("Distribution");
+                ui.label("Tolerance, part of nominal");
+                ui.end_row();
+                for (i, (name, tolerance)) in TOLERANCE_PARAMETERS.iter().zip(self.montecarlo.set_tolerances().iter_mut()).enumerate(){
+                    ui.label(*name);
+                    let distribution = tolerance.set_distribution();
+                    egui::ComboBox::from_id_source(("montecarlo_distribution", i)).selected_text(distribution.get_name()).show_ui(ui, |ui|{
+                        for option in [Distribution::Uniform, Distribution::Normal]{
+                            ui.selectable_value(distribution, option, option.get_name());
+                        }
+                    });
+                    ui.add(egui::DragValue::new(tolerance.set_tolerance()).speed(0.001).clamp_range(0.0..=0.99));
+                    ui.end_row();
+                }
+            });
+
+            let running = self.montecarlo_progress.as_ref().is_some_and(|progress| progress.lock().unwrap().get_running());
+            ui.horizontal(|ui|{
+                ui.label("Samples :");
+                ui.add(egui::DragValue::new(self.montecarlo.set_samples()).clamp_range(1..=100000));
+                ui.label("Seed :");
+                ui.add(egui::DragValue::new(self.montecarlo.set_seed()));
+                let headless = self.registry.get_headless(*self.config.get_controller_conf().get_control_option());
+                if ui.add_enabled(!running && headless, egui::Button::new("Run")).on_disabled_hover_text(LIVE_ONLY).clicked(){
+                    let mut config = self.config;
+                    *config.set_controller_conf().set_calib_option() = Some(self.analysis_loop);
+                    *config.set_excitation_conf().set_enabled() = false;
+                    self.montecarlo_progress = Some(montecarlo::run(config, self.montecarlo, Arc::clone(&self.registry)));
+                }
+                if ui.add_enabled(running, egui::Button::new("Stop")).clicked(){
+                    if let Some(progress) = &self.montecarlo_progress{
+                        *progress.lock().unwrap().set_stop_flag() = true;
+                    }
+                }
+            });
+
+            let Some(progress) = &self.montecarlo_progress else {
+                return;
+            };
+            let progress = progress.lock().unwrap();
+            ui.add(egui::ProgressBar::new(progress.get_done() as f32/progress.get_samples() as f32)
+                .text(format!("sample {} / {}", progress.get_done(), progress.get_samples())));
+
+            let unstable: Vec<(usize, &montecarlo::Sample)> = progress.get_results().iter().enumerate().filter(|(_, sample)| sample.get_unstable()).collect();
+            if unstable.is_empty(){
+                ui.label("Unstable samples : none");
+            } else {
+                egui::CollapsingHeader::new(egui::RichText::new(format!("Unstable samples : {}", unstable.len())).color(egui::Color32::RED)).show(ui, |ui|{
+                    for (index, sample) in unstable{
+                        let motor = sample.get_motor();
+                        ui.label(format!("#{} : j = {:.4e}, b = {:.4e}, k = {:.4e}, r = {:.4e}, l = {:.4e}", index,
+                            motor.get_j(), motor.get_b(), motor.get_k(), motor.get_r(), motor.get_l()));
+                    }
+                });
+            }
+
+            let height = ui.available_height().max(400.0)/2.0;
+            Plot::new("Monte Carlo envelope").height(height).legend(Legend::default()).show(ui, |plot_ui|{
+                plot_ui.line(Line::new(PlotPoints::from(progress.get_nominal().clone())).name("Nominal"));
+                plot_ui.line(Line::new(PlotPoints::from(progress.lower_as_vec())).style(LineStyle::dashed_dense()).name("Lower envelope"));
+                plot_ui.line(Line::new(PlotPoints::from(progress.upper_as_vec())).style(LineStyle::dashed_dense()).name("Upper envelope"));
+            });
+
+            let to_chart = |values: &[f64], name: &str| {
+                let (width, bins) = montecarlo::histogram(values, 20);
+                BarChart::new(bins.iter().map(|[center, count]| Bar::new(*center, *count).width(width)).collect()).name(name)
+            };
+            let overshoots = progress.get_overshoots();
+            let settling_times = progress.get_settling_times();
+            ui.columns(2, |columns|{
+                Plot::new("Monte Carlo overshoot").height(height/1.5).legend(Legend::default()).show(&mut columns[0], |plot_ui|{
+                    plot_ui.bar_chart(to_chart(&overshoots, "Overshoot, %"));
+                });
+                Plot::new("Monte Carlo settling").height(height/1.5).legend(Legend::default()).show(&mut columns[1], |plot_ui|{
+                    plot_ui.bar_chart(to_chart(&settling_times, "Settling time, sec"));
+                });
+            });
+        });
+        self.show_montecarlo = open;
+    }
+}
@@ -0,0 +1,57 @@
+use eframe::egui;
+use egui::plot::{Legend, Line, LineStyle, MarkerShape, Plot, PlotPoints, Points};
+use crate::analysis::freq;
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn nyquist_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_nyquist;
+        egui::Window::new("Nyquist / Nichols").open(&mut open).default_width(600.0).show(ctx, |ui|{
+            let available = self.analysis.get_available(&self.config);
+            Motorsim::analysis_loop_ui(&mut self.analysis_loop, available, ui);
+            let Some(response) = self.analysis.get_response(&self.config, self.analysis_loop, 1000) else {
+                return;
+            };
+            ui.horizontal(|ui|{
+                ui.selectable_value(&mut self.nichols, false, "Nyquist");
+                ui.selectable_value(&mut self.nichols, true, "Nichols");
+            });
+
+            let (open_fr, margins) = (response.get_open(), response.get_margins());
+            Motorsim::margins_ui(margins, ui);
+
+            // closed loop magnitudes of the M contours, dB
+            let contours = [-6.0, -3.0, 3.0, 6.0];
+            let m_circle = |db: f64| freq::m_circle(10f64.powf(db/20.0), 200);
+
+            if self.nichols{
+                Plot::new("Nichols").legend(Legend::default()).include_x(-360.0).include_x(0.0)
+                    .label_formatter(|name, value| format!("{}\n{:.1} deg, {:.2} dB", name, value.x, value.y))
+                    .show(ui, |plot_ui|{
+                        plot_ui.line(Line::new(PlotPoints::from(open_fr.nichols_as_vec())).name("Open loop"));
+                        for db in contours{
+                            plot_ui.line(Line::new(PlotPoints::from(freq::to_nichols(&m_circle(db)))).style(LineStyle::dashed_loose()).name(format!("M = {} dB", db)));
+                        }
+                        plot_ui.points(Points::new([-180.0, 0.0]).shape(MarkerShape::Plus).radius(6.0).name("-1"));
+                    });
+            } else {
+                let to_points = |values: Vec<nalgebra::Complex<f64>>| -> Vec<[f64; 2]>{ values.iter().map(|z| [z.re, z.im]).collect() };
+                let mirror: Vec<[f64; 2]> = open_fr.nyquist_as_vec(10.0).iter().map(|[re, im]| [*re, -im]).collect();
+                Plot::new("Nyquist").data_aspect(1.0).view_aspect(1.0).legend(Legend::default())
+                    .include_x(-3.0).include_x(1.0).include_y(-2.0).include_y(2.0)
+                    .show(ui, |plot_ui|{
+                        plot_ui.line(Line::new(PlotPoints::from(open_fr.nyquist_as_vec(10.0))).name("Open loop"));
+                        plot_ui.line(Line::new(PlotPoints::from(mirror)).style(LineStyle::dashed_dense()).name("Open loop, negative frequencies"));
+                        for db in contours{
+                            plot_ui.line(Line::new(PlotPoints::from(to_points(m_circle(db)))).style(LineStyle::dashed_loose()).name(format!("M = {} dB", db)));
+                        }
+                        if let Some(ms) = margins.get_sensitivity_peak(){
+                            plot_ui.line(Line::new(PlotPoints::from(to_points(freq::ms_circle(ms, 200)))).name("Ms circle"));
+                        }
+                        plot_ui.points(Points::new([-1.0, 0.0]).shape(MarkerShape::Plus).radius(6.0).name("-1"));
+                    });
+            }
+        });
+        self.show_nyquist = open;
+    }
+}
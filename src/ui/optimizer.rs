@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use eframe::egui;
+use crate::control::TypePid;
+use crate::control::optimizer::{self, OptimTarget, COSTS};
+use super::{Motorsim, LIVE_ONLY};
+
+impl Motorsim{
+    pub fn optimizer_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_optimizer;
+        egui::Window::new("Gain optimizer").open(&mut open).show(ctx, |ui|{
+            let target_name = |target: &OptimTarget| match target{
+                OptimTarget::Loop(TypePid::Pos) => "Angle loop",
+                OptimTarget::Loop(TypePid::Vel) => "Speed loop",
+                OptimTarget::Loop(TypePid::Trq) => "Torque loop",
+                OptimTarget::Cascade => "Whole cascade",
+            };
+            ui.horizontal(|ui|{
+                let target = self.optim.set_target();
+                egui::ComboBox::from_id_source("optim_target").selected_text(target_name(target)).show_ui(ui, |ui|{
+                    for option in [OptimTarget::Loop(TypePid::Pos), OptimTarget::Loop(TypePid::Vel), OptimTarget::Loop(TypePid::Trq), OptimTarget::Cascade]{
+                        ui.selectable_value(target, option, target_name(&option));
+                    }
+                });
+                let cost = self.optim.set_cost();
+                egui::ComboBox::from_id_source("optim_cost").selected_text(cost.get_name()).show_ui(ui, |ui|{
+                    for option in COSTS{
+                        ui.selectable_value(cost, option, option.get_name());
+                    }
+                });
+                ui.label("Max iterations :");
+                ui.add(egui::DragValue::new(self.optim.set_max_iter()).clamp_range(1..=10000));
+            });
+
+            egui::CollapsingHeader::new("Bounds (equal bounds keep the gain fixed)").show(ui, |ui|{
+                egui::Grid::new("optim_bounds").show(ui, |ui|{
+                    ui.label("");
+                    for gain in ["Kp", "Ki", "Kd"]{
+                        ui.label(format!("{} min", gain));
+                        ui.label(format!("{} max", gain));
+                    }
+                    ui.end_row();
+                    for (label, bounds) in ["Angle", "Speed", "Torque"].iter().zip(self.optim.set_bounds().iter_mut()){
+                        ui.label(*label);
+                        for (lower, upper) in bounds.iter_mut(){
+                            ui.add(egui::DragValue::new(lower).speed(0.01).max_decimals(6));
+                            ui.add(egui::DragValue::new(upper).speed(0.01).max_decimals(6));
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+
+            let running = self.optim_progress.as_ref().is_some_and(|progress| progress.lock().unwrap().get_running());
+            ui.horizontal(|ui|{
+                let headless = self.registry.get_headless(*self.config.get_controller_conf().get_control_option());
+                if ui.add_enabled(!running && headless, egui::Button::new("Optimize")).on_disabled_hover_text(LIVE_ONLY).clicked(){
+                    self.optim_progress = Some(optimizer::optimize(self.config, self.optim, Arc::clone(&self.registry)));
+                }
+                if ui.add_enabled(running, egui::Button::new("Stop")).clicked(){
+                    if let Some(progress) = &self.optim_progress{
+                        *progress.lock().unwrap().set_stop_flag() = true;
+                    }
+                }
+                if ui.add_enabled(!running && self.optim_progress.is_some(), egui::Button::new("Apply")).clicked(){
+                    if let Some(progress) = &self.optim_progress{
+                        *self.config.set_pid_conf() = *progress.lock().unwrap().get_best_gains();
+                    }
+                }
+            });
+
+            if let Some(progress) = &self.optim_progress{
+                let progress = progress.lock().unwrap();
+                ui.add(egui::ProgressBar::new(progress.get_iteration() as f32/progress.get_max_iter() as f32)
+                    .text(format!("iteration {} / {}", progress.get_iteration(), progress.get_max_iter())));
+                ui.label(format!("Best {} : {:.6}", self.optim.get_cost().get_name(), progress.get_best_cost()));
+                for (label, pid) in ["Angle", "Speed", "Torque"].iter().zip(progress.get_best_gains().iter()){
+                    ui.label(format!("{} : Kp = {:.6}, Ki = {:.6}, Kd = {:.6}", label, pid.get_kp(), pid.get_ki(), pid.get_kd()));
+                }
+            }
+        });
+        self.show_optimizer = open;
+    }
+}
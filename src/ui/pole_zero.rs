@@ -0,0 +1,54 @@
+use eframe::egui;
+use egui::plot::{Legend, Line, MarkerShape, Plot, PlotPoints, Points};
+use crate::analysis::locus::{self, Gain};
+use crate::control::TypePid;
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn pole_zero_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_pz;
+        egui::Window::new("Pole-zero map").open(&mut open).default_width(600.0).show(ctx, |ui|{
+            let available = self.analysis.get_available(&self.config);
+            Motorsim::analysis_loop_ui(&mut self.analysis_loop, available, ui);
+            let Some((poles, zeros)) = self.analysis.get_pole_zero(&self.config, self.analysis_loop) else {
+                return;
+            };
+
+            ui.horizontal(|ui|{
+                ui.label("Root locus of");
+                for (option, label) in [(TypePid::Pos, "Angle"), (TypePid::Vel, "Speed"), (TypePid::Trq, "Torque")]{
+                    ui.selectable_value(&mut self.locus_pid, option, label);
+                }
+                for option in [Gain::Kp, Gain::Ki, Gain::Kd]{
+                    ui.selectable_value(&mut self.locus_gain, option, option.get_name());
+                }
+                ui.label("from 0 to :");
+                ui.add(egui::DragValue::new(&mut self.locus_max).speed(0.01).max_decimals(6).clamp_range(0.0..=f64::MAX));
+            });
+
+            let locus = self.analysis.get_locus(&self.config, self.analysis_loop, self.locus_pid, self.locus_gain, self.locus_max);
+            let max_pole = poles.iter().map(locus::magnitude).fold(0.0, f64::max);
+            let critical = match locus.get_critical(){
+                Some(value) => format!("{:.6}", value),
+                None => "-".to_string(),
+            };
+            ui.label(format!("Max |pole| : {:.6}, current {} : {:.6}, first unstable {} : {}", max_pole, self.locus_gain.get_name(),
+                self.locus_gain.get_value(&self.config, self.locus_pid), self.locus_gain.get_name(), critical));
+
+            let to_points = |values: &Vec<nalgebra::Complex<f64>>| -> Vec<[f64; 2]>{ values.iter().map(|z| [z.re, z.im]).collect() };
+            let circle: Vec<[f64; 2]> = (0..=200).map(|i| {
+                let angle = 2.0*std::f64::consts::PI*i as f64/200.0;
+                [angle.cos(), angle.sin()]
+            }).collect();
+            let locus_points: Vec<[f64; 2]> = locus.get_locus().iter().flat_map(|(_, poles)| to_points(poles)).collect();
+
+            Plot::new("Pole-zero map").data_aspect(1.0).view_aspect(1.0).legend(Legend::default()).show(ui, |plot_ui|{
+                plot_ui.line(Line::new(PlotPoints::from(circle)).name("Unit circle"));
+                plot_ui.points(Points::new(PlotPoints::from(locus_points)).radius(1.0).name("Root locus"));
+                plot_ui.points(Points::new(PlotPoints::from(to_points(&poles))).shape(MarkerShape::Cross).radius(6.0).name("Poles"));
+                plot_ui.points(Points::new(PlotPoints::from(to_points(&zeros))).shape(MarkerShape::Circle).filled(false).radius(6.0).name("Zeros"));
+            });
+        });
+        self.show_pz = open;
+    }
+}
@@ -0,0 +1,77 @@
+use eframe::egui::{self,Ui};
+use crate::control::protection::{DriveState, Reaction};
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn protection_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_protection;
+        egui::Window::new("Protection").open(&mut open).show(ctx, |ui|{
+            let protection = self.config.set_protection_conf();
+            ui.horizontal(|ui|{
+                ui.checkbox(protection.set_enabled(), "Enabled");
+                ui.label("Reaction :");
+                let reaction = protection.set_reaction();
+                egui::ComboBox::from_id_source("protection_reaction").selected_text(reaction.get_name()).show_ui(ui, |ui|{
+                    for option in [Reaction::Coast, Reaction::Brake, Reaction::QuickStop]{
+                        ui.selectable_value(reaction, option, option.get_name());
+                    }
+                });
+                ui.label("Quick stop decel, rpm/s :");
+                ui.add(egui::DragValue::new(protection.set_quick_stop_decel()).speed(10.0).clamp_range(0.0..=f64::MAX));
+            });
+            ui.label("A limit of 0 disables its check, quick stop needs the speed loop and brakes otherwise");
+            egui::Grid::new("protection_limits").show(ui, |ui|{
+                let row = |ui: &mut Ui, label: &str, value: &mut f64|{
+                    ui.label(label);
+                    ui.add(egui::DragValue::new(value).speed(0.01).clamp_range(0.0..=f64::MAX));
+                    ui.end_row();
+                };
+                row(ui, "Stop timeout, sec", protection.set_stop_time());
+                row(ui, "Overcurrent, A", protection.set_overcurrent());
+                row(ui, "Overspeed, rpm", protection.set_overspeed());
+                row(ui, "Stall current, A", protection.set_stall_current());
+                row(ui, "Stall speed, rpm", protection.set_stall_speed());
+                row(ui, "Stall time, sec", protection.set_stall_time());
+                row(ui, "Following error, deg", protection.set_following_error());
+                row(ui, "Following time, sec", protection.set_following_time());
+                row(ui, "Max temperature, C", protection.set_max_temperature());
+                row(ui, "Ambient, C", protection.set_ambient());
+                row(ui, "Thermal resistance, K/W", protection.set_thermal_resistance());
+                row(ui, "Thermal capacity, J/K", protection.set_thermal_capacity());
+            });
+            if ui.add(egui::Button::new("Apply")).clicked(){
+                self.transmitter.send(self.config).unwrap();
+            }
+
+            ui.separator();
+            let mut status = self.protection.lock().unwrap();
+            let state = status.get_state();
+            let color = if state == DriveState::Running {ui.visuals().text_color()} else {egui::Color32::RED};
+            ui.horizontal(|ui|{
+                ui.colored_label(color, state.get_name());
+                ui.label(format!("Winding : {:.1} C", status.get_temperature()));
+                if ui.add_enabled(state != DriveState::Running, egui::Button::new("Reset fault")).clicked(){
+                    *status.set_reset_request() = true;
+                }
+                if ui.add(egui::Button::new("Clear history")).clicked(){
+                    status.clear_history();
+                }
+            });
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui|{
+                egui::Grid::new("protection_history").striped(true).show(ui, |ui|{
+                    ui.label("Time, sec");
+                    ui.label("Trip");
+                    ui.label("Value");
+                    ui.end_row();
+                    for record in status.get_history().iter().rev(){
+                        ui.label(format!("{:.4}", record.get_time()));
+                        ui.label(record.get_trip().get_name());
+                        ui.label(format!("{:.3}", record.get_value()));
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+        self.show_protection = open;
+    }
+}
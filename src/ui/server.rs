@@ -0,0 +1,70 @@
+use eframe::egui;
+use crate::control::server::{self, ServerMode};
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn server_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_server;
+        egui::Window::new("Plant server").open(&mut open).show(ctx, |ui|{
+            ui.label("Serves the motor over UDP on 127.0.0.1 to an external controller, the protocol is in the README");
+            let running = self.server_status.as_ref().is_some_and(|status| status.lock().unwrap().get_running());
+            ui.add_enabled_ui(!running, |ui|{
+                ui.horizontal(|ui|{
+                    ui.label("Port :");
+                    ui.add(egui::DragValue::new(self.server.set_port()));
+                    let mode = self.server.set_mode();
+                    egui::ComboBox::from_id_source("server_mode").selected_text(mode.get_name()).show_ui(ui, |ui|{
+                        for option in [ServerMode::LockStep, ServerMode::RealTime]{
+                            ui.selectable_value(mode, option, option.get_name());
+                        }
+                    });
+                });
+            });
+            ui.horizontal(|ui|{
+                if ui.add_enabled(!running, egui::Button::new("Start")).clicked(){
+                    self.server_status = Some(server::run(self.config, self.server));
+                }
+                if ui.add_enabled(running, egui::Button::new("Stop")).clicked(){
+                    if let Some(status) = &self.server_status{
+                        *status.lock().unwrap().set_stop_flag() = true;
+                    }
+                }
+            });
+
+            let Some(status) = &self.server_status else {
+                return;
+            };
+            let status = status.lock().unwrap();
+            if let Some(error) = status.get_error(){
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            egui::Grid::new("server_grid").show(ui, |ui|{
+                ui.label("Client");
+                ui.label(status.get_client().map_or("-".to_string(), |client| client.to_string()));
+                ui.end_row();
+                ui.label("Commands / invalid");
+                ui.label(format!("{} / {}", status.get_commands(), status.get_invalid()));
+                ui.end_row();
+                ui.label("Plant time, sec");
+                ui.label(format!("{:.4}", status.get_time()));
+                ui.end_row();
+                ui.label("Voltage, V");
+                ui.label(format!("{:.3}", status.get_voltage()));
+                ui.end_row();
+                ui.label("Position, deg");
+                ui.label(format!("{:.3}", status.get_position()));
+                ui.end_row();
+                ui.label("Velocity, rpm");
+                ui.label(format!("{:.3}", status.get_velocity()));
+                ui.end_row();
+                ui.label("Current, A");
+                ui.label(format!("{:.3}", status.get_current()));
+                ui.end_row();
+            });
+            if status.get_running(){
+                ctx.request_repaint();
+            }
+        });
+        self.show_server = open;
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use eframe::egui;
+use egui::plot::{Legend, Line, Plot, PlotPoints, Points, Polygon};
+use crate::control::ControlType;
+use crate::control::sweep::{self, SweepCase, SweepParameter, SWEEP_METRICS};
+use super::{Motorsim, LIVE_ONLY};
+
+impl Motorsim{
+    pub fn sweep_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_sweep;
+        egui::Window::new("Parameter sweep").open(&mut open).default_width(800.0).show(ctx, |ui|{
+            let available = match self.config.get_controller_conf().get_control_option(){
+                ControlType::Pos => [true, false, false],
+                _ => [true, true, true],
+            };
+            Motorsim::analysis_loop_ui(&mut self.analysis_loop, available, ui);
+
+            ui.checkbox(self.sweep.set_two_dimensional(), "Two parameters");
+            let axes = if self.sweep.get_two_dimensional() {2} else {1};
+            egui::Grid::new("sweep_axes").show(ui, |ui|{
+                for (i, axis) in self.sweep.set_axes().iter_mut().take(axes).enumerate(){
+                    let parameter = axis.set_parameter();
+                    egui::ComboBox::from_id_source(("sweep_parameter", i)).selected_text(parameter.get_name()).show_ui(ui, |ui|{
+                        for option in SweepParameter::all(){
+                            ui.selectable_value(parameter, option, option.get_name());
+                        }
+                    });
+                    ui.label("From :");
+                    ui.add(egui::DragValue::new(axis.set_start()).speed(0.01).max_decimals(6));
+                    ui.label("To :");
+                    ui.add(egui::DragValue::new(axis.set_end()).speed(0.01).max_decimals(6));
+                    ui.label("Points :");
+                    ui.add(egui::DragValue::new(axis.set_points()).clamp_range(1..=1000));
+                    ui.end_row();
+                }
+            });
+
+            let running = self.sweep_progress.as_ref().is_some_and(|progress| progress.lock().unwrap().get_running());
+            ui.horizontal(|ui|{
+                ui.label("Threads :");
+                ui.add(egui::DragValue::new(self.sweep.set_threads()).clamp_range(1..=256));
+                let headless = self.registry.get_headless(*self.config.get_controller_conf().get_control_option());
+                if ui.add_enabled(!running && headless, egui::Button::new(format!("Run {} cases", self.sweep.get_cases()))).on_disabled_hover_text(LIVE_ONLY).clicked(){
+                    let mut config = self.config;
+                    *config.set_controller_conf().set_calib_option() = Some(self.analysis_loop);
+                    *config.set_excitation_conf().set_enabled() = false;
+                    self.sweep_progress = Some(sweep::run(config, self.sweep, Arc::clone(&self.registry)));
+                    self.sweep_status.clear();
+                }
+                if ui.add_enabled(running, egui::Button::new("Stop")).clicked(){
+                    if let Some(progress) = &self.sweep_progress{
+                        *progress.lock().unwrap().set_stop_flag() = true;
+                    }
+                }
+            });
+
+            let Some(progress) = &self.sweep_progress else {
+                return;
+            };
+            let progress = progress.lock().unwrap();
+            let sweep = *progress.get_sweep();
+            ui.add(egui::ProgressBar::new(progress.get_done() as f32/sweep.get_cases() as f32)
+                .text(format!("case {} / {}", progress.get_done(), sweep.get_cases())));
+
+            ui.horizontal(|ui|{
+                ui.label("CSV file :");
+                ui.text_edit_singleline(&mut self.sweep_path);
+                if ui.add(egui::Button::new("Export")).clicked(){
+                    self.sweep_status = match std::fs::write(&self.sweep_path, progress.to_csv()){
+                        Ok(()) => format!("Saved to {}", self.sweep_path),
+                        Err(error) => error.to_string(),
+                    };
+                }
+                ui.label(&self.sweep_status);
+            });
+
+            // table columns: swept values, then SWEEP_METRICS
+            let values = if sweep.get_two_dimensional() {2} else {1};
+            let column = |case: &SweepCase, column: usize| if column < values {Some(case.get_values()[column])} else {case.get_metric(SWEEP_METRICS[column - values])};
+            let mut cases: Vec<SweepCase> = progress.get_cases().iter().flatten().copied().collect();
+            let (sort_column, ascending) = self.sweep_sort;
+            cases.sort_by(|a, b| match (column(a, sort_column), column(b, sort_column)){
+                (Some(a), Some(b)) => if ascending {a.total_cmp(&b)} else {b.total_cmp(&a)},
+                (a, b) => a.is_none().cmp(&b.is_none()),
+            });
+
+            let mut headers: Vec<String> = sweep.get_axes().iter().take(values).map(|axis| axis.get_parameter().get_name()).collect();
+            headers.extend(SWEEP_METRICS.iter().map(|metric| metric.get_name().to_string()));
+            egui::ScrollArea::both().max_height(250.0).show(ui, |ui|{
+                egui::Grid::new("sweep_table").striped(true).show(ui, |ui|{
+                    for (i, header) in headers.iter().enumerate(){
+                        let arrow = match self.sweep_sort{
+                            (column, true) if column == i => " ^",
+                            (column, false) if column == i => " v",
+                            _ => "",
+                        };
+                        if ui.add(egui::Button::new(format!("{}{}", header, arrow)).frame(false)).clicked(){
+                            self.sweep_sort = (i, if self.sweep_sort.0 == i {!self.sweep_sort.1} else {true});
+                        }
+                    }
+                    ui.end_row();
+                    for case in cases.iter(){
+                        for i in 0..headers.len(){
+                            match (column(case, i), case.get_error()){
+                                (Some(value), _) => ui.label(format!("{:.5}", value)),
+                                (None, Some(error)) => ui.label("invalid").on_hover_text(error),
+                                (None, None) => ui.label("-"),
+                            };
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+
+            let metric = &mut self.sweep_metric;
+            egui::ComboBox::from_id_source("sweep_metric").selected_text(metric.get_name()).show_ui(ui, |ui|{
+                for option in SWEEP_METRICS{
+                    ui.selectable_value(metric, option, option.get_name());
+                }
+            });
+            let metric = self.sweep_metric;
+            let points: Vec<([f64; 2], f64)> = cases.iter().filter_map(|case| case.get_metric(metric).map(|value| (case.get_values(), value))).collect();
+            let min = points.iter().map(|(_, value)| *value).fold(f64::MAX, f64::min);
+            let max = points.iter().map(|(_, value)| *value).fold(f64::MIN, f64::max);
+            let [first, second] = *sweep.get_axes();
+            if sweep.get_two_dimensional(){
+                ui.label(format!("{} : blue {:.5} to red {:.5}", metric.get_name(), min, max));
+                Plot::new("Sweep heatmap").height(ui.available_height().max(300.0)).show(ui, |plot_ui|{
+                    let (dx, dy) = (first.get_step().abs()/2.0, second.get_step().abs()/2.0);
+                    for ([x, y], value) in points.iter(){
+                        let ratio = if max > min {(value - min)/(max - min)} else {0.5};
+                        let color = egui::Color32::from_rgb((255.0*ratio) as u8, 64, (255.0*(1.0 - ratio)) as u8);
+                        let cell = vec![[x - dx, y - dy], [x + dx, y - dy], [x + dx, y + dy], [x - dx, y + dy]];
+                        plot_ui.polygon(Polygon::new(PlotPoints::from(cell)).color(color).fill_alpha(1.0).width(0.0)
+                            .name(format!("{} = {:.5}, {} = {:.5}", first.get_parameter().get_name(), x, second.get_parameter().get_name(), y)));
+                    }
+                });
+            } else {
+                let mut line: Vec<[f64; 2]> = points.iter().map(|([x, _], value)| [*x, *value]).collect();
+                line.sort_by(|a, b| a[0].total_cmp(&b[0]));
+                Plot::new("Sweep metric").height(ui.available_height().max(300.0)).legend(Legend::default()).show(ui, |plot_ui|{
+                    plot_ui.line(Line::new(PlotPoints::from(line.clone())).name(metric.get_name()));
+                    plot_ui.points(Points::new(PlotPoints::from(line)).radius(3.0));
+                });
+            }
+        });
+        self.show_sweep = open;
+    }
+}
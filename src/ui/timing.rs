@@ -0,0 +1,40 @@
+use eframe::egui;
+use crate::control::montecarlo::Distribution;
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn timing_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_timing;
+        egui::Window::new("Timing emulation").open(&mut open).show(ctx, |ui|{
+            ui.label("Delays in control periods, the control law runs with the nominal period");
+            let timing = self.config.set_timing_conf();
+            ui.checkbox(timing.set_enabled(), "Enabled");
+            egui::Grid::new("timing_grid").show(ui, |ui|{
+                ui.label("Computation delay");
+                ui.add(egui::DragValue::new(timing.set_computation_delay()).speed(0.01).clamp_range(0.0..=100.0));
+                ui.end_row();
+                ui.label("Sensor delay");
+                ui.add(egui::DragValue::new(timing.set_sensor_delay()).clamp_range(0..=1000));
+                ui.end_row();
+                ui.label("Jitter, part of the period");
+                ui.add(egui::DragValue::new(timing.set_jitter()).speed(0.001).clamp_range(0.0..=0.9));
+                ui.end_row();
+                ui.label("Distribution");
+                let distribution = timing.set_distribution();
+                egui::ComboBox::from_id_source("timing_distribution").selected_text(distribution.get_name()).show_ui(ui, |ui|{
+                    for option in [Distribution::Uniform, Distribution::Normal]{
+                        ui.selectable_value(distribution, option, option.get_name());
+                    }
+                });
+                ui.end_row();
+                ui.label("Seed");
+                ui.add(egui::DragValue::new(timing.set_seed()));
+                ui.end_row();
+            });
+            if ui.add(egui::Button::new("Apply")).clicked(){
+                self.transmitter.send(self.config).unwrap();
+            }
+        });
+        self.show_timing = open;
+    }
+}
@@ -0,0 +1,44 @@
+use eframe::egui;
+use egui::plot::{Bar, BarChart, Legend, Plot};
+use crate::control::montecarlo;
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn timing_stats_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_timing_stats;
+        egui::Window::new("Timing diagnostics").open(&mut open).show(ctx, |ui|{
+            let stats = self.timing_stats.lock().unwrap();
+            let nominal = stats.get_nominal();
+            let us = |value: f64| format!("{:.1} us", value*1e6);
+            ui.label(format!("Rate : {:.1} hz of {:.1} hz", stats.get_rate(), if nominal > 0.0 {1.0/nominal} else {0.0}));
+            egui::Grid::new("timing_stats").show(ui, |ui|{
+                ui.label("Period, mean / max");
+                ui.label(format!("{} / {}", us(stats.get_mean_period()), us(stats.get_max_period())));
+                ui.end_row();
+                ui.label("Jitter, std");
+                ui.label(us(stats.get_jitter()));
+                ui.end_row();
+                ui.label("Step wall time, mean / max");
+                ui.label(format!("{} / {}", us(stats.get_mean_step_time()), us(stats.get_max_step_time())));
+                ui.end_row();
+                ui.label("Load, wall time");
+                ui.label(format!("{:.1} %", if nominal > 0.0 {100.0*stats.get_mean_step_time()/nominal} else {0.0}));
+                ui.end_row();
+                ui.label("Overruns");
+                ui.label(format!("{} of {} steps", stats.get_overruns(), stats.get_steps()));
+                ui.end_row();
+            });
+            ui.label("The controller thread only runs between Start and Stop, statistics restart with every run and applied config. Step times are wall clock and include preemption");
+
+            // deviation from the nominal period
+            let deviations: Vec<f64> = stats.get_periods().iter().map(|period| (period - nominal)*1e6).collect();
+            drop(stats);
+            let (width, bins) = montecarlo::histogram(&deviations, 40);
+            let chart = BarChart::new(bins.iter().map(|[center, count]| Bar::new(*center, *count).width(width)).collect()).name("Period deviation, us");
+            Plot::new("Timing histogram").height(250.0).legend(Legend::default()).show(ui, |plot_ui|{
+                plot_ui.bar_chart(chart);
+            });
+        });
+        self.show_timing_stats = open;
+    }
+}
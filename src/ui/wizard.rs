@@ -0,0 +1,43 @@
+use eframe::egui;
+use crate::control::analytic::{self, SpeedTuning};
+use super::Motorsim;
+
+impl Motorsim{
+    pub fn wizard_window(&mut self, ctx: &egui::Context){
+        let mut open = self.show_wizard;
+        egui::Window::new("Tuning wizard").open(&mut open).show(ctx, |ui|{
+            egui::Grid::new("wizard_bandwidths").show(ui, |ui|{
+                ui.label("Torque bandwidth, rad/s :");
+                ui.add(egui::DragValue::new(self.analytic.set_trq_bandwidth()).speed(1.0).clamp_range(0.1..=1.0e6));
+                ui.end_row();
+                ui.label("Speed bandwidth, rad/s :");
+                ui.add(egui::DragValue::new(self.analytic.set_vel_bandwidth()).speed(1.0).clamp_range(0.1..=1.0e6));
+                ui.end_row();
+                ui.label("Angle bandwidth, rad/s :");
+                ui.add(egui::DragValue::new(self.analytic.set_pos_bandwidth()).speed(0.1).clamp_range(0.1..=1.0e6));
+                ui.end_row();
+                ui.label("Speed loop :");
+                let speed_tuning = self.analytic.set_speed_tuning();
+                egui::ComboBox::from_id_source("speed_tuning").selected_text(speed_tuning.get_name()).show_ui(ui, |ui|{
+                    for option in [SpeedTuning::SymmetricOptimum, SpeedTuning::TechnicalOptimum]{
+                        ui.selectable_value(speed_tuning, option, option.get_name());
+                    }
+                });
+                ui.end_row();
+            });
+
+            for warning in self.analytic.get_warnings(self.config.get_controller_conf().get_frequency()){
+                ui.colored_label(egui::Color32::YELLOW, warning);
+            }
+
+            let gains = analytic::analytic_gains(self.config.get_motor_conf(), &self.analytic);
+            for (label, pid) in ["Angle", "Speed", "Torque"].iter().zip(gains.iter()){
+                ui.label(format!("{} : Kp = {:.6}, Ki = {:.6}, Kd = {:.6}", label, pid.get_kp(), pid.get_ki(), pid.get_kd()));
+            }
+            if ui.add(egui::Button::new("Apply")).clicked(){
+                *self.config.set_pid_conf() = gains;
+            }
+        });
+        self.show_wizard = open;
+    }
+}